@@ -0,0 +1,286 @@
+//! How monitors touch each other in the virtual desktop. Pure geometry on
+//! pixel rectangles, no Win32, so the calibration order can be reasoned
+//! about (and tested) independently of the overlay.
+
+use crate::monitors::Monitor;
+use serde::{Deserialize, Serialize};
+
+/// A monitor's desktop rectangle in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+impl Rect {
+    pub fn of(m: &Monitor) -> Self {
+        Rect {
+            x: m.position_x,
+            y: m.position_y,
+            w: m.resolution_x as i32,
+            h: m.resolution_y as i32,
+        }
+    }
+
+    fn right(&self) -> i32 {
+        self.x + self.w
+    }
+
+    fn bottom(&self) -> i32 {
+        self.y + self.h
+    }
+}
+
+/// Side of the first monitor of a pair that faces the second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Side {
+    Left,
+    Right,
+    Top,
+    Bottom,
+}
+
+impl Side {
+    /// True when the monitors sit side by side (a vertical shared edge).
+    pub fn is_horizontal(self) -> bool {
+        matches!(self, Side::Left | Side::Right)
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Side::Left => Side::Right,
+            Side::Right => Side::Left,
+            Side::Top => Side::Bottom,
+            Side::Bottom => Side::Top,
+        }
+    }
+}
+
+/// How two monitors meet in the virtual desktop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Contact {
+    /// They share a stretch of edge
+    Edge,
+    /// Only their corners touch
+    Corner,
+    /// They don't touch at all
+    Apart,
+}
+
+/// Relation between monitors `a` and `b`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adjacency {
+    pub a: usize,
+    pub b: usize,
+    pub contact: Contact,
+    /// Side of `a` facing `b`; for diagonal neighbours, the axis with the
+    /// larger separation
+    pub side: Side,
+    /// Pixels both monitors span along `side`; the shared edge length when
+    /// `contact` is `Edge`
+    pub overlap: i32,
+    /// Pixel distance between the closest points of the two rectangles
+    pub separation: f64,
+}
+
+impl Adjacency {
+    /// The same relation seen from `b`.
+    pub fn reversed(&self) -> Self {
+        Adjacency {
+            a: self.b,
+            b: self.a,
+            side: self.side.opposite(),
+            ..*self
+        }
+    }
+}
+
+/// Relate two rectangles. Returns `None` when they overlap, which only
+/// happens for mirrored displays.
+pub fn relate(a: usize, ra: Rect, b: usize, rb: Rect) -> Option<Adjacency> {
+    // Gaps along each axis; negative means the projections overlap
+    let dx = (rb.x - ra.right()).max(ra.x - rb.right());
+    let dy = (rb.y - ra.bottom()).max(ra.y - rb.bottom());
+    if dx < 0 && dy < 0 {
+        return None;
+    }
+
+    let (side, overlap) = if dx >= dy {
+        let side = if rb.x >= ra.right() { Side::Right } else { Side::Left };
+        (side, (-dy).max(0))
+    } else {
+        let side = if rb.y >= ra.bottom() { Side::Bottom } else { Side::Top };
+        (side, (-dx).max(0))
+    };
+
+    let contact = if dx.max(dy) > 0 {
+        Contact::Apart
+    } else if overlap > 0 {
+        Contact::Edge
+    } else {
+        Contact::Corner
+    };
+
+    let sx = dx.max(0) as f64;
+    let sy = dy.max(0) as f64;
+
+    Some(Adjacency {
+        a,
+        b,
+        contact,
+        side,
+        overlap,
+        separation: (sx * sx + sy * sy).sqrt(),
+    })
+}
+
+/// Relations for every pair of the given monitors (`a < b` by position in
+/// the input). Overlapping pairs are left out.
+pub fn build_graph(rects: &[(usize, Rect)]) -> Vec<Adjacency> {
+    let mut edges = Vec::new();
+    for (i, &(a, ra)) in rects.iter().enumerate() {
+        for &(b, rb) in &rects[i + 1..] {
+            if let Some(adj) = relate(a, ra, b, rb) {
+                edges.push(adj);
+            }
+        }
+    }
+    edges
+}
+
+/// Preference for a tree edge: shared edges by length, then corner contacts,
+/// then the closest non-touching pair.
+fn edge_priority(adj: &Adjacency) -> (u8, f64) {
+    match adj.contact {
+        Contact::Edge => (2, adj.overlap as f64),
+        Contact::Corner => (1, 0.0),
+        Contact::Apart => (0, -adj.separation),
+    }
+}
+
+/// Spanning tree over the given monitors, grown from `root`, that maximises
+/// shared edge length (Prim's algorithm). Edges come back in the order they
+/// were added, each oriented with `a` as the newly added monitor and `b` as
+/// the one already in the tree. Corner and apart links are only used when a
+/// monitor has no shared edge with the tree. Monitors that overlap all the
+/// others can't be linked at all; they come back as the error.
+pub fn spanning_tree(rects: &[(usize, Rect)], root: usize) -> Result<Vec<Adjacency>, Vec<usize>> {
+    let graph = build_graph(rects);
+    let mut in_tree = vec![root];
+    let mut tree = Vec::new();
+
+    while in_tree.len() < rects.len() {
+        let best = graph
+            .iter()
+            .filter_map(|adj| {
+                match (in_tree.contains(&adj.a), in_tree.contains(&adj.b)) {
+                    (true, false) => Some(adj.reversed()),
+                    (false, true) => Some(*adj),
+                    _ => None,
+                }
+            })
+            .fold(None::<Adjacency>, |best, adj| match best {
+                Some(b) if edge_priority(&b) >= edge_priority(&adj) => Some(b),
+                _ => Some(adj),
+            });
+
+        match best {
+            Some(adj) => {
+                in_tree.push(adj.a);
+                tree.push(adj);
+            }
+            // Remaining monitors overlap the tree; nothing to pair them with
+            None => {
+                let stranded = rects
+                    .iter()
+                    .map(|&(idx, _)| idx)
+                    .filter(|idx| !in_tree.contains(idx))
+                    .collect();
+                return Err(stranded);
+            }
+        }
+    }
+
+    Ok(tree)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    #[test]
+    fn corner_only_contact() {
+        let adj = relate(0, rect(0, 0, 1920, 1080), 1, rect(1920, 1080, 1920, 1080)).unwrap();
+        assert_eq!(adj.contact, Contact::Corner);
+        assert_eq!(adj.overlap, 0);
+        assert_eq!(adj.separation, 0.0);
+    }
+
+    #[test]
+    fn shared_edge_and_side() {
+        let adj = relate(0, rect(0, 0, 2560, 1440), 1, rect(2560, 400, 1920, 1080)).unwrap();
+        assert_eq!(adj.contact, Contact::Edge);
+        assert_eq!(adj.side, Side::Right);
+        assert_eq!(adj.overlap, 1040);
+        assert_eq!(adj.reversed().side, Side::Left);
+    }
+
+    #[test]
+    fn mirrored_rectangles_are_not_related() {
+        let r = rect(0, 0, 1920, 1080);
+        assert!(relate(0, r, 1, r).is_none());
+        assert!(build_graph(&[(0, r), (1, r)]).is_empty());
+    }
+
+    #[test]
+    fn tree_prefers_longest_shared_edge() {
+        // Monitor 2 sits below both 0 and 1 but shares more edge with 1
+        let rects = [
+            (0, rect(0, 0, 1920, 1080)),
+            (1, rect(1920, 0, 1920, 1080)),
+            (2, rect(1200, 1080, 1920, 1080)),
+        ];
+        let tree = spanning_tree(&rects, 0).unwrap();
+        assert_eq!(tree.len(), 2);
+        assert_eq!((tree[0].a, tree[0].b), (1, 0));
+        assert_eq!((tree[1].a, tree[1].b), (2, 1));
+        assert_eq!(tree[1].side, Side::Top);
+        assert_eq!(tree[1].overlap, 1200);
+    }
+
+    #[test]
+    fn disconnected_monitor_joins_by_closest_gap() {
+        // Monitor 2 touches nothing; it is linked to whichever monitor is
+        // nearest, after the shared edge between 0 and 1
+        let rects = [
+            (0, rect(0, 0, 1920, 1080)),
+            (1, rect(1920, 0, 1920, 1080)),
+            (2, rect(4200, 100, 1920, 1080)),
+        ];
+        let tree = spanning_tree(&rects, 0).unwrap();
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree[0].contact, Contact::Edge);
+        assert_eq!((tree[1].a, tree[1].b), (2, 1));
+        assert_eq!(tree[1].contact, Contact::Apart);
+        assert_eq!(tree[1].separation, 360.0);
+    }
+
+    #[test]
+    fn overlapping_monitor_is_reported() {
+        // Monitor 2 covers parts of both others, so it can't be paired
+        let rects = [
+            (0, rect(0, 0, 1920, 1080)),
+            (1, rect(1920, 0, 1920, 1080)),
+            (2, rect(1500, 200, 1280, 720)),
+        ];
+        assert_eq!(spanning_tree(&rects, 0).unwrap_err(), vec![2]);
+    }
+}
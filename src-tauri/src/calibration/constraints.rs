@@ -0,0 +1,356 @@
+//! Facts about the desk declared before calibration: monitors standing on
+//! one surface, hanging side by side on a dual arm, or pushed together. A
+//! fact that pins down part of a pair's measurement lets that step be
+//! skipped; after solving, every fact is checked against the layout and
+//! those the measurements contradict are reported.
+
+use super::flow::{self, CalibrationPair};
+use super::solve::LayoutSolution;
+use super::PairMeasurement;
+use crate::monitors::Monitor;
+use serde::{Deserialize, Serialize};
+
+/// A known fact about how monitors are set up.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Constraint {
+    /// Bottom edges of the housings are level, as for monitors standing on
+    /// the same desk. Without bezels the lit areas' bottoms are used.
+    BottomsLevel { monitors: Vec<usize> },
+    /// Centres are level, as for monitors on one dual arm
+    SameHeight { monitors: Vec<usize> },
+    /// The housings touch across the shared edge, leaving no air between
+    /// the bezels. Needs both monitors' bezels.
+    Touching { monitors: [usize; 2] },
+}
+
+impl Constraint {
+    fn monitors(&self) -> &[usize] {
+        match self {
+            Constraint::BottomsLevel { monitors } | Constraint::SameHeight { monitors } => monitors,
+            Constraint::Touching { monitors } => monitors,
+        }
+    }
+
+    fn involves(&self, a: usize, b: usize) -> bool {
+        self.monitors().contains(&a) && self.monitors().contains(&b)
+    }
+}
+
+/// A declared fact the solved layout doesn't bear out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConstraintViolation {
+    /// Index into the declared constraints
+    pub constraint: usize,
+    /// The two monitors that disagree; for a group, its first monitor and
+    /// the one out of line
+    pub monitors: [usize; 2],
+    /// How far off the fact is, mm: the second monitor's bottom or centre
+    /// below the first's, or the air between touching housings
+    pub error_mm: f64,
+}
+
+/// Disagreement below this is left unreported, whatever the error bars say.
+const TOLERANCE_MM: f64 = 3.0;
+
+/// Check that every constraint names selected monitors and has what it
+/// needs.
+pub fn validate(
+    monitors: &[Monitor],
+    selected: &[usize],
+    constraints: &[Constraint],
+) -> Result<(), String> {
+    for c in constraints {
+        let ids = c.monitors();
+        if let Some(idx) = ids.iter().find(|idx| !selected.contains(idx)) {
+            return Err(format!("Display {} in a declared fact isn't selected", idx + 1));
+        }
+        if ids.len() < 2 || (1..ids.len()).any(|i| ids[..i].contains(&ids[i])) {
+            return Err("A declared fact needs at least two different displays".to_string());
+        }
+        if let Constraint::Touching { monitors: [a, b] } = c {
+            if let Some(idx) = [a, b].into_iter().find(|&&i| monitors[i].bezel_mm.is_none()) {
+                return Err(format!(
+                    "Display {} has no bezel sizes; they are needed to declare it touching display {}",
+                    idx + 1,
+                    if idx == a { b + 1 } else { a + 1 }
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Height of `m` in millimetres at its known size.
+fn height_mm(m: &Monitor) -> Option<f64> {
+    Some(m.resolution_y as f64 / m.effective_ppi()? * 25.4)
+}
+
+/// Along-edge offset of a side-by-side pair that the constraints fix, in the
+/// form of `TapeMeasurement::offset_mm`: how far the unbound monitor's top
+/// edge sits below the bound one's. Needs both monitors' sizes.
+pub fn declared_offset_mm(
+    monitors: &[Monitor],
+    pair: &CalibrationPair,
+    constraints: &[Constraint],
+) -> Option<f64> {
+    if !pair.bind_horizontal {
+        return None;
+    }
+    let (mu, mb) = (&monitors[pair.unbound], &monitors[pair.bound]);
+    let (hu, hb) = (height_mm(mu)?, height_mm(mb)?);
+    constraints
+        .iter()
+        .filter(|c| c.involves(pair.unbound, pair.bound))
+        .find_map(|c| match c {
+            Constraint::BottomsLevel { .. } => {
+                let (bu, bb) = match (mu.bezel_mm, mb.bezel_mm) {
+                    (Some(u), Some(b)) => (u[1], b[1]),
+                    _ => (0.0, 0.0),
+                };
+                Some(hb + bb - hu - bu)
+            }
+            Constraint::SameHeight { .. } => Some((hb - hu) / 2.0),
+            Constraint::Touching { .. } => None,
+        })
+}
+
+/// Gap between the lit areas of a pair declared touching: the two facing
+/// bezels, in millimetres.
+pub fn declared_gap_mm(
+    monitors: &[Monitor],
+    pair: &CalibrationPair,
+    constraints: &[Constraint],
+) -> Option<f64> {
+    constraints
+        .iter()
+        .filter(|c| matches!(c, Constraint::Touching { .. }))
+        .find(|c| c.involves(pair.unbound, pair.bound))?;
+    let (mu, mb) = (&monitors[pair.unbound], &monitors[pair.bound]);
+    let (bu, bb) = (mu.bezel_mm?, mb.bezel_mm?);
+    // Indices are top, bottom, left, right
+    let unbound_first = if pair.bind_horizontal {
+        mu.position_x < mb.position_x
+    } else {
+        mu.position_y < mb.position_y
+    };
+    let (near_u, near_b) = match (pair.bind_horizontal, unbound_first) {
+        (true, true) => (3, 2),
+        (true, false) => (2, 3),
+        (false, true) => (1, 0),
+        (false, false) => (0, 1),
+    };
+    Some(bu[near_u] + bb[near_b])
+}
+
+/// Solved extent of one monitor in reference pixels, with its one-sigma
+/// position uncertainty.
+struct Placed {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+    sigma: [f64; 2],
+}
+
+/// Constraints the solved layout contradicts by more than its error bars
+/// and `TOLERANCE_MM`. Monitors without a measured position, and layouts
+/// with no known size to convert to millimetres, aren't checked.
+pub fn check(
+    monitors: &[Monitor],
+    solution: &LayoutSolution,
+    measurements: &[PairMeasurement],
+    constraints: &[Constraint],
+) -> Vec<ConstraintViolation> {
+    // Millimetres per reference pixel, from any monitor of known size
+    let Some(mm_per_px) = measurements
+        .iter()
+        .flat_map(|m| [m.unbound, m.bound])
+        .find_map(|idx| Some(25.4 * solution.scales[idx] / monitors[idx].effective_ppi()?))
+    else {
+        return Vec::new();
+    };
+    let placed = |idx: usize| {
+        let positioned = measurements
+            .iter()
+            .filter(|m| m.unbound == idx || m.bound == idx)
+            .any(|m| m.method.measures_position());
+        let m = &monitors[idx];
+        let s = solution.scales[idx];
+        positioned.then(|| Placed {
+            x: solution.x[idx],
+            y: solution.y[idx],
+            w: m.resolution_x as f64 / s,
+            h: m.resolution_y as f64 / s,
+            sigma: [solution.x_sigma[idx], solution.y_sigma[idx]],
+        })
+    };
+
+    let mut violations = Vec::new();
+    for (index, c) in constraints.iter().enumerate() {
+        let ids = c.monitors();
+        let first = ids[0];
+        let Some(a) = placed(first) else { continue };
+        for &other in &ids[1..] {
+            let Some(b) = placed(other) else { continue };
+            let (error_px, sigma_px) = match c {
+                Constraint::BottomsLevel { .. } => {
+                    let bezels = [first, other].map(|i| monitors[i].bezel_mm.map(|z| z[1]));
+                    let [ba, bb] = match bezels {
+                        [Some(ba), Some(bb)] => [ba, bb],
+                        _ => [0.0, 0.0],
+                    };
+                    let below = b.y + b.h - (a.y + a.h) + (bb - ba) / mm_per_px;
+                    (below, a.sigma[1].hypot(b.sigma[1]))
+                }
+                Constraint::SameHeight { .. } => {
+                    let below = b.y + b.h / 2.0 - (a.y + a.h / 2.0);
+                    (below, a.sigma[1].hypot(b.sigma[1]))
+                }
+                Constraint::Touching { .. } => {
+                    let (ma, mb) = (&monitors[first], &monitors[other]);
+                    let pair = CalibrationPair {
+                        unbound: other,
+                        bound: first,
+                        bind_horizontal: flow::determine_bind_horizontal(ma, mb),
+                        contact: super::adjacency::Contact::Edge,
+                    };
+                    let Some(bezels_mm) = declared_gap_mm(monitors, &pair, constraints) else {
+                        continue;
+                    };
+                    let (gap, axis) = if pair.bind_horizontal {
+                        ((b.x - (a.x + a.w)).max(a.x - (b.x + b.w)), 0)
+                    } else {
+                        ((b.y - (a.y + a.h)).max(a.y - (b.y + b.h)), 1)
+                    };
+                    (gap - bezels_mm / mm_per_px, a.sigma[axis].hypot(b.sigma[axis]))
+                }
+            };
+            let error_mm = error_px * mm_per_px;
+            if error_mm.abs() > TOLERANCE_MM.max(3.0 * sigma_px * mm_per_px) {
+                violations.push(ConstraintViolation {
+                    constraint: index,
+                    monitors: [first, other],
+                    error_mm,
+                });
+            }
+        }
+    }
+    violations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calibration::adjacency::Contact;
+    use crate::calibration::PairMethod;
+
+    /// A 27" 1440p monitor with a 23.8" 1080p one to its right, tops level
+    /// in the virtual desktop.
+    fn desk(bezels: Option<[[f64; 4]; 2]>) -> Vec<Monitor> {
+        let mut monitors = vec![
+            Monitor::for_test(0, [0, 0], [2560, 1440], Some([597, 336])),
+            Monitor::for_test(1, [2560, 0], [1920, 1080], Some([527, 296])),
+        ];
+        if let Some(bezels) = bezels {
+            for (m, b) in monitors.iter_mut().zip(bezels) {
+                m.bezel_mm = Some(b);
+            }
+        }
+        monitors
+    }
+
+    fn right_of_reference() -> CalibrationPair {
+        CalibrationPair { unbound: 1, bound: 0, bind_horizontal: true, contact: Contact::Edge }
+    }
+
+    fn offset(monitors: &[Monitor], constraint: Constraint) -> Option<f64> {
+        declared_offset_mm(monitors, &right_of_reference(), &[constraint])
+    }
+
+    #[test]
+    fn bottoms_level_offsets_by_the_height_difference() {
+        let monitors = desk(None);
+        let [hb, hu] = [0, 1].map(|i| height_mm(&monitors[i]).unwrap());
+        let declared = offset(&monitors, Constraint::BottomsLevel { monitors: vec![0, 1] });
+        assert!((declared.unwrap() - (hb - hu)).abs() < 1e-9);
+        assert!((hb - hu - 40.0).abs() < 0.5);
+    }
+
+    #[test]
+    fn bottoms_level_counts_the_bottom_bezels() {
+        // Top, bottom, left, right
+        let monitors = desk(Some([[10.0, 12.0, 8.0, 8.0], [9.0, 20.0, 9.0, 9.0]]));
+        let [hb, hu] = [0, 1].map(|i| height_mm(&monitors[i]).unwrap());
+        let declared = offset(&monitors, Constraint::BottomsLevel { monitors: vec![0, 1] });
+        assert!((declared.unwrap() - (hb + 12.0 - hu - 20.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn same_height_offsets_by_half_the_height_difference() {
+        let monitors = desk(None);
+        let [hb, hu] = [0, 1].map(|i| height_mm(&monitors[i]).unwrap());
+        let declared = offset(&monitors, Constraint::SameHeight { monitors: vec![0, 1] });
+        assert!((declared.unwrap() - (hb - hu) / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn offsets_need_a_side_by_side_pair_of_the_constraint() {
+        let monitors = desk(None);
+        let stacked = CalibrationPair { bind_horizontal: false, ..right_of_reference() };
+        let level = [Constraint::SameHeight { monitors: vec![0, 1] }];
+        assert!(declared_offset_mm(&monitors, &stacked, &level).is_none());
+        let touching = Constraint::Touching { monitors: [0, 1] };
+        assert!(offset(&monitors, touching).is_none());
+    }
+
+    /// Check a touching declaration against a layout with `air_mm` between
+    /// the two monitors' facing bezels.
+    fn check_touching(air_mm: f64) -> Vec<ConstraintViolation> {
+        let monitors = desk(Some([[10.0, 10.0, 8.0, 8.0], [9.0, 9.0, 7.0, 7.0]]));
+        let ppi = [0, 1].map(|i| monitors[i].effective_ppi().unwrap());
+        let mm_per_px = 25.4 / ppi[0];
+        // Lit areas are apart by the facing bezels, 8 + 7 mm, plus the air
+        let x1 = 2560.0 + (15.0 + air_mm) / mm_per_px;
+        let solution = LayoutSolution {
+            scales: vec![1.0, ppi[1] / ppi[0]],
+            x: vec![0.0, x1],
+            y: vec![0.0, 0.0],
+            scale_sigma: vec![0.0; 2],
+            x_sigma: vec![0.0; 2],
+            y_sigma: vec![0.0; 2],
+        };
+        let measurement = PairMeasurement {
+            unbound: 1,
+            bound: 0,
+            bind_horizontal: true,
+            contact: Contact::Edge,
+            in_tree: true,
+            line_offsets: [0.0; 4],
+            gaps: [0.0; 2],
+            line_sigma_px: None,
+            spread: None,
+            method: PairMethod::Lines,
+            declared_offset: false,
+            declared_gap: false,
+        };
+        let touching = [Constraint::Touching { monitors: [0, 1] }];
+        check(&monitors, &solution, &[measurement], &touching)
+    }
+
+    #[test]
+    fn touching_housings_are_not_flagged() {
+        assert!(check_touching(0.0).is_empty());
+        assert!(check_touching(2.0).is_empty());
+    }
+
+    #[test]
+    fn air_between_housings_declared_touching_is_flagged() {
+        let violations = check_touching(12.0);
+        assert_eq!(violations.len(), 1);
+        let v = &violations[0];
+        assert_eq!((v.constraint, v.monitors), (0, [0, 1]));
+        assert!((v.error_mm - 12.0).abs() < 1e-6, "{}", v.error_mm);
+    }
+}
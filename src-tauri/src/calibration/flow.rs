@@ -1,55 +1,211 @@
+use super::adjacency::{self, Contact, Rect};
 use crate::monitors::Monitor;
+use serde::Serialize;
 
-/// Compute pairwise calibration order.
-/// Returns vec of (unbound_monitor_idx, bound_monitor_idx).
-pub fn compute_calibration_order(monitors: &[Monitor]) -> Vec<(usize, usize)> {
-    if monitors.len() < 2 {
-        return vec![];
+/// Monitors calibrated when the user hasn't picked any: every active one
+/// with a real panel, counting each mirror group once. Virtual, wireless and
+/// remote displays must be opted in.
+pub fn default_selection(monitors: &[Monitor]) -> Vec<usize> {
+    monitors
+        .iter()
+        .filter(|m| m.is_active() && m.is_physical() && !m.is_secondary_clone())
+        .map(|m| m.id)
+        .collect()
+}
+
+/// Check a user selection: known, active monitors, each once and at most one
+/// per mirror group, at least two of them.
+pub fn validate_selection(monitors: &[Monitor], selected: &[usize]) -> Result<(), String> {
+    for (pos, &idx) in selected.iter().enumerate() {
+        if selected[..pos].contains(&idx) {
+            return Err(format!("Display {} is selected more than once", idx + 1));
+        }
+        match monitors.get(idx) {
+            None => return Err(format!("Unknown monitor {idx}")),
+            Some(m) if !m.is_active() => {
+                return Err(format!(
+                    "Display {} is not part of the desktop and can't be calibrated",
+                    idx + 1
+                ))
+            }
+            Some(m) => {
+                let mirror = m.clone_group.and_then(|group| {
+                    selected[..pos]
+                        .iter()
+                        .find(|&&other| monitors[other].clone_group == Some(group))
+                });
+                if let Some(&other) = mirror {
+                    return Err(format!(
+                        "Displays {} and {} mirror each other; select only one of them",
+                        other + 1,
+                        idx + 1
+                    ));
+                }
+            }
+        }
+    }
+    if selected.len() < 2 {
+        return Err("Need at least 2 monitors for calibration".to_string());
     }
+    Ok(())
+}
 
-    let primary_idx = monitors
-        .iter()
-        .position(|m| m.is_primary)
-        .unwrap_or(0);
+/// One step of the calibration: measure `unbound` against the already
+/// calibrated `bound` monitor.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrationPair {
+    pub unbound: usize,
+    pub bound: usize,
+    pub bind_horizontal: bool,
+    /// How the two monitors meet; corner and apart pairs give poor results
+    pub contact: Contact,
+}
 
-    let mut bound = vec![false; monitors.len()];
-    bound[primary_idx] = true;
+/// Reference monitor when the user hasn't picked one: the primary if it is
+/// selected, otherwise the first selected monitor.
+pub fn default_reference(monitors: &[Monitor], selected: &[usize]) -> usize {
+    selected
+        .iter()
+        .copied()
+        .find(|&i| monitors[i].is_primary)
+        .unwrap_or(selected[0])
+}
 
-    let mut pairs = Vec::new();
+/// Compute pairwise calibration order over the selected monitors: a spanning
+/// tree from the reference monitor that pairs monitors along their longest
+/// shared edges. Fails if a selected monitor overlaps the others, since it
+/// could then never be calibrated.
+pub fn compute_calibration_order(
+    monitors: &[Monitor],
+    selected: &[usize],
+    reference: usize,
+) -> Result<Vec<CalibrationPair>, String> {
+    if selected.len() < 2 {
+        return Ok(vec![]);
+    }
 
-    while bound.iter().any(|&b| !b) {
-        let mut best_dist = f64::MAX;
-        let mut best_unbound = 0;
-        let mut best_bound = 0;
+    let rects: Vec<(usize, Rect)> = selected
+        .iter()
+        .map(|&i| (i, Rect::of(&monitors[i])))
+        .collect();
 
-        for (i, m) in monitors.iter().enumerate() {
-            if bound[i] {
-                continue;
-            }
-            let cx_i = m.position_x as f64 + m.resolution_x as f64 / 2.0;
-            let cy_i = m.position_y as f64 + m.resolution_y as f64 / 2.0;
+    let tree = adjacency::spanning_tree(&rects, reference).map_err(|stranded| {
+        let names: Vec<String> = stranded.iter().map(|i| (i + 1).to_string()).collect();
+        match names.as_slice() {
+            [name] => format!(
+                "Display {name} overlaps the other selected displays and can't be calibrated"
+            ),
+            _ => format!(
+                "Displays {} overlap the other selected displays and can't be calibrated",
+                names.join(", ")
+            ),
+        }
+    })?;
+    Ok(tree.iter().map(|adj| to_pair(monitors, adj)).collect())
+}
 
-            for (j, n) in monitors.iter().enumerate() {
-                if !bound[j] {
-                    continue;
-                }
-                let cx_j = n.position_x as f64 + n.resolution_x as f64 / 2.0;
-                let cy_j = n.position_y as f64 + n.resolution_y as f64 / 2.0;
-
-                let dist = ((cx_i - cx_j).powi(2) + (cy_i - cy_j).powi(2)).sqrt();
-                if dist < best_dist {
-                    best_dist = dist;
-                    best_unbound = i;
-                    best_bound = j;
-                }
-            }
+/// Order user-chosen (unbound, bound) pairs so every bound monitor is
+/// calibrated before it is used. The pairs must form a tree over the
+/// selection rooted at the reference: each other monitor is unbound exactly
+/// once and can be reached from the reference.
+pub fn custom_calibration_order(
+    monitors: &[Monitor],
+    selected: &[usize],
+    reference: usize,
+    pairs: &[(usize, usize)],
+) -> Result<Vec<CalibrationPair>, String> {
+    for &(unbound, bound) in pairs {
+        if !selected.contains(&unbound) || !selected.contains(&bound) {
+            return Err(format!(
+                "Pair ({}, {}) uses a monitor that isn't selected",
+                unbound + 1,
+                bound + 1
+            ));
+        }
+        if unbound == bound {
+            return Err(format!("Display {} can't be paired with itself", unbound + 1));
+        }
+        if unbound == reference {
+            return Err(format!(
+                "Display {} is the reference and can't be calibrated against another monitor",
+                unbound + 1
+            ));
         }
+    }
+    for &idx in selected {
+        let count = pairs.iter().filter(|&&(u, _)| u == idx).count();
+        if idx != reference && count != 1 {
+            return Err(format!(
+                "Display {} must be calibrated exactly once (found {count} pairs)",
+                idx + 1
+            ));
+        }
+    }
 
-        bound[best_unbound] = true;
-        pairs.push((best_unbound, best_bound));
+    let mut calibrated = vec![reference];
+    let mut remaining: Vec<(usize, usize)> = pairs.to_vec();
+    let mut ordered = Vec::new();
+    while !remaining.is_empty() {
+        let Some(pos) = remaining.iter().position(|(_, b)| calibrated.contains(b)) else {
+            return Err(
+                "Calibration pairs don't connect every monitor to the reference".to_string(),
+            );
+        };
+        let (unbound, bound) = remaining.remove(pos);
+        let adj = adjacency::relate(
+            unbound,
+            Rect::of(&monitors[unbound]),
+            bound,
+            Rect::of(&monitors[bound]),
+        )
+        .ok_or_else(|| {
+            format!(
+                "Displays {} and {} overlap and can't be calibrated against each other",
+                unbound + 1,
+                bound + 1
+            )
+        })?;
+        calibrated.push(unbound);
+        ordered.push(to_pair(monitors, &adj));
     }
 
-    pairs
+    Ok(ordered)
+}
+
+/// Pairs of selected monitors that share an edge but aren't in the tree;
+/// measuring them too over-determines the layout.
+pub fn extra_adjacent_pairs(
+    monitors: &[Monitor],
+    selected: &[usize],
+    tree: &[CalibrationPair],
+) -> Vec<CalibrationPair> {
+    let rects: Vec<(usize, Rect)> = selected
+        .iter()
+        .map(|&i| (i, Rect::of(&monitors[i])))
+        .collect();
+    let in_tree = |a: usize, b: usize| {
+        tree.iter()
+            .any(|p| (p.unbound, p.bound) == (a, b) || (p.unbound, p.bound) == (b, a))
+    };
+
+    adjacency::build_graph(&rects)
+        .into_iter()
+        .filter(|adj| adj.contact == Contact::Edge && !in_tree(adj.a, adj.b))
+        .map(|adj| to_pair(monitors, &adj))
+        .collect()
+}
+
+fn to_pair(monitors: &[Monitor], adj: &adjacency::Adjacency) -> CalibrationPair {
+    CalibrationPair {
+        unbound: adj.a,
+        bound: adj.b,
+        bind_horizontal: match adj.contact {
+            Contact::Edge => adj.side.is_horizontal(),
+            _ => determine_bind_horizontal(&monitors[adj.a], &monitors[adj.b]),
+        },
+        contact: adj.contact,
+    }
 }
 
 /// Determine if two monitors are side-by-side (horizontal binding)
@@ -69,3 +225,85 @@ pub fn determine_bind_horizontal(m1: &Monitor, m2: &Monitor) -> bool {
 
     v_overlap >= h_overlap
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monitors::MonitorStatus;
+
+    /// Three 1080p monitors side by side, the middle one primary.
+    fn row() -> Vec<Monitor> {
+        let mut monitors: Vec<Monitor> = (0..3)
+            .map(|i| Monitor::for_test(i, [1920 * i as i32 - 1920, 0], [1920, 1080], None))
+            .collect();
+        monitors[0].is_primary = false;
+        monitors[1].is_primary = true;
+        monitors
+    }
+
+    #[test]
+    fn selection_needs_two_distinct_active_monitors() {
+        let mut monitors = row();
+        assert!(validate_selection(&monitors, &[0, 2]).is_ok());
+        assert!(validate_selection(&monitors, &[1]).is_err());
+        assert!(validate_selection(&monitors, &[0, 3]).is_err());
+        let err = validate_selection(&monitors, &[0, 0]).unwrap_err();
+        assert!(err.contains("more than once"), "{err}");
+        monitors[2].status = MonitorStatus::Inactive;
+        assert!(validate_selection(&monitors, &[0, 2]).is_err());
+    }
+
+    /// A 2x2 grid of 1080p monitors: 0 and 1 on top, 2 and 3 below.
+    fn grid() -> Vec<Monitor> {
+        [[0, 0], [1920, 0], [0, 1080], [1920, 1080]]
+            .into_iter()
+            .enumerate()
+            .map(|(i, position)| Monitor::for_test(i, position, [1920, 1080], None))
+            .collect()
+    }
+
+    fn custom(pairs: &[(usize, usize)]) -> Result<Vec<CalibrationPair>, String> {
+        custom_calibration_order(&grid(), &[0, 1, 2, 3], 0, pairs)
+    }
+
+    #[test]
+    fn custom_pairs_must_use_selected_distinct_monitors() {
+        let err = custom_calibration_order(&grid(), &[0, 1, 2], 0, &[(1, 0), (3, 1)]);
+        assert!(err.unwrap_err().contains("isn't selected"));
+        let err = custom(&[(1, 0), (2, 2), (3, 1)]).unwrap_err();
+        assert!(err.contains("with itself"), "{err}");
+    }
+
+    #[test]
+    fn reference_is_never_unbound() {
+        let err = custom(&[(0, 1), (1, 0), (2, 0), (3, 1)]).unwrap_err();
+        assert!(err.contains("is the reference"), "{err}");
+    }
+
+    #[test]
+    fn each_other_monitor_is_unbound_exactly_once() {
+        let twice = custom(&[(1, 0), (2, 0), (3, 1), (3, 2)]).unwrap_err();
+        assert!(twice.contains("Display 4") && twice.contains("found 2"), "{twice}");
+        let never = custom(&[(1, 0), (2, 0)]).unwrap_err();
+        assert!(never.contains("Display 4") && never.contains("found 0"), "{never}");
+    }
+
+    #[test]
+    fn pairs_must_reach_the_reference() {
+        // 2 and 3 only refer to each other
+        let err = custom(&[(1, 0), (2, 3), (3, 2)]).unwrap_err();
+        assert!(err.contains("don't connect"), "{err}");
+    }
+
+    #[test]
+    fn chain_given_out_of_order_is_reordered() {
+        // 3 hangs off 2, which hangs off 1, but the pairs come in reverse
+        let order = custom(&[(3, 2), (2, 1), (1, 0)]).unwrap();
+        let steps: Vec<_> = order.iter().map(|p| (p.unbound, p.bound)).collect();
+        assert_eq!(steps, vec![(1, 0), (2, 1), (3, 2)]);
+        assert!(order[0].bind_horizontal && order[2].bind_horizontal);
+        // 1 and 2 only meet at a corner
+        assert_eq!(order[1].contact, Contact::Corner);
+        assert_eq!(order[0].contact, Contact::Edge);
+    }
+}
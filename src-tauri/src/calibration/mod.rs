@@ -1,9 +1,14 @@
+pub mod adjacency;
+pub mod constraints;
 pub mod flow;
 pub mod overlay;
+pub mod pair;
+pub mod sizes;
+pub mod solve;
+pub mod trials;
 
 use crate::monitors::Monitor;
 use serde::{Deserialize, Serialize};
-use windows::Win32::UI::WindowsAndMessaging::*;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -12,187 +17,1086 @@ pub struct CalibrationResult {
     pub scale: f64,
     pub relative_x: f64,
     pub relative_y: f64,
-    pub gap: i32,
+    /// Gap in pixels of the bound monitor, negative where the monitors
+    /// overlap; fractional when the gap lines were shallower than 45° or
+    /// trials were averaged
+    pub gap: f64,
     pub bound_to: usize,
     pub bind_horizontal: bool,
+    /// How the pair meets in the virtual desktop; corner-only contacts are
+    /// flagged so the user can double-check them
+    pub contact: adjacency::Contact,
     /// Pixel offset from unbound monitor's top (or left) edge to alignment midpoint
     pub align_offset_unbound: f64,
     /// Pixel offset from bound monitor's top (or left) edge to alignment midpoint
     pub align_offset_bound: f64,
+    /// One-sigma uncertainty of `scale`, as a fraction of it (0.01 = 1%)
+    pub scale_sigma: f64,
+    /// One-sigma uncertainty of the monitor's top-left corner relative to the
+    /// reference monitor, in reference-monitor pixels (x, y)
+    pub position_sigma: [f64; 2],
+    /// In-plane rotation of this monitor relative to the bound one, degrees
+    /// clockwise as seen from the front. Derived from the gap at two points
+    /// along the shared edge.
+    pub rotation_deg: f64,
+    /// How much repeated trials of this pair disagreed, when it was measured
+    /// more than once
+    pub spread: Option<trials::PairSpread>,
+    /// Only the scale was measured (matched squares). The position fields
+    /// are zero and carry no information.
+    #[serde(default)]
+    pub scale_only: bool,
 }
 
-pub fn run_calibration(monitors: &[Monitor], color1: [u8; 3], color2: [u8; 3]) -> Result<Vec<CalibrationResult>, String> {
-    if monitors.len() < 2 {
-        return Err("Need at least 2 monitors for calibration".to_string());
+/// How a pair was measured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PairMethod {
+    /// Scale lines and gap lines across the shared edge
+    #[default]
+    Lines,
+    /// A square on each monitor resized to look the same physical size.
+    /// Works for monitors that don't share an edge, but gives scale only.
+    MatchedSquare,
+    /// Gap and offset typed in from a tape measure; the scale comes from
+    /// both monitors' sizes
+    Tape,
+}
+
+impl PairMethod {
+    /// Whether the measurement relates the two monitors' positions.
+    pub fn measures_position(self) -> bool {
+        self != PairMethod::MatchedSquare
+    }
+}
+
+/// Everyday object of known size held against a screen to measure its
+/// pixel density, for monitors whose size EDID doesn't report.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ReferenceObject {
+    /// Bank or ID card (ISO/IEC 7810 ID-1)
+    Card,
+    A4,
+    Letter,
+    /// A stretch of ruler between two marks
+    #[serde(rename_all = "camelCase")]
+    Ruler { length_mm: f64 },
+}
+
+impl ReferenceObject {
+    /// Width and height in millimetres, held landscape. A ruler is measured
+    /// along its length only and has no height.
+    pub fn size_mm(self) -> [f64; 2] {
+        match self {
+            ReferenceObject::Card => [85.60, 53.98],
+            ReferenceObject::A4 => [297.0, 210.0],
+            ReferenceObject::Letter => [279.4, 215.9],
+            ReferenceObject::Ruler { length_mm } => [length_mm, 0.0],
+        }
+    }
+
+    pub fn label(self) -> String {
+        match self {
+            ReferenceObject::Card => "Credit card".into(),
+            ReferenceObject::A4 => "A4 sheet (landscape)".into(),
+            ReferenceObject::Letter => "Letter sheet (landscape)".into(),
+            ReferenceObject::Ruler { length_mm } => format!("{length_mm} mm of a ruler"),
+        }
+    }
+}
+
+/// Raw result of the scale and gap steps for one pair, before solving.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairMeasurement {
+    pub unbound: usize,
+    pub bound: usize,
+    pub bind_horizontal: bool,
+    pub contact: adjacency::Contact,
+    /// Part of the spanning tree; other pairs only add redundancy
+    pub in_tree: bool,
+    /// Scale-step line offsets from each monitor's top (or left) edge:
+    /// [blue_unbound, blue_bound, red_unbound, red_bound]. Averaged over
+    /// trials, so not necessarily whole pixels.
+    pub line_offsets: [f64; 4],
+    /// Gap-step results in pixels of the bound monitor, measured level with
+    /// the blue and the red line. They differ when a monitor is tilted.
+    pub gaps: [f64; 2],
+    /// Line placement uncertainty for this pair, if known from repeated
+    /// measurements; otherwise the session's assumed value is used
+    pub line_sigma_px: Option<f64>,
+    pub spread: Option<trials::PairSpread>,
+    /// For `MatchedSquare`, `line_offsets` is [0, 0, side_unbound,
+    /// side_bound] so that `spans()` gives the square sizes, and the gaps
+    /// are zero. For `Tape`, both are where the lines would have been.
+    #[serde(default)]
+    pub method: PairMethod,
+    /// The lines were moved along the edge to the offset a declared
+    /// constraint and both monitors' sizes give; their spacing, and so the
+    /// scale, is still measured
+    #[serde(default)]
+    pub declared_offset: bool,
+    /// The gaps follow from a declared constraint instead of the gap step
+    #[serde(default)]
+    pub declared_gap: bool,
+}
+
+impl PairMeasurement {
+    /// Distance between the two lines on (unbound, bound); both cover the
+    /// same physical length.
+    pub fn spans(&self) -> (f64, f64) {
+        let o = &self.line_offsets;
+        ((o[2] - o[0]).abs(), (o[3] - o[1]).abs())
+    }
+
+    /// Midpoint between the two lines on (unbound, bound); both are at the
+    /// same physical height (or horizontal position).
+    pub fn align_offsets(&self) -> (f64, f64) {
+        let o = &self.line_offsets;
+        ((o[0] + o[2]) / 2.0, (o[1] + o[3]) / 2.0)
     }
 
-    let pairs = flow::compute_calibration_order(monitors);
-    let mut results = Vec::new();
-    let mut scales: Vec<f64> = vec![1.0; monitors.len()];
+    /// Gap at the alignment midpoint, halfway between the two readings.
+    pub fn gap(&self) -> f64 {
+        (self.gaps[0] + self.gaps[1]) / 2.0
+    }
+
+    /// Change in gap per bound-monitor pixel along the edge, from the blue
+    /// to the red line.
+    pub fn gap_slope(&self) -> f64 {
+        let run = self.line_offsets[3] - self.line_offsets[1];
+        if run.abs() < 1.0 {
+            return 0.0;
+        }
+        (self.gaps[1] - self.gaps[0]) / run
+    }
+
+    /// Whether the scale was derived from the monitors' sizes rather than
+    /// measured.
+    pub fn scale_from_sizes(&self) -> bool {
+        self.method == PairMethod::Tape
+    }
+}
+
+/// Disagreement between one measured pair and the solved layout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EdgeResidual {
+    /// Index into `CalibrationSession::measurements`
+    pub measurement: usize,
+    pub unbound: usize,
+    pub bound: usize,
+    pub in_tree: bool,
+    /// Measured over solved scale ratio, minus one (0.01 = 1% off)
+    pub scale_error: f64,
+    /// Along-edge disagreement in pixels of the bound monitor
+    pub offset_error_px: f64,
+    /// Across-edge (gap) disagreement in pixels of the bound monitor
+    pub gap_error_px: f64,
+}
+
+/// Everything a calibration run produced. `results` feed the layout map and
+/// export; the measurements are kept so a single pair can be redone.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CalibrationSession {
+    pub reference: usize,
+    /// Assumed placement uncertainty of a single line, in pixels
+    pub line_sigma_px: f64,
+    /// Trials per step; redoing a pair repeats it as often
+    pub repeats: u32,
+    /// Gap line angle used for the run, reused when redoing a pair
+    pub gap_angle_deg: f64,
+    pub results: Vec<CalibrationResult>,
+    pub measurements: Vec<PairMeasurement>,
+    pub residuals: Vec<EdgeResidual>,
+    /// Facts declared for the run, checked again when a pair is redone
+    #[serde(default)]
+    pub constraints: Vec<constraints::Constraint>,
+    /// Declared facts the solved layout contradicts
+    #[serde(default)]
+    pub violations: Vec<constraints::ConstraintViolation>,
+}
+
+/// Optional choices for a calibration run; anything left out uses the
+/// automatic behaviour.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct CalibrationOptions {
+    /// Monitor every other monitor is measured relative to. Defaults to the
+    /// primary display if it is selected.
+    pub reference: Option<usize>,
+    /// Explicit (unbound, bound) pairs forming a tree rooted at the reference.
+    /// Defaults to pairing along the longest shared edges.
+    pub pairs: Option<Vec<(usize, usize)>>,
+    /// Also measure adjacent pairs outside the tree and solve the layout
+    /// globally, spreading measurement error instead of chaining it.
+    pub measure_all_adjacent: bool,
+    /// How far off (in pixels) a single dragged line is assumed to be; feeds
+    /// the error bars. Defaults to `DEFAULT_LINE_SIGMA_PX`.
+    pub line_sigma_px: Option<f64>,
+    /// Run each scale and gap step this many times and average the trials,
+    /// dropping outliers. Defaults to once.
+    pub repeats: Option<u32>,
+    /// Angle of the gap-step lines against the shared edge, in degrees.
+    /// Shallower lines read the gap more finely. Defaults to 45°.
+    pub gap_angle_deg: Option<f64>,
+    /// Monitors compared with their bound monitor by matched squares instead
+    /// of lines across the edge, e.g. on another wall. Only their scale is
+    /// measured; they keep their Windows position relative to the bound one.
+    pub matched_square: Vec<usize>,
+    /// Tree pairs measured with a tape instead of on screen
+    pub tape: Vec<TapeMeasurement>,
+    /// Known facts about the desk. Tree pairs whose offset or gap they fix
+    /// skip that step, and the result is checked against all of them.
+    pub constraints: Vec<constraints::Constraint>,
+}
+
+/// A pair measured by hand, between the edges of the two lit areas.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TapeMeasurement {
+    /// Unbound monitor of the tree pair this measures
+    pub unbound: usize,
+    /// Gap in millimetres, negative where the monitors overlap
+    pub gap_mm: f64,
+    /// How far the unbound monitor's top edge sits below the bound
+    /// monitor's (side by side), or its left edge right of the bound
+    /// monitor's (stacked), in millimetres
+    pub offset_mm: f64,
+}
+
+/// Assumed one-sigma reading error of a tape measure.
+pub const TAPE_SIGMA_MM: f64 = 1.0;
+
+/// Assumed one-sigma placement error of a line dragged by eye.
+pub const DEFAULT_LINE_SIGMA_PX: f64 = 1.0;
 
-    let vx = unsafe { GetSystemMetrics(SM_XVIRTUALSCREEN) };
-    let vy = unsafe { GetSystemMetrics(SM_YVIRTUALSCREEN) };
+/// Upper limit on `CalibrationOptions::repeats`.
+pub const MAX_REPEATS: u32 = 10;
 
-    let monitor_rects: Vec<overlay::MonitorRect> = monitors
+/// Monitor rectangles in overlay-window coordinates (virtual screen origin at 0,0).
+/// Monitors that aren't on the desktop get an empty rect.
+fn overlay_rects(monitors: &[Monitor]) -> Vec<overlay::MonitorRect> {
+    let (vx, vy) = overlay::virtual_screen_origin();
+
+    monitors
         .iter()
-        .map(|m| overlay::MonitorRect {
-            x: m.position_x - vx,
-            y: m.position_y - vy,
-            w: m.resolution_x as i32,
-            h: m.resolution_y as i32,
+        .map(|m| {
+            if !m.is_active() {
+                return overlay::MonitorRect { x: 0, y: 0, w: 0, h: 0 };
+            }
+            overlay::MonitorRect {
+                x: m.position_x - vx,
+                y: m.position_y - vy,
+                w: m.resolution_x as i32,
+                h: m.resolution_y as i32,
+            }
         })
-        .collect();
+        .collect()
+}
 
-    let monitor_labels: Vec<String> = monitors
+/// Monitor labels for the overlay. A mirror group shares one rectangle, so
+/// its first member carries every member's name and the rest get none.
+fn overlay_labels(monitors: &[Monitor]) -> Vec<String> {
+    let display_name = |m: &Monitor| {
+        if m.friendly_name.is_empty() {
+            m.device_name.clone()
+        } else {
+            m.friendly_name.clone()
+        }
+    };
+
+    monitors
         .iter()
         .map(|m| {
-            let name = if m.friendly_name.is_empty() {
-                &m.device_name
-            } else {
-                &m.friendly_name
+            if !m.is_active() || m.is_secondary_clone() {
+                return String::new();
+            }
+            let name = match m.clone_group {
+                Some(group) => monitors
+                    .iter()
+                    .filter(|o| o.clone_group == Some(group))
+                    .map(display_name)
+                    .collect::<Vec<_>>()
+                    .join(" = "),
+                None => display_name(m),
             };
             format!("{}\n{}×{}", name, m.resolution_x, m.resolution_y)
         })
-        .collect();
+        .collect()
+}
 
-    for (unbound_idx, bound_idx) in &pairs {
-        let bind_horizontal = flow::determine_bind_horizontal(
-            &monitors[*unbound_idx],
-            &monitors[*bound_idx],
-        );
-
-        // --- Scale step ---
-        let scale_result = overlay::run_overlay(overlay::OverlayConfig {
-            step: overlay::OverlayStep::Scale,
-            m1_idx: *unbound_idx,
-            m2_idx: *bound_idx,
-            monitors: monitor_rects.clone(),
-            monitor_labels: monitor_labels.clone(),
-            bind_horizontal,
-            temp_middles: None,
+/// Where a pair's readings come from: the overlay in a real run, or a
+/// simulated user checking the math against a known desk. Positions are in
+/// the coordinates of `rect`.
+pub trait MeasurementSource {
+    /// Rectangle of monitor `idx`
+    fn rect(&self, idx: usize) -> adjacency::Rect;
+
+    /// Scale step: where the lines were left, as y (side by side) or x
+    /// (stacked) of [blue_unbound, blue_bound, red_unbound, red_bound]
+    fn scale_lines(
+        &mut self,
+        pair: &flow::CalibrationPair,
+        trial: Option<(u32, u32)>,
+    ) -> Result<[i32; 4], String>;
+
+    /// Gap step level with the blue (`line` 0) or red (1) line, which sit at
+    /// `anchor` on the unbound and the bound monitor. Returns the gap in
+    /// pixels of the bound monitor.
+    fn gap(
+        &mut self,
+        pair: &flow::CalibrationPair,
+        line: usize,
+        anchor: [i32; 2],
+        trial: Option<(u32, u32)>,
+    ) -> Result<f64, String>;
+
+    /// Matched-square step: sides of the resized square on the unbound
+    /// monitor and of the fixed one on the bound monitor
+    fn squares(
+        &mut self,
+        pair: &flow::CalibrationPair,
+        trial: Option<(u32, u32)>,
+    ) -> Result<[i32; 2], String>;
+}
+
+/// Readings from the user, through the overlay on the real monitors.
+struct OverlaySource {
+    rects: Vec<overlay::MonitorRect>,
+    labels: Vec<String>,
+    gap_angle_deg: f64,
+    color1: [u8; 3],
+    color2: [u8; 3],
+}
+
+impl OverlaySource {
+    fn new(monitors: &[Monitor], gap_angle_deg: f64, color1: [u8; 3], color2: [u8; 3]) -> Self {
+        OverlaySource {
+            rects: overlay_rects(monitors),
+            labels: overlay_labels(monitors),
+            gap_angle_deg,
             color1,
             color2,
-        })?;
+        }
+    }
 
-        if scale_result.cancelled {
+    fn run(
+        &self,
+        step: overlay::OverlayStep,
+        pair: &flow::CalibrationPair,
+        temp_middles: Option<[i32; 2]>,
+        trial: Option<(u32, u32)>,
+        caption: Option<&str>,
+    ) -> Result<overlay::OverlayResult, String> {
+        let result = overlay::run_overlay(overlay::OverlayConfig {
+            step,
+            m1_idx: pair.unbound,
+            m2_idx: pair.bound,
+            monitors: self.rects.clone(),
+            monitor_labels: self.labels.clone(),
+            bind_horizontal: pair.bind_horizontal,
+            temp_middles,
+            color1: self.color1,
+            color2: self.color2,
+            gap_angle_deg: self.gap_angle_deg,
+            trial,
+            caption: caption.map(str::to_string),
+        })?;
+        if result.cancelled {
             return Err("Calibration cancelled".to_string());
         }
+        Ok(result)
+    }
+}
 
-        let m1r = &monitor_rects[*unbound_idx];
-        let m2r = &monitor_rects[*bound_idx];
-
-        // Compute per-monitor midpoints from the scale step line positions.
-        // segments: [blue_m1, blue_m2, red_m1, red_m2]
-        let temp_mid_m1 = (scale_result.segments[0] + scale_result.segments[2]) / 2;
-        let temp_mid_m2 = (scale_result.segments[1] + scale_result.segments[3]) / 2;
-
-        // off[0..3] are pixel offsets from each monitor's top (or left) edge
-        // to each line position: [blue_m1, blue_m2, red_m1, red_m2]
-        let (scale, relative_offset, align_off_m1, align_off_m2) = if bind_horizontal {
-            let off = [
-                scale_result.segments[0] - m1r.y,
-                scale_result.segments[1] - m2r.y,
-                scale_result.segments[2] - m1r.y,
-                scale_result.segments[3] - m2r.y,
-            ];
-
-            let span_m1 = (off[2] - off[0]).abs() as f64;
-            let span_m2 = (off[3] - off[1]).abs() as f64;
-
-            let scale = if span_m2 > 1.0 {
-                scales[*bound_idx] * (span_m1 / span_m2)
-            } else {
-                scales[*bound_idx]
-            };
+impl MeasurementSource for OverlaySource {
+    fn rect(&self, idx: usize) -> adjacency::Rect {
+        let r = &self.rects[idx];
+        adjacency::Rect { x: r.x, y: r.y, w: r.w, h: r.h }
+    }
 
-            let mid_m1 = (off[0] + off[2]) as f64 / 2.0;
-            let mid_m2 = (off[1] + off[3]) as f64 / 2.0;
-            let rel_y = off[1] as f64 - off[0] as f64 * scale;
-            (scale, rel_y, mid_m1, mid_m2)
-        } else {
-            let off = [
-                scale_result.segments[0] - m1r.x,
-                scale_result.segments[1] - m2r.x,
-                scale_result.segments[2] - m1r.x,
-                scale_result.segments[3] - m2r.x,
-            ];
-
-            let span_m1 = (off[2] - off[0]).abs() as f64;
-            let span_m2 = (off[3] - off[1]).abs() as f64;
-
-            let scale = if span_m2 > 1.0 {
-                scales[*bound_idx] * (span_m1 / span_m2)
-            } else {
-                scales[*bound_idx]
-            };
+    fn scale_lines(
+        &mut self,
+        pair: &flow::CalibrationPair,
+        trial: Option<(u32, u32)>,
+    ) -> Result<[i32; 4], String> {
+        let result = self.run(overlay::OverlayStep::Scale, pair, None, trial, None)?;
+        Ok(result.segments)
+    }
 
-            let mid_m1 = (off[0] + off[2]) as f64 / 2.0;
-            let mid_m2 = (off[1] + off[3]) as f64 / 2.0;
-            let rel_x = off[1] as f64 - off[0] as f64 * scale;
-            (scale, rel_x, mid_m1, mid_m2)
-        };
+    fn gap(
+        &mut self,
+        pair: &flow::CalibrationPair,
+        line: usize,
+        anchor: [i32; 2],
+        trial: Option<(u32, u32)>,
+    ) -> Result<f64, String> {
+        let caption = ["Gap at the blue line (1 of 2)", "Gap at the red line (2 of 2)"][line];
+        let result = self.run(overlay::OverlayStep::Gap, pair, Some(anchor), trial, Some(caption))?;
+        Ok(result.gap)
+    }
 
-        scales[*unbound_idx] = scale;
+    fn squares(
+        &mut self,
+        pair: &flow::CalibrationPair,
+        trial: Option<(u32, u32)>,
+    ) -> Result<[i32; 2], String> {
+        let caption = Some("Scale only: matched squares");
+        let result = self.run(overlay::OverlayStep::Square, pair, None, trial, caption)?;
+        Ok(result.square_px)
+    }
+}
 
-        // --- Gap step ---
-        let gap_result = overlay::run_overlay(overlay::OverlayConfig {
-            step: overlay::OverlayStep::Gap,
-            m1_idx: *unbound_idx,
-            m2_idx: *bound_idx,
-            monitors: monitor_rects.clone(),
-            monitor_labels: monitor_labels.clone(),
-            bind_horizontal,
-            temp_middles: Some([temp_mid_m1, temp_mid_m2]),
-            color1,
-            color2,
-        })?;
+/// Gap-step line angle of a run, within what the overlay can show.
+pub fn gap_angle_deg(options: &CalibrationOptions) -> f64 {
+    options
+        .gap_angle_deg
+        .unwrap_or(overlay::DEFAULT_GAP_ANGLE_DEG)
+        .clamp(overlay::MIN_GAP_ANGLE_DEG, overlay::MAX_GAP_ANGLE_DEG)
+}
 
-        if gap_result.cancelled {
-            return Err("Calibration cancelled".to_string());
+/// Show the nested edge markers on one monitor and return the overscan
+/// (percent per edge) the user picked.
+pub fn run_overscan_measurement(
+    monitors: &[Monitor],
+    monitor_idx: usize,
+    color1: [u8; 3],
+    color2: [u8; 3],
+) -> Result<f64, String> {
+    match monitors.get(monitor_idx) {
+        None => return Err(format!("Unknown monitor {monitor_idx}")),
+        Some(m) if !m.is_active() => {
+            return Err("Overscan can only be measured on an active display".to_string())
         }
+        Some(_) => {}
+    }
 
-        let gap = gap_result.gap;
+    let result = overlay::run_overlay(overlay::OverlayConfig {
+        step: overlay::OverlayStep::Overscan,
+        m1_idx: monitor_idx,
+        m2_idx: monitor_idx,
+        monitors: overlay_rects(monitors),
+        monitor_labels: overlay_labels(monitors),
+        bind_horizontal: true,
+        temp_middles: None,
+        color1,
+        color2,
+        gap_angle_deg: overlay::DEFAULT_GAP_ANGLE_DEG,
+        trial: None,
+        caption: None,
+    })?;
 
-        let (relative_x, relative_y) = if bind_horizontal {
-            let m1 = &monitors[*unbound_idx];
-            let m2 = &monitors[*bound_idx];
-            let m1_w = m1.resolution_x as f64;
-            let m2_w = m2.resolution_x as f64;
+    if result.cancelled {
+        return Err("Overscan measurement cancelled".to_string());
+    }
 
-            let rx = if m1.position_x < m2.position_x {
-                -(gap as f64 * 2.0) - m1_w * scale
-            } else {
-                m2_w * scales[*bound_idx] + gap as f64 * 2.0
-            };
+    Ok(result.overscan_pct)
+}
+
+/// Show an outline of `object` on one monitor for the user to resize over
+/// the real thing, and return the pixels per inch of the image as shown.
+pub fn run_reference_measurement(
+    monitors: &[Monitor],
+    monitor_idx: usize,
+    object: ReferenceObject,
+    color1: [u8; 3],
+    color2: [u8; 3],
+) -> Result<f64, String> {
+    match monitors.get(monitor_idx) {
+        None => return Err(format!("Unknown monitor {monitor_idx}")),
+        Some(m) if !m.is_active() => {
+            return Err("Size can only be measured on an active display".to_string())
+        }
+        Some(_) => {}
+    }
+    let [width_mm, _] = object.size_mm();
+    if !(10.0..=1000.0).contains(&width_mm) {
+        return Err("Ruler length must be between 10 and 1000 mm".to_string());
+    }
+
+    let rects = overlay_rects(monitors);
+    let largest = overlay::max_outline(&rects[monitor_idx], object);
+    let result = overlay::run_overlay(overlay::OverlayConfig {
+        step: overlay::OverlayStep::Reference(object),
+        m1_idx: monitor_idx,
+        m2_idx: monitor_idx,
+        monitors: rects,
+        monitor_labels: overlay_labels(monitors),
+        bind_horizontal: true,
+        temp_middles: None,
+        color1,
+        color2,
+        gap_angle_deg: overlay::DEFAULT_GAP_ANGLE_DEG,
+        trial: None,
+        caption: None,
+    })?;
+
+    if result.cancelled {
+        return Err("Size measurement cancelled".to_string());
+    }
+    // At its largest the outline may still be smaller than the object
+    if result.outline_px >= largest {
+        return Err(format!(
+            "{} doesn't fit on this display; use a smaller object",
+            object.label()
+        ));
+    }
+
+    Ok(result.outline_px as f64 / (width_mm / 25.4))
+}
+
+/// Reference monitor and spanning-tree pairs a run with these options
+/// measures.
+pub fn calibration_tree(
+    monitors: &[Monitor],
+    selected: &[usize],
+    options: &CalibrationOptions,
+) -> Result<(usize, Vec<flow::CalibrationPair>), String> {
+    flow::validate_selection(monitors, selected)?;
+
+    let reference = match options.reference {
+        Some(idx) if !selected.contains(&idx) => {
+            return Err(format!("Reference display {} isn't selected", idx + 1))
+        }
+        Some(idx) => idx,
+        None => flow::default_reference(monitors, selected),
+    };
+    let tree = match &options.pairs {
+        Some(custom) => flow::custom_calibration_order(monitors, selected, reference, custom)?,
+        None => flow::compute_calibration_order(monitors, selected, reference)?,
+    };
+    Ok((reference, tree))
+}
+
+pub fn run_calibration(
+    monitors: &[Monitor],
+    selected: &[usize],
+    options: &CalibrationOptions,
+    color1: [u8; 3],
+    color2: [u8; 3],
+) -> Result<CalibrationSession, String> {
+    let mut source = OverlaySource::new(monitors, gap_angle_deg(options), color1, color2);
+    run_calibration_with(monitors, selected, options, &mut source)
+}
 
-            (rx, relative_offset)
+/// Run a calibration with the on-screen readings taken from `source`.
+pub fn run_calibration_with(
+    monitors: &[Monitor],
+    selected: &[usize],
+    options: &CalibrationOptions,
+    source: &mut dyn MeasurementSource,
+) -> Result<CalibrationSession, String> {
+    let (reference, tree) = calibration_tree(monitors, selected, options)?;
+    constraints::validate(monitors, selected, &options.constraints)?;
+    let extra = if options.measure_all_adjacent {
+        flow::extra_adjacent_pairs(monitors, selected, &tree)
+    } else {
+        Vec::new()
+    };
+
+    let repeats = options.repeats.unwrap_or(1).clamp(1, MAX_REPEATS);
+
+    let mut measurements = Vec::new();
+    for (pair, in_tree) in tree
+        .iter()
+        .map(|p| (p, true))
+        .chain(extra.iter().map(|p| (p, false)))
+    {
+        let tape = options.tape.iter().find(|t| t.unbound == pair.unbound);
+        if let Some(tape) = tape.filter(|_| in_tree) {
+            measurements.push(tape_measurement(monitors, pair, tape)?);
+            continue;
+        }
+        let method = if in_tree && options.matched_square.contains(&pair.unbound) {
+            PairMethod::MatchedSquare
         } else {
-            let m1 = &monitors[*unbound_idx];
-            let m2 = &monitors[*bound_idx];
-            let m1_h = m1.resolution_y as f64;
-            let m2_h = m2.resolution_y as f64;
-
-            let ry = if m1.position_y < m2.position_y {
-                -(gap as f64 * 2.0) - m1_h * scale
-            } else {
-                m2_h * scales[*bound_idx] + gap as f64 * 2.0
-            };
+            PairMethod::Lines
+        };
+        let declared = if in_tree {
+            declare(monitors, pair, &options.constraints)
+        } else {
+            Declared::default()
+        };
+        measurements.push(measure_pair(pair, in_tree, method, &declared, repeats, source)?);
+    }
+
+    let line_sigma_px = options
+        .line_sigma_px
+        .filter(|s| *s > 0.0)
+        .unwrap_or(DEFAULT_LINE_SIGMA_PX);
+    solve_session(
+        monitors,
+        reference,
+        line_sigma_px,
+        repeats,
+        gap_angle_deg(options),
+        measurements,
+        options.constraints.clone(),
+    )
+}
+
+/// Measure one pair again and re-solve the layout with the new measurement.
+pub fn remeasure_pair(
+    monitors: &[Monitor],
+    session: &CalibrationSession,
+    index: usize,
+    color1: [u8; 3],
+    color2: [u8; 3],
+) -> Result<CalibrationSession, String> {
+    let old = session
+        .measurements
+        .get(index)
+        .ok_or_else(|| format!("Unknown measurement {index}"))?;
+    if old.unbound >= monitors.len() || old.bound >= monitors.len() {
+        return Err("Monitors changed since calibration; please recalibrate".to_string());
+    }
+    let pair = flow::CalibrationPair {
+        unbound: old.unbound,
+        bound: old.bound,
+        bind_horizontal: old.bind_horizontal,
+        contact: old.contact,
+    };
+
+    // There is nothing to redo for a pair typed in from a tape, so it is
+    // measured on screen instead; so are steps a constraint made up
+    let method = match old.method {
+        PairMethod::Tape => PairMethod::Lines,
+        method => method,
+    };
+    let mut source = OverlaySource::new(monitors, session.gap_angle_deg, color1, color2);
+    let fresh = measure_pair(
+        &pair,
+        old.in_tree,
+        method,
+        &Declared::default(),
+        session.repeats,
+        &mut source,
+    )?;
+    let mut measurements = session.measurements.clone();
+    measurements[index] = fresh;
+
+    solve_session(
+        monitors,
+        session.reference,
+        session.line_sigma_px,
+        session.repeats,
+        session.gap_angle_deg,
+        measurements,
+        session.constraints.clone(),
+    )
+}
+
+/// Solve the layout from all measurements and report the tree results along
+/// with how well each pair and each declared fact agrees with the solution.
+fn solve_session(
+    monitors: &[Monitor],
+    reference: usize,
+    line_sigma_px: f64,
+    repeats: u32,
+    gap_angle_deg: f64,
+    measurements: Vec<PairMeasurement>,
+    constraints: Vec<constraints::Constraint>,
+) -> Result<CalibrationSession, String> {
+    let rects: Vec<adjacency::Rect> = monitors.iter().map(adjacency::Rect::of).collect();
+    let solution = solve::solve_layout(&rects, reference, &measurements, line_sigma_px)
+        .ok_or("Calibration measurements don't determine the layout")?;
+
+    let results = measurements
+        .iter()
+        .filter(|m| m.in_tree)
+        .map(|m| solve::tree_result(&rects, &solution, m))
+        .collect();
+    let residuals = measurements
+        .iter()
+        .enumerate()
+        .map(|(i, m)| solve::residual(&rects, &solution, i, m))
+        .collect();
+    let violations = constraints::check(monitors, &solution, &measurements, &constraints);
+
+    Ok(CalibrationSession {
+        reference,
+        line_sigma_px,
+        repeats,
+        gap_angle_deg,
+        results,
+        measurements,
+        residuals,
+        constraints,
+        violations,
+    })
+}
+
+/// Parts of a pair's measurement that declared constraints already fix.
+#[derive(Default)]
+struct Declared {
+    /// How far the unbound monitor's top (or left) edge sits below (or right
+    /// of) the bound one's, in pixels of the bound monitor
+    offset_px: Option<f64>,
+    /// Gap in pixels of the bound monitor
+    gap_px: Option<f64>,
+}
+
+/// What the constraints fix for one tree pair. A declared offset needs both
+/// monitors' sizes, and a declared gap the bound monitor's, to turn into
+/// pixels.
+fn declare(
+    monitors: &[Monitor],
+    pair: &flow::CalibrationPair,
+    declared: &[constraints::Constraint],
+) -> Declared {
+    let ppi_b = monitors[pair.bound].effective_ppi();
+    let to_px = |mm: f64| ppi_b.map(|ppi| mm / 25.4 * ppi);
+    Declared {
+        offset_px: constraints::declared_offset_mm(monitors, pair, declared).and_then(to_px),
+        gap_px: constraints::declared_gap_mm(monitors, pair, declared).and_then(to_px),
+    }
+}
+
+/// Move the unbound lines of `lines` along the edge, keeping their spacing,
+/// so the unbound monitor's top (or left) edge ends up `offset_px` bound
+/// pixels below (or right of) the bound one's.
+fn with_offset(lines: [f64; 4], offset_px: f64) -> [f64; 4] {
+    let [blue_u, blue_b, red_u, red_b] = lines;
+    let run = red_b - blue_b;
+    if run.abs() < 1.0 {
+        return lines;
+    }
+    // Unbound pixels per bound pixel
+    let scale = (red_u - blue_u) / run;
+    let shift = (blue_b - offset_px) * scale - blue_u;
+    [blue_u + shift, blue_b, red_u + shift, red_b]
+}
+
+/// Run the scale and gap steps for one pair and record where the user put
+/// the lines. With `repeats` above one, each step is run that many times
+/// and the trials are averaged with outliers dropped; their spread becomes
+/// the pair's line uncertainty. A gap `declared` fixes skips the gap step; a
+/// declared offset only moves the measured lines along the edge, so the
+/// scale is always measured.
+fn measure_pair(
+    pair: &flow::CalibrationPair,
+    in_tree: bool,
+    method: PairMethod,
+    declared: &Declared,
+    repeats: u32,
+    source: &mut dyn MeasurementSource,
+) -> Result<PairMeasurement, String> {
+    if method == PairMethod::MatchedSquare {
+        return measure_square(pair, in_tree, repeats, source);
+    }
+    let unbound_idx = pair.unbound;
+    let bound_idx = pair.bound;
+    let bind_horizontal = pair.bind_horizontal;
+    let trial_of = |t: u32| (repeats > 1).then_some((t + 1, repeats));
+
+    let rect_u = source.rect(unbound_idx);
+    let rect_b = source.rect(bound_idx);
+
+    // Pixel offsets from each monitor's top (or left) edge to each line
+    let (edge_m1, edge_m2) = if bind_horizontal {
+        (rect_u.y, rect_b.y)
+    } else {
+        (rect_u.x, rect_b.x)
+    };
+
+    // --- Scale step ---
+    let mut scale_trials = Vec::new();
+    for t in 0..repeats {
+        let segments = source.scale_lines(pair, trial_of(t))?;
+        scale_trials.push(pair::line_offsets(rect_u, rect_b, bind_horizontal, segments));
+    }
+    let scale = trials::combine_lines(&scale_trials);
+    let measured = [scale.mean[0], scale.mean[1], scale.mean[2], scale.mean[3]];
+    let line_offsets = match declared.offset_px {
+        Some(offset_px) => with_offset(measured, offset_px),
+        None => measured,
+    };
+
+    // --- Gap step ---
+    // Measured level with each line in turn: the blue lines on both monitors
+    // are at the same physical height, and so are the red ones. Different
+    // gaps at the two points mean one monitor is rotated. The lines are
+    // shown where they were matched, whatever offset was declared.
+    let anchors = [
+        [
+            edge_m1 + measured[0].round() as i32,
+            edge_m2 + measured[1].round() as i32,
+        ],
+        [
+            edge_m1 + measured[2].round() as i32,
+            edge_m2 + measured[3].round() as i32,
+        ],
+    ];
+    let gap_runs = if declared.gap_px.is_some() { 0 } else { repeats };
+    let mut gap_trials = Vec::new();
+    for t in 0..gap_runs {
+        let mut readings = Vec::new();
+        for (line, anchor) in anchors.into_iter().enumerate() {
+            readings.push(source.gap(pair, line, anchor, trial_of(t))?);
+        }
+        gap_trials.push(readings);
+    }
+    let gap = (!gap_trials.is_empty()).then(|| trials::combine(&gap_trials));
+    let gaps = match &gap {
+        Some(gap) => [gap.mean[0], gap.mean[1]],
+        None => [declared.gap_px.unwrap_or_default(); 2],
+    };
+
+    // The averaged positions are better than any single trial by the square
+    // root of the number of trials kept
+    let steps: Vec<&trials::Combined> = std::iter::once(&scale).chain(&gap).collect();
+    let (line_sigma_px, spread) = if repeats > 1 {
+        let kept = steps.iter().map(|c| c.kept).min().unwrap_or(1) as f64;
+        let spread_px = steps.iter().map(|c| c.spread_px).fold(0.0, f64::max);
+        let spread = trials::PairSpread {
+            trials: repeats,
+            rejected: steps.iter().map(|c| c.rejected).sum(),
+            line_px: scale.spread_px,
+            gap_px: gap.as_ref().map_or(0.0, |c| c.spread_px),
+        };
+        (Some(spread_px / kept.sqrt()), Some(spread))
+    } else {
+        (None, None)
+    };
+
+    Ok(PairMeasurement {
+        unbound: unbound_idx,
+        bound: bound_idx,
+        bind_horizontal,
+        contact: pair.contact,
+        in_tree,
+        line_offsets,
+        gaps,
+        line_sigma_px,
+        spread,
+        method,
+        declared_offset: declared.offset_px.is_some(),
+        declared_gap: gap.is_none(),
+    })
+}
+
+/// Scale-step lines for a pair whose along-edge offset is known in
+/// millimetres (as `TapeMeasurement::offset_mm`): a quarter and three
+/// quarters along the bound monitor's edge, carried over to the unbound
+/// monitor by the offset and both monitors' pixel densities. The scale they
+/// give therefore follows the size data rather than being measured.
+fn synthetic_lines(
+    monitors: &[Monitor],
+    pair: &flow::CalibrationPair,
+    offset_mm: f64,
+) -> Result<[f64; 4], String> {
+    let density = |idx: usize| {
+        monitors[idx].effective_ppi().ok_or_else(|| {
+            format!(
+                "Display {} has no known size; a tape measurement needs the size of both displays",
+                idx + 1
+            )
+        })
+    };
+    let ppi_u = density(pair.unbound)?;
+    let ppi_b = density(pair.bound)?;
+
+    let bound = &monitors[pair.bound];
+    let edge_px = if pair.bind_horizontal {
+        bound.resolution_y
+    } else {
+        bound.resolution_x
+    } as f64;
+    let offset_in = offset_mm / 25.4;
+    // The same physical point on both monitors, as (unbound, bound) pixels
+    // from their top (or left) edges
+    let line = |bound_px: f64| ((bound_px / ppi_b - offset_in) * ppi_u, bound_px);
+    let (blue_u, blue_b) = line(edge_px / 4.0);
+    let (red_u, red_b) = line(edge_px * 3.0 / 4.0);
+    Ok([blue_u, blue_b, red_u, red_b])
+}
+
+/// Turn a tape reading into the measurement the scale and gap steps would
+/// have produced, with lines from `synthetic_lines`.
+fn tape_measurement(
+    monitors: &[Monitor],
+    pair: &flow::CalibrationPair,
+    tape: &TapeMeasurement,
+) -> Result<PairMeasurement, String> {
+    if !tape.gap_mm.is_finite() || tape.gap_mm.abs() > 1000.0 {
+        return Err(format!(
+            "Tape gap for display {} must be within ±1000 mm",
+            pair.unbound + 1
+        ));
+    }
+    if !tape.offset_mm.is_finite() || tape.offset_mm.abs() > 5000.0 {
+        return Err(format!(
+            "Tape offset for display {} must be within ±5000 mm",
+            pair.unbound + 1
+        ));
+    }
+    let line_offsets = synthetic_lines(monitors, pair, tape.offset_mm)?;
+    // Both sizes are known once the lines are
+    let ppi_b = monitors[pair.bound].effective_ppi().unwrap_or(1.0);
+    let gap = tape.gap_mm / 25.4 * ppi_b;
 
-            (relative_offset, ry)
+    Ok(PairMeasurement {
+        unbound: pair.unbound,
+        bound: pair.bound,
+        bind_horizontal: pair.bind_horizontal,
+        contact: pair.contact,
+        in_tree: true,
+        line_offsets,
+        gaps: [gap; 2],
+        line_sigma_px: Some(TAPE_SIGMA_MM / 25.4 * ppi_b),
+        spread: None,
+        method: PairMethod::Tape,
+        declared_offset: false,
+        declared_gap: false,
+    })
+}
+
+/// Run the matched-square step for one pair. The squares' sides stand in
+/// for the scale lines; there is no gap step.
+fn measure_square(
+    pair: &flow::CalibrationPair,
+    in_tree: bool,
+    repeats: u32,
+    source: &mut dyn MeasurementSource,
+) -> Result<PairMeasurement, String> {
+    let mut square_trials = Vec::new();
+    let mut fixed_side = 0;
+    for t in 0..repeats {
+        let [side, fixed] = source.squares(pair, (repeats > 1).then_some((t + 1, repeats)))?;
+        fixed_side = fixed;
+        square_trials.push(vec![side as f64]);
+    }
+    let squares = trials::combine(&square_trials);
+
+    let (line_sigma_px, spread) = if repeats > 1 {
+        let spread = trials::PairSpread {
+            trials: repeats,
+            rejected: squares.rejected,
+            line_px: squares.spread_px,
+            gap_px: 0.0,
         };
+        (Some(squares.spread_px / (squares.kept as f64).sqrt()), Some(spread))
+    } else {
+        (None, None)
+    };
+
+    Ok(PairMeasurement {
+        unbound: pair.unbound,
+        bound: pair.bound,
+        bind_horizontal: pair.bind_horizontal,
+        contact: pair.contact,
+        in_tree,
+        line_offsets: [0.0, 0.0, squares.mean[0], fixed_side as f64],
+        gaps: [0.0; 2],
+        line_sigma_px,
+        spread,
+        method: PairMethod::MatchedSquare,
+        declared_offset: false,
+        declared_gap: false,
+    })
+}
 
-        results.push(CalibrationResult {
-            monitor_id: *unbound_idx,
-            scale,
-            relative_x,
-            relative_y,
-            gap,
-            bound_to: *bound_idx,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export;
+
+    fn pair(bind_horizontal: bool) -> flow::CalibrationPair {
+        flow::CalibrationPair {
+            unbound: 1,
+            bound: 0,
             bind_horizontal,
-            align_offset_unbound: align_off_m1,
-            align_offset_bound: align_off_m2,
+            contact: adjacency::Contact::Edge,
+        }
+    }
+
+    fn tape(gap_mm: f64, offset_mm: f64) -> TapeMeasurement {
+        TapeMeasurement {
+            unbound: 1,
+            gap_mm,
+            offset_mm,
+        }
+    }
+
+    /// Gap and along-edge offset, mm, between monitor 0 and monitor 1 after
+    /// solving and placing the tape reading.
+    fn placed_tape(
+        monitors: &[Monitor],
+        bind_horizontal: bool,
+        tape: &TapeMeasurement,
+    ) -> [f64; 2] {
+        let m = tape_measurement(monitors, &pair(bind_horizontal), tape).unwrap();
+        let session = solve_session(
+            monitors,
+            0,
+            DEFAULT_LINE_SIGMA_PX,
+            1,
+            45.0,
+            vec![m],
+            Vec::new(),
+        )
+        .unwrap();
+        let layout = export::compute_physical_placements(monitors, &session.results, &[0, 1], None);
+        let [p0, p1] = [0, 1].map(|i| {
+            layout
+                .placements
+                .iter()
+                .find(|p| p.monitor_idx == i)
+                .unwrap()
         });
+        let mm = |inches: f64| inches * 25.4;
+        if bind_horizontal {
+            [mm(p1.x - (p0.x + p0.w)), mm(p1.y - p0.y)]
+        } else {
+            [mm(p1.y - (p0.y + p0.h)), mm(p1.x - p0.x)]
+        }
+    }
+
+    #[test]
+    fn tape_reading_side_by_side_is_placed_as_typed() {
+        let monitors = [
+            Monitor::for_test(0, [0, 0], [2560, 1440], Some([597, 336])),
+            Monitor::for_test(1, [2560, 100], [1920, 1080], Some([527, 296])),
+        ];
+        let [gap, offset] = placed_tape(&monitors, true, &tape(12.0, 25.0));
+        assert!((gap - 12.0).abs() < 0.2, "gap {gap}");
+        assert!((offset - 25.0).abs() < 0.2, "offset {offset}");
     }
 
-    Ok(results)
+    #[test]
+    fn tape_reading_stacked_is_placed_as_typed() {
+        let monitors = [
+            Monitor::for_test(0, [0, 0], [2560, 1440], Some([597, 336])),
+            Monitor::for_test(1, [400, 1440], [1920, 1200], Some([286, 179])),
+        ];
+        let [gap, offset] = placed_tape(&monitors, false, &tape(-3.0, 140.0));
+        assert!((gap + 3.0).abs() < 0.2, "gap {gap}");
+        assert!((offset - 140.0).abs() < 0.2, "offset {offset}");
+    }
+
+    #[test]
+    fn tape_reading_out_of_range_is_rejected() {
+        let monitors = [
+            Monitor::for_test(0, [0, 0], [2560, 1440], Some([597, 336])),
+            Monitor::for_test(1, [2560, 0], [1920, 1080], Some([527, 296])),
+        ];
+        let err = |t: TapeMeasurement| tape_measurement(&monitors, &pair(true), &t).unwrap_err();
+        assert!(err(tape(1000.5, 0.0)).contains("±1000 mm"));
+        assert!(err(tape(f64::NAN, 0.0)).contains("±1000 mm"));
+        assert!(err(tape(0.0, -5000.5)).contains("±5000 mm"));
+        assert!(tape_measurement(&monitors, &pair(true), &tape(-1000.0, 5000.0)).is_ok());
+    }
+
+    #[test]
+    fn tape_reading_needs_both_sizes() {
+        let monitors = [
+            Monitor::for_test(0, [0, 0], [2560, 1440], Some([597, 336])),
+            Monitor::for_test(1, [2560, 0], [1920, 1080], None),
+        ];
+        let err = tape_measurement(&monitors, &pair(true), &tape(10.0, 0.0)).unwrap_err();
+        assert!(err.starts_with("Display 2 has no known size"), "{err}");
+    }
 }
@@ -1,13 +1,7 @@
-#![allow(unused_must_use)]
-
-use std::ffi::c_void;
-use std::sync::mpsc;
-use windows::core::PCWSTR;
-use windows::Win32::Foundation::*;
-use windows::Win32::Graphics::Gdi::*;
-use windows::Win32::UI::Input::KeyboardAndMouse::*;
-use windows::Win32::UI::WindowsAndMessaging::*;
+use super::ReferenceObject;
 
+/// Monitor bounds in overlay-window coordinates. Always physical pixels:
+/// the overlay thread is per-monitor DPI aware, so no scaling is applied.
 #[derive(Clone)]
 pub struct MonitorRect {
     pub x: i32,
@@ -20,8 +14,25 @@ pub struct MonitorRect {
 pub enum OverlayStep {
     Scale,
     Gap,
-}
-
+    /// Single-monitor step: nested edge markers for measuring TV overscan.
+    /// Only `m1_idx` is used.
+    Overscan,
+    /// Scale only, for monitors that don't share an edge: a fixed square on
+    /// `m2_idx` and one on `m1_idx` the user resizes until both look the
+    /// same physical size.
+    Square,
+    /// Single-monitor step: an outline of a real object the user resizes to
+    /// match it held against the screen. Only `m1_idx` is used.
+    Reference(ReferenceObject),
+}
+
+/// Gap lines at 45° to the shared edge: one pixel of gap, one pixel of shift.
+pub const DEFAULT_GAP_ANGLE_DEG: f64 = 45.0;
+/// Allowed range of the gap line angle.
+pub const MIN_GAP_ANGLE_DEG: f64 = 10.0;
+pub const MAX_GAP_ANGLE_DEG: f64 = 80.0;
+
+#[cfg_attr(not(windows), allow(dead_code))]
 pub struct OverlayConfig {
     pub step: OverlayStep,
     pub m1_idx: usize,
@@ -34,54 +45,103 @@ pub struct OverlayConfig {
     pub temp_middles: Option<[i32; 2]>,
     pub color1: [u8; 3],
     pub color2: [u8; 3],
+    /// Angle between the gap-step lines and the shared edge, in degrees.
+    /// One pixel of gap shifts the lines 1/tan(angle) pixels along the edge,
+    /// so angles below 45° read the gap to a fraction of a pixel.
+    pub gap_angle_deg: f64,
+    /// (trial, of) when the step is repeated. The lines then start at a
+    /// random place each time so a trial can't be confirmed without looking.
+    pub trial: Option<(u32, u32)>,
+    /// Extra note shown in the top corner of both monitors
+    pub caption: Option<String>,
 }
 
 pub struct OverlayResult {
     pub cancelled: bool,
     pub segments: [i32; 4],
-    pub gap: i32,
+    /// Gap in pixels of the far monitor; fractional unless the lines are at 45°
+    pub gap: f64,
+    /// Overscan per edge in percent (only set by the overscan step)
+    pub overscan_pct: f64,
+    /// Side of the resized square on m1 and the fixed one on m2, in pixels
+    /// (only set by the square step)
+    pub square_px: [i32; 2],
+    /// Width of the reference outline in pixels (only set by the reference
+    /// step)
+    pub outline_px: i32,
+}
+
+/// Seed for the start positions of one overlay step: zero (no scatter) unless
+/// the step is repeated, otherwise `entropy` mixed with the trial number.
+pub fn trial_seed(entropy: u64, trial: Option<(u32, u32)>) -> u64 {
+    match trial {
+        Some((trial, _)) => (entropy ^ ((trial as u64) << 32)) | 1,
+        None => 0,
+    }
 }
 
-struct State {
-    step: OverlayStep,
-    m1_idx: usize,
-    m2_idx: usize,
-    monitors: Vec<MonitorRect>,
-    monitor_labels: Vec<String>,
-    bind_horizontal: bool,
-
-    color1: [u8; 3],
-    color2: [u8; 3],
-
-    segments: [i32; 4],
-    gap: i32,
-    mid_m1: i32,
-    mid_m2: i32,
-
-    selected: Option<usize>,
-    dragging: bool,
-    drag_start: i32,
-    drag_start_val: i32,
-    last_interacted: Option<usize>,
-    hovering_line: bool,
-
-    confirmed: bool,
-    cancelled: bool,
+/// Pseudo-random offset in `-range..=range` (xorshift64). It only has to be
+/// unpredictable to the user, not statistically good.
+pub fn scatter(seed: &mut u64, range: i32) -> i32 {
+    *seed ^= *seed << 13;
+    *seed ^= *seed >> 7;
+    *seed ^= *seed << 17;
+    if range <= 0 {
+        return 0;
+    }
+    (*seed % (2 * range as u64 + 1)) as i32 - range
 }
 
-fn rgb(r: u8, g: u8, b: u8) -> COLORREF {
-    COLORREF(r as u32 | ((g as u32) << 8) | ((b as u32) << 16))
+/// Where the scale-step lines start, as `[m1 blue, m2 blue, m1 red, m2 red]`:
+/// a quarter and three quarters along the smaller monitor. With a non-zero
+/// seed each line moves at most an eighth of it, so blue stays in the top
+/// (left) half and red in the bottom (right) half.
+pub fn start_lines(
+    m1: &MonitorRect,
+    m2: &MonitorRect,
+    bind_horizontal: bool,
+    seed: &mut u64,
+) -> [i32; 4] {
+    let (start1, start2, min_extent) = if bind_horizontal {
+        (m1.y, m2.y, m1.h.min(m2.h))
+    } else {
+        (m1.x, m2.x, m1.w.min(m2.w))
+    };
+    let mut lines = [
+        start1 + min_extent / 4,
+        start2 + min_extent / 4,
+        start1 + 3 * min_extent / 4,
+        start2 + 3 * min_extent / 4,
+    ];
+    if *seed != 0 {
+        for line in lines.iter_mut() {
+            *line += scatter(seed, min_extent / 8);
+        }
+    }
+    lines
 }
 
-fn encode_wide(s: &str) -> Vec<u16> {
-    s.encode_utf16().chain(std::iter::once(0)).collect()
+/// Where the gap-step shift starts: 20 to 60 pixels either way with a
+/// non-zero seed, so neither an overlap nor a gap is suggested and the lines
+/// never start already aligned.
+pub fn start_shift(seed: &mut u64) -> i32 {
+    if *seed == 0 {
+        return 0;
+    }
+    let shift = scatter(seed, 40);
+    shift + if shift < 0 { -20 } else { 20 }
 }
 
+#[cfg(windows)]
+mod window;
+
+/// Show the overlay for one step and wait for the user to confirm or cancel.
+#[cfg(windows)]
 pub fn run_overlay(config: OverlayConfig) -> Result<OverlayResult, String> {
-    let (tx, rx) = mpsc::channel();
+    let (tx, rx) = std::sync::mpsc::channel();
 
     std::thread::spawn(move || {
-        let result = run_overlay_window(config);
+        let result = window::run_overlay_window(config);
         let _ = tx.send(result);
     });
 
@@ -89,549 +149,39 @@ pub fn run_overlay(config: OverlayConfig) -> Result<OverlayResult, String> {
         .map_err(|_| "Overlay thread failed".to_string())?
 }
 
-fn run_overlay_window(config: OverlayConfig) -> Result<OverlayResult, String> {
-    unsafe {
-        let vx = GetSystemMetrics(SM_XVIRTUALSCREEN);
-        let vy = GetSystemMetrics(SM_YVIRTUALSCREEN);
-        let vw = GetSystemMetrics(SM_CXVIRTUALSCREEN);
-        let vh = GetSystemMetrics(SM_CYVIRTUALSCREEN);
-
-        let m1 = &config.monitors[config.m1_idx];
-        let m2 = &config.monitors[config.m2_idx];
-
-        let initial_segments = if config.step == OverlayStep::Scale {
-            if config.bind_horizontal {
-                let min_h = m1.h.min(m2.h);
-                [
-                    m1.y + min_h / 4,
-                    m2.y + min_h / 4,
-                    m1.y + 3 * min_h / 4,
-                    m2.y + 3 * min_h / 4,
-                ]
-            } else {
-                let min_w = m1.w.min(m2.w);
-                [
-                    m1.x + min_w / 4,
-                    m2.x + min_w / 4,
-                    m1.x + 3 * min_w / 4,
-                    m2.x + 3 * min_w / 4,
-                ]
-            }
-        } else {
-            [0; 4]
-        };
-
-        let [mid_m1, mid_m2] = config.temp_middles.unwrap_or_else(|| {
-            if config.bind_horizontal {
-                [m1.y + m1.h / 2, m2.y + m2.h / 2]
-            } else {
-                [m1.x + m1.w / 2, m2.x + m2.w / 2]
-            }
-        });
-
-        let mut state = Box::new(State {
-            step: config.step,
-            m1_idx: config.m1_idx,
-            m2_idx: config.m2_idx,
-            monitors: config.monitors,
-            monitor_labels: config.monitor_labels,
-            bind_horizontal: config.bind_horizontal,
-            color1: config.color1,
-            color2: config.color2,
-            segments: initial_segments,
-            gap: 0,
-            mid_m1,
-            mid_m2,
-            selected: None,
-            dragging: false,
-            drag_start: 0,
-            drag_start_val: 0,
-            last_interacted: None,
-            hovering_line: false,
-            confirmed: false,
-            cancelled: false,
-        });
-
-        let class_name = encode_wide("SpanrightCalibrationOverlay");
-        let wc = WNDCLASSW {
-            style: CS_HREDRAW | CS_VREDRAW,
-            lpfnWndProc: Some(wnd_proc),
-            hInstance: HINSTANCE::default(),
-            hCursor: LoadCursorW(HINSTANCE::default(), IDC_ARROW).unwrap_or_default(),
-            lpszClassName: PCWSTR(class_name.as_ptr()),
-            ..Default::default()
-        };
-        RegisterClassW(&wc);
-
-        let state_ptr = &mut *state as *mut State as *const c_void;
-        let hwnd = CreateWindowExW(
-            WS_EX_TOPMOST,
-            PCWSTR(class_name.as_ptr()),
-            PCWSTR::null(),
-            WS_POPUP,
-            vx,
-            vy,
-            vw,
-            vh,
-            HWND::default(),
-            HMENU::default(),
-            HINSTANCE::default(),
-            Some(state_ptr),
-        )
-        .map_err(|e| format!("CreateWindowExW: {e}"))?;
-
-        ShowWindow(hwnd, SW_SHOW);
-        let _ = SetForegroundWindow(hwnd);
-
-        let mut msg = MSG::default();
-        loop {
-            let ret = GetMessageW(&mut msg, HWND::default(), 0, 0);
-            if ret.0 <= 0 {
-                break;
-            }
-            TranslateMessage(&msg);
-            DispatchMessageW(&msg);
-        }
-
-        UnregisterClassW(PCWSTR(class_name.as_ptr()), HINSTANCE::default()).ok();
-
-        Ok(OverlayResult {
-            cancelled: state.cancelled,
-            segments: state.segments,
-            gap: state.gap,
-        })
-    }
-}
-
-unsafe extern "system" fn wnd_proc(
-    hwnd: HWND,
-    msg: u32,
-    wparam: WPARAM,
-    lparam: LPARAM,
-) -> LRESULT {
-    if msg == WM_CREATE {
-        let cs = &*(lparam.0 as *const CREATESTRUCTW);
-        SetWindowLongPtrW(hwnd, GWLP_USERDATA, cs.lpCreateParams as isize);
-        return LRESULT(0);
-    }
-
-    let ptr = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut State;
-    if ptr.is_null() {
-        return DefWindowProcW(hwnd, msg, wparam, lparam);
-    }
-    let state = &mut *ptr;
-
-    match msg {
-        WM_PAINT => {
-            let mut ps = PAINTSTRUCT::default();
-            let hdc = BeginPaint(hwnd, &mut ps);
-
-            let vw = GetSystemMetrics(SM_CXVIRTUALSCREEN);
-            let vh = GetSystemMetrics(SM_CYVIRTUALSCREEN);
-
-            let mem_dc = CreateCompatibleDC(hdc);
-            let draw_hdc = HDC(mem_dc.0);
-            let mem_bmp = CreateCompatibleBitmap(hdc, vw, vh);
-            let old_bmp = SelectObject(draw_hdc, HGDIOBJ(mem_bmp.0));
-
-            match state.step {
-                OverlayStep::Scale => draw_scale(state, draw_hdc),
-                OverlayStep::Gap => draw_gap(state, draw_hdc),
-            }
-
-            BitBlt(hdc, 0, 0, vw, vh, draw_hdc, 0, 0, SRCCOPY);
-
-            SelectObject(draw_hdc, old_bmp);
-            DeleteObject(HGDIOBJ(mem_bmp.0));
-            DeleteDC(mem_dc);
-
-            EndPaint(hwnd, &ps);
-            LRESULT(0)
-        }
-        WM_ERASEBKGND => LRESULT(1),
-        WM_SETCURSOR => {
-            if (lparam.0 & 0xFFFF) as u16 == 1 {
-                let cursor = if state.dragging || state.hovering_line {
-                    LoadCursorW(HINSTANCE::default(), IDC_HAND)
-                } else {
-                    LoadCursorW(HINSTANCE::default(), IDC_ARROW)
-                };
-                SetCursor(cursor.unwrap_or_default());
-                return LRESULT(1);
-            }
-            return DefWindowProcW(hwnd, msg, wparam, lparam);
-        }
-        WM_KEYDOWN => {
-            let vk = VIRTUAL_KEY(wparam.0 as u16);
-            match vk {
-                VK_RETURN => {
-                    state.confirmed = true;
-                    DestroyWindow(hwnd);
-                }
-                VK_ESCAPE => {
-                    state.cancelled = true;
-                    DestroyWindow(hwnd);
-                }
-                VK_UP | VK_DOWN | VK_LEFT | VK_RIGHT => {
-                    let delta = if vk == VK_UP || vk == VK_LEFT {
-                        -1
-                    } else {
-                        1
-                    };
-                    if state.step == OverlayStep::Scale {
-                        if let Some(idx) = state.last_interacted {
-                            state.segments[idx] += delta;
-                            InvalidateRect(hwnd, None, BOOL(0));
-                        }
-                    } else {
-                        state.gap += delta;
-                        InvalidateRect(hwnd, None, BOOL(0));
-                    }
-                }
-                _ => return DefWindowProcW(hwnd, msg, wparam, lparam),
-            }
-            LRESULT(0)
-        }
-        WM_LBUTTONDOWN => {
-            let (mx, my) = mouse_pos(lparam);
-            if state.step == OverlayStep::Scale {
-                state.selected = hit_test_scale(state, mx, my);
-                if state.selected.is_some() {
-                    state.dragging = true;
-                    state.drag_start = if state.bind_horizontal { my } else { mx };
-                    state.drag_start_val = state.segments[state.selected.unwrap()];
-                    state.last_interacted = state.selected;
-                    SetCapture(hwnd);
-                }
-            } else {
-                state.dragging = true;
-                state.drag_start = if state.bind_horizontal { mx } else { my };
-                state.drag_start_val = state.gap;
-                SetCapture(hwnd);
-            }
-            LRESULT(0)
-        }
-        WM_MOUSEMOVE => {
-            let (mx, my) = mouse_pos(lparam);
-
-            state.hovering_line = if state.step == OverlayStep::Scale {
-                hit_test_scale(state, mx, my).is_some()
-            } else {
-                hit_test_gap(state, mx, my)
-            };
-
-            if state.dragging {
-                if state.step == OverlayStep::Scale {
-                    if let Some(idx) = state.selected {
-                        let pos = if state.bind_horizontal { my } else { mx };
-                        let delta = pos - state.drag_start;
-                        state.segments[idx] = state.drag_start_val + delta;
-                        InvalidateRect(hwnd, None, BOOL(0));
-                    }
-                } else {
-                    let pos = if state.bind_horizontal { mx } else { my };
-                    let delta = pos - state.drag_start;
-                    state.gap = state.drag_start_val + delta;
-                    InvalidateRect(hwnd, None, BOOL(0));
-                }
-            }
-            LRESULT(0)
-        }
-        WM_LBUTTONUP => {
-            if state.dragging {
-                state.dragging = false;
-                state.selected = None;
-                ReleaseCapture().ok();
-            }
-            LRESULT(0)
-        }
-        WM_DESTROY => {
-            PostQuitMessage(0);
-            LRESULT(0)
-        }
-        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
-    }
-}
-
-fn mouse_pos(lparam: LPARAM) -> (i32, i32) {
-    let x = (lparam.0 & 0xFFFF) as i16 as i32;
-    let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
-    (x, y)
-}
-
-fn hit_test_scale(state: &State, mx: i32, my: i32) -> Option<usize> {
-    let m1 = &state.monitors[state.m1_idx];
-    let m2 = &state.monitors[state.m2_idx];
-    let threshold = 20;
-
-    if state.bind_horizontal {
-        let rects = [
-            (m1.x, m1.x + m1.w, state.segments[0]),
-            (m2.x, m2.x + m2.w, state.segments[1]),
-            (m1.x, m1.x + m1.w, state.segments[2]),
-            (m2.x, m2.x + m2.w, state.segments[3]),
-        ];
-        for (i, &(left, right, y)) in rects.iter().enumerate() {
-            if mx >= left && mx <= right && (my - y).abs() <= threshold {
-                return Some(i);
-            }
-        }
-    } else {
-        let rects = [
-            (m1.y, m1.y + m1.h, state.segments[0]),
-            (m2.y, m2.y + m2.h, state.segments[1]),
-            (m1.y, m1.y + m1.h, state.segments[2]),
-            (m2.y, m2.y + m2.h, state.segments[3]),
-        ];
-        for (i, &(top, bottom, x)) in rects.iter().enumerate() {
-            if my >= top && my <= bottom && (mx - x).abs() <= threshold {
-                return Some(i);
-            }
-        }
-    }
-    None
+#[cfg(not(windows))]
+pub fn run_overlay(_config: OverlayConfig) -> Result<OverlayResult, String> {
+    Err("The calibration overlay needs Windows".to_string())
 }
 
-fn hit_test_gap(state: &State, mx: i32, my: i32) -> bool {
-    let m1 = &state.monitors[state.m1_idx];
-    let m2 = &state.monitors[state.m2_idx];
-    let threshold = 150;
-
-    if state.bind_horizontal {
-        let boundary_x = if m1.x < m2.x {
-            m1.x + m1.w
-        } else {
-            m2.x + m2.w
-        };
-        (mx - boundary_x).abs() <= threshold
-    } else {
-        let boundary_y = if m1.y < m2.y {
-            m1.y + m1.h
-        } else {
-            m2.y + m2.h
-        };
-        (my - boundary_y).abs() <= threshold
+/// Top-left corner of the virtual screen, where overlay coordinates start.
+#[cfg(windows)]
+pub fn virtual_screen_origin() -> (i32, i32) {
+    use windows::Win32::UI::WindowsAndMessaging::*;
+    unsafe {
+        (
+            GetSystemMetrics(SM_XVIRTUALSCREEN),
+            GetSystemMetrics(SM_YVIRTUALSCREEN),
+        )
     }
 }
 
-unsafe fn draw_scale(state: &State, hdc: HDC) {
-    let m1 = &state.monitors[state.m1_idx];
-    let m2 = &state.monitors[state.m2_idx];
-
-    fill_background(hdc, state);
-
-    draw_monitor_frame(hdc, m1);
-    draw_monitor_frame(hdc, m2);
-
-    for (i, m) in state.monitors.iter().enumerate() {
-        if let Some(label) = state.monitor_labels.get(i) {
-            draw_monitor_label(hdc, m, label);
-        }
-    }
-
-    let color1 = rgb(state.color1[0], state.color1[1], state.color1[2]);
-    let color2 = rgb(state.color2[0], state.color2[1], state.color2[2]);
-    let line_h = 6;
-
-    if state.bind_horizontal {
-        fill_rect(hdc, m1.x, state.segments[0] - line_h / 2, m1.w, line_h, color1);
-        fill_rect(hdc, m2.x, state.segments[1] - line_h / 2, m2.w, line_h, color1);
-        fill_rect(hdc, m1.x, state.segments[2] - line_h / 2, m1.w, line_h, color2);
-        fill_rect(hdc, m2.x, state.segments[3] - line_h / 2, m2.w, line_h, color2);
-    } else {
-        let line_w = 6;
-        fill_rect(hdc, state.segments[0] - line_w / 2, m1.y, line_w, m1.h, color1);
-        fill_rect(hdc, state.segments[1] - line_w / 2, m2.y, line_w, m2.h, color1);
-        fill_rect(hdc, state.segments[2] - line_w / 2, m1.y, line_w, m1.h, color2);
-        fill_rect(hdc, state.segments[3] - line_w / 2, m2.y, line_w, m2.h, color2);
-    }
-
-    let text = "Drag each colored line so it sits at the same physical height on both displays. \n\
-                Keep both lines far apart for best accuracy. \n\
-                Arrow keys: \u{00B1}1px  |  Enter: confirm  |  Esc: cancel";
-    draw_text_at(hdc, m1.x + 20, m1.y + m1.h - 60, text);
-    draw_text_at(hdc, m2.x + 20, m2.y + m2.h - 60, text);
+#[cfg(not(windows))]
+pub fn virtual_screen_origin() -> (i32, i32) {
+    (0, 0)
 }
 
-unsafe fn draw_gap(state: &State, hdc: HDC) {
-    let m1 = &state.monitors[state.m1_idx];
-    let m2 = &state.monitors[state.m2_idx];
-
-    fill_background(hdc, state);
-    draw_monitor_frame(hdc, m1);
-    draw_monitor_frame(hdc, m2);
-
-    for (i, m) in state.monitors.iter().enumerate() {
-        if let Some(label) = state.monitor_labels.get(i) {
-            draw_monitor_label(hdc, m, label);
-        }
-    }
-
-    let color1 = rgb(state.color1[0], state.color1[1], state.color1[2]);
-    let color2 = rgb(state.color2[0], state.color2[1], state.color2[2]);
-    let gap = state.gap;
-    let pen_w = 4;
-
-    if state.bind_horizontal {
-        let (left_mid, right_mid, left_m, right_m) = if m1.x < m2.x {
-            (state.mid_m1, state.mid_m2, m1, m2)
-        } else {
-            (state.mid_m2, state.mid_m1, m2, m1)
-        };
-
-        let bx = left_m.x + left_m.w;
-        let arm = (left_m.w.min(right_m.w) * 2 / 5).max(150);
-        let inset = pen_w + 2; // pull lines back from edges to prevent bleed
+/// Smallest side of the resizable square, in pixels.
+const MIN_SQUARE_PX: i32 = 40;
 
-        // All lines stay at exactly 45 degrees. The gap translates the
-        // right-side lines vertically: a 45-degree line crossing G pixels of
-        // horizontal gap drops G pixels vertically, so the right-side pair
-        // shifts by +gap / -gap respectively.
-
-        // Left monitor lines are fixed at left_mid (anchored)
-        draw_line(hdc, bx - arm, left_mid - arm, bx - inset, left_mid - inset, color1, pen_w);
-        draw_line(hdc, bx - arm, left_mid + arm, bx - inset, left_mid + inset, color2, pen_w);
-
-        // Right monitor lines translated by gap
-        draw_line(hdc, bx + inset, right_mid + gap + inset, bx + arm, right_mid + gap + arm, color1, pen_w);
-        draw_line(hdc, bx + inset, right_mid - gap - inset, bx + arm, right_mid - gap - arm, color2, pen_w);
+/// Widest outline of `object` that fits on a monitor with a margin.
+pub fn max_outline(m: &MonitorRect, object: ReferenceObject) -> i32 {
+    let [w_mm, h_mm] = object.size_mm();
+    let by_width = m.w - 40;
+    let by_height = if h_mm > 0.0 {
+        ((m.h - 40) as f64 * w_mm / h_mm) as i32
     } else {
-        let (top_mid, bottom_mid, top_m, bottom_m) = if m1.y < m2.y {
-            (state.mid_m1, state.mid_m2, m1, m2)
-        } else {
-            (state.mid_m2, state.mid_m1, m2, m1)
-        };
-
-        let by = top_m.y + top_m.h;
-        let arm = (top_m.h.min(bottom_m.h) * 2 / 5).max(150);
-        let inset = pen_w + 2;
-
-        draw_line(hdc, top_mid - arm, by - arm, top_mid - inset, by - inset, color1, pen_w);
-        draw_line(hdc, top_mid + arm, by - arm, top_mid + inset, by - inset, color2, pen_w);
-
-        draw_line(hdc, bottom_mid + gap + inset, by + inset, bottom_mid + gap + arm, by + arm, color1, pen_w);
-        draw_line(hdc, bottom_mid - gap - inset, by + inset, bottom_mid - gap - arm, by + arm, color2, pen_w);
-    }
-
-    let text = format!(
-        "Gap: {}px  |  Drag or arrow keys to adjust so both diagonal lines are aligned  |  Enter: confirm  |  Esc: cancel",
-        gap
-    );
-    draw_text_at(hdc, m1.x + 20, m1.y + m1.h - 40, &text);
-    draw_text_at(hdc, m2.x + 20, m2.y + m2.h - 40, &text);
-}
-
-unsafe fn fill_background(hdc: HDC, state: &State) {
-    let vw = GetSystemMetrics(SM_CXVIRTUALSCREEN);
-    let vh = GetSystemMetrics(SM_CYVIRTUALSCREEN);
-
-    let bg = CreateSolidBrush(rgb(10, 12, 18));
-    let full = RECT {
-        left: 0,
-        top: 0,
-        right: vw,
-        bottom: vh,
-    };
-    FillRect(hdc, &full, bg);
-    DeleteObject(HGDIOBJ(bg.0));
-
-    // Slightly lighter bg on paired monitors
-    let light = CreateSolidBrush(rgb(22, 25, 32));
-    for &idx in &[state.m1_idx, state.m2_idx] {
-        let m = &state.monitors[idx];
-        let r = RECT {
-            left: m.x,
-            top: m.y,
-            right: m.x + m.w,
-            bottom: m.y + m.h,
-        };
-        FillRect(hdc, &r, light);
-    }
-    DeleteObject(HGDIOBJ(light.0));
-}
-
-unsafe fn draw_monitor_frame(hdc: HDC, m: &MonitorRect) {
-    let pen = CreatePen(PS_SOLID, 1, rgb(60, 65, 80));
-    let old = SelectObject(hdc, HGDIOBJ(pen.0));
-    let null_brush = GetStockObject(NULL_BRUSH);
-    let old_brush = SelectObject(hdc, null_brush);
-
-    Rectangle(hdc, m.x, m.y, m.x + m.w, m.y + m.h);
-
-    SelectObject(hdc, old_brush);
-    SelectObject(hdc, old);
-    DeleteObject(HGDIOBJ(pen.0));
-}
-
-unsafe fn fill_rect(hdc: HDC, x: i32, y: i32, w: i32, h: i32, color: COLORREF) {
-    let brush = CreateSolidBrush(color);
-    let r = RECT {
-        left: x,
-        top: y,
-        right: x + w,
-        bottom: y + h,
+        by_width
     };
-    FillRect(hdc, &r, brush);
-    DeleteObject(HGDIOBJ(brush.0));
-}
-
-unsafe fn draw_line(hdc: HDC, x1: i32, y1: i32, x2: i32, y2: i32, color: COLORREF, width: i32) {
-    let pen = CreatePen(PS_SOLID, width, color);
-    let old = SelectObject(hdc, HGDIOBJ(pen.0));
-    MoveToEx(hdc, x1, y1, None);
-    LineTo(hdc, x2, y2);
-    SelectObject(hdc, old);
-    DeleteObject(HGDIOBJ(pen.0));
-}
-
-unsafe fn draw_text_at(hdc: HDC, x: i32, y: i32, text: &str) {
-    SetBkMode(hdc, TRANSPARENT);
-    SetTextColor(hdc, rgb(160, 165, 175));
-    let wide: Vec<u16> = text.encode_utf16().collect();
-    TextOutW(hdc, x, y, &wide);
-}
-
-unsafe fn draw_monitor_label(hdc: HDC, m: &MonitorRect, label: &str) {
-    if label.is_empty() {
-        return;
-    }
-
-    let font_height = (m.h / 35).clamp(20, 70);
-    let face = encode_wide("Segoe UI");
-    let font = CreateFontW(
-        font_height,
-        0,
-        0,
-        0,
-        FW_NORMAL.0 as i32,
-        0,
-        0,
-        0,
-        DEFAULT_CHARSET.0 as u32,
-        OUT_DEFAULT_PRECIS.0 as u32,
-        CLIP_DEFAULT_PRECIS.0 as u32,
-        CLEARTYPE_QUALITY.0 as u32,
-        0,
-        PCWSTR(face.as_ptr()),
-    );
-    let old_font = SelectObject(hdc, HGDIOBJ(font.0));
-    SetBkMode(hdc, TRANSPARENT);
-    SetTextColor(hdc, rgb(80, 85, 105));
-
-    let lines: Vec<&str> = label.lines().collect();
-    let line_spacing = font_height + 6;
-    let total_height = lines.len() as i32 * line_spacing;
-    let start_y = m.y + (m.h - total_height) / 2;
-
-    for (i, line) in lines.iter().enumerate() {
-        let wide: Vec<u16> = line.encode_utf16().collect();
-        let mut size = SIZE::default();
-        GetTextExtentPoint32W(hdc, &wide, &mut size);
-        let tx = m.x + (m.w - size.cx) / 2;
-        let ty = start_y + i as i32 * line_spacing;
-        TextOutW(hdc, tx, ty, &wide);
-    }
-
-    SelectObject(hdc, old_font);
-    DeleteObject(HGDIOBJ(font.0));
+    by_width.min(by_height).max(MIN_SQUARE_PX)
 }
@@ -0,0 +1,965 @@
+//! The overlay window itself: a borderless topmost window across the whole
+//! virtual screen, drawn with GDI.
+
+#![allow(unused_must_use)]
+
+use std::ffi::c_void;
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::*;
+use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::UI::HiDpi::*;
+use windows::Win32::UI::Input::KeyboardAndMouse::*;
+use windows::Win32::UI::WindowsAndMessaging::*;
+
+use super::*;
+
+/// Spacing between nested overscan markers, in percent of the monitor size.
+const OVERSCAN_MARKER_STEP_PCT: f64 = 0.5;
+/// Number of overscan markers; the innermost one sits at 10% per edge.
+const OVERSCAN_MARKER_COUNT: i32 = 21;
+
+struct State {
+    step: OverlayStep,
+    m1_idx: usize,
+    m2_idx: usize,
+    monitors: Vec<MonitorRect>,
+    monitor_labels: Vec<String>,
+    bind_horizontal: bool,
+
+    color1: [u8; 3],
+    color2: [u8; 3],
+
+    segments: [i32; 4],
+    /// Along-edge shift of the far gap lines, in whole pixels
+    shift: i32,
+    gap_angle_deg: f64,
+    mid_m1: i32,
+    mid_m2: i32,
+    overscan_marker: i32,
+    /// Sides of the resizable square on m1 and the fixed square on m2
+    square: i32,
+    fixed_square: i32,
+    /// Width of the reference outline
+    outline: i32,
+    trial: Option<(u32, u32)>,
+    caption: Option<String>,
+
+    selected: Option<usize>,
+    dragging: bool,
+    drag_start: i32,
+    drag_start_val: i32,
+    last_interacted: Option<usize>,
+    hovering_line: bool,
+
+    confirmed: bool,
+    cancelled: bool,
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> COLORREF {
+    COLORREF(r as u32 | ((g as u32) << 8) | ((b as u32) << 16))
+}
+
+fn encode_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+pub fn run_overlay_window(config: OverlayConfig) -> Result<OverlayResult, String> {
+    unsafe {
+        // The window spans monitors with different scaling; keep every
+        // coordinate (metrics, drawing, mouse) in physical pixels.
+        SetThreadDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+
+        let vx = GetSystemMetrics(SM_XVIRTUALSCREEN);
+        let vy = GetSystemMetrics(SM_YVIRTUALSCREEN);
+        let vw = GetSystemMetrics(SM_CXVIRTUALSCREEN);
+        let vh = GetSystemMetrics(SM_CYVIRTUALSCREEN);
+
+        let m1 = &config.monitors[config.m1_idx];
+        let m2 = &config.monitors[config.m2_idx];
+
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        let mut seed = trial_seed(nanos, config.trial);
+
+        let initial_segments = if config.step == OverlayStep::Scale {
+            start_lines(m1, m2, config.bind_horizontal, &mut seed)
+        } else {
+            [0; 4]
+        };
+        let initial_shift = if config.step == OverlayStep::Gap {
+            start_shift(&mut seed)
+        } else {
+            0
+        };
+        let fixed_square = (m2.w.min(m2.h) / 3).max(MIN_SQUARE_PX);
+        let mut initial_square = fixed_square;
+        if seed != 0 && config.step == OverlayStep::Square {
+            initial_square += scatter(&mut seed, fixed_square / 4);
+        }
+        let largest_square = max_square(m1);
+        let initial_outline = match config.step {
+            OverlayStep::Reference(object) => max_outline(m1, object) / 2,
+            _ => 0,
+        };
+
+        let [mid_m1, mid_m2] = config.temp_middles.unwrap_or_else(|| {
+            if config.bind_horizontal {
+                [m1.y + m1.h / 2, m2.y + m2.h / 2]
+            } else {
+                [m1.x + m1.w / 2, m2.x + m2.w / 2]
+            }
+        });
+
+        let mut state = Box::new(State {
+            step: config.step,
+            m1_idx: config.m1_idx,
+            m2_idx: config.m2_idx,
+            monitors: config.monitors,
+            monitor_labels: config.monitor_labels,
+            bind_horizontal: config.bind_horizontal,
+            color1: config.color1,
+            color2: config.color2,
+            segments: initial_segments,
+            shift: initial_shift,
+            gap_angle_deg: config
+                .gap_angle_deg
+                .clamp(MIN_GAP_ANGLE_DEG, MAX_GAP_ANGLE_DEG),
+            mid_m1,
+            mid_m2,
+            overscan_marker: 0,
+            square: initial_square.clamp(MIN_SQUARE_PX, largest_square),
+            fixed_square,
+            outline: initial_outline,
+            trial: config.trial,
+            caption: config.caption,
+            selected: None,
+            dragging: false,
+            drag_start: 0,
+            drag_start_val: 0,
+            last_interacted: None,
+            hovering_line: false,
+            confirmed: false,
+            cancelled: false,
+        });
+
+        let class_name = encode_wide("SpanrightCalibrationOverlay");
+        let wc = WNDCLASSW {
+            style: CS_HREDRAW | CS_VREDRAW,
+            lpfnWndProc: Some(wnd_proc),
+            hInstance: HINSTANCE::default(),
+            hCursor: LoadCursorW(HINSTANCE::default(), IDC_ARROW).unwrap_or_default(),
+            lpszClassName: PCWSTR(class_name.as_ptr()),
+            ..Default::default()
+        };
+        RegisterClassW(&wc);
+
+        let state_ptr = &mut *state as *mut State as *const c_void;
+        let hwnd = CreateWindowExW(
+            WS_EX_TOPMOST,
+            PCWSTR(class_name.as_ptr()),
+            PCWSTR::null(),
+            WS_POPUP,
+            vx,
+            vy,
+            vw,
+            vh,
+            HWND::default(),
+            HMENU::default(),
+            HINSTANCE::default(),
+            Some(state_ptr),
+        )
+        .map_err(|e| format!("CreateWindowExW: {e}"))?;
+
+        ShowWindow(hwnd, SW_SHOW);
+        let _ = SetForegroundWindow(hwnd);
+
+        let mut msg = MSG::default();
+        loop {
+            let ret = GetMessageW(&mut msg, HWND::default(), 0, 0);
+            if ret.0 <= 0 {
+                break;
+            }
+            TranslateMessage(&msg);
+            DispatchMessageW(&msg);
+        }
+
+        UnregisterClassW(PCWSTR(class_name.as_ptr()), HINSTANCE::default()).ok();
+
+        Ok(OverlayResult {
+            cancelled: state.cancelled,
+            segments: state.segments,
+            gap: gap_px(&state),
+            overscan_pct: state.overscan_marker as f64 * OVERSCAN_MARKER_STEP_PCT,
+            square_px: [state.square, state.fixed_square],
+            outline_px: state.outline,
+        })
+    }
+}
+
+unsafe extern "system" fn wnd_proc(
+    hwnd: HWND,
+    msg: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if msg == WM_CREATE {
+        let cs = &*(lparam.0 as *const CREATESTRUCTW);
+        SetWindowLongPtrW(hwnd, GWLP_USERDATA, cs.lpCreateParams as isize);
+        return LRESULT(0);
+    }
+
+    let ptr = GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *mut State;
+    if ptr.is_null() {
+        return DefWindowProcW(hwnd, msg, wparam, lparam);
+    }
+    let state = &mut *ptr;
+
+    match msg {
+        WM_PAINT => {
+            let mut ps = PAINTSTRUCT::default();
+            let hdc = BeginPaint(hwnd, &mut ps);
+
+            let vw = GetSystemMetrics(SM_CXVIRTUALSCREEN);
+            let vh = GetSystemMetrics(SM_CYVIRTUALSCREEN);
+
+            let mem_dc = CreateCompatibleDC(hdc);
+            let draw_hdc = HDC(mem_dc.0);
+            let mem_bmp = CreateCompatibleBitmap(hdc, vw, vh);
+            let old_bmp = SelectObject(draw_hdc, HGDIOBJ(mem_bmp.0));
+
+            match state.step {
+                OverlayStep::Scale => draw_scale(state, draw_hdc),
+                OverlayStep::Gap => draw_gap(state, draw_hdc),
+                OverlayStep::Overscan => draw_overscan(state, draw_hdc),
+                OverlayStep::Square => draw_square(state, draw_hdc),
+                OverlayStep::Reference(object) => draw_reference(state, object, draw_hdc),
+            }
+
+            BitBlt(hdc, 0, 0, vw, vh, draw_hdc, 0, 0, SRCCOPY);
+
+            SelectObject(draw_hdc, old_bmp);
+            DeleteObject(HGDIOBJ(mem_bmp.0));
+            DeleteDC(mem_dc);
+
+            EndPaint(hwnd, &ps);
+            LRESULT(0)
+        }
+        WM_ERASEBKGND => LRESULT(1),
+        // The overlay must keep covering the virtual screen 1:1 in physical
+        // pixels, so ignore the suggested rect when crossing a DPI boundary.
+        WM_DPICHANGED => LRESULT(0),
+        WM_SETCURSOR => {
+            if (lparam.0 & 0xFFFF) as u16 == 1 {
+                let cursor = if state.dragging || state.hovering_line {
+                    LoadCursorW(HINSTANCE::default(), IDC_HAND)
+                } else {
+                    LoadCursorW(HINSTANCE::default(), IDC_ARROW)
+                };
+                SetCursor(cursor.unwrap_or_default());
+                return LRESULT(1);
+            }
+            DefWindowProcW(hwnd, msg, wparam, lparam)
+        }
+        WM_KEYDOWN => {
+            let vk = VIRTUAL_KEY(wparam.0 as u16);
+            match vk {
+                VK_RETURN => {
+                    state.confirmed = true;
+                    DestroyWindow(hwnd);
+                }
+                VK_ESCAPE => {
+                    state.cancelled = true;
+                    DestroyWindow(hwnd);
+                }
+                VK_UP | VK_DOWN | VK_LEFT | VK_RIGHT => {
+                    let delta = if vk == VK_UP || vk == VK_LEFT {
+                        -1
+                    } else {
+                        1
+                    };
+                    match state.step {
+                        OverlayStep::Scale => {
+                            if let Some(idx) = state.last_interacted {
+                                state.segments[idx] += delta;
+                                InvalidateRect(hwnd, None, BOOL(0));
+                            }
+                        }
+                        OverlayStep::Gap => {
+                            state.shift += delta;
+                            InvalidateRect(hwnd, None, BOOL(0));
+                        }
+                        OverlayStep::Overscan => {
+                            state.overscan_marker = (state.overscan_marker + delta)
+                                .clamp(0, OVERSCAN_MARKER_COUNT - 1);
+                            InvalidateRect(hwnd, None, BOOL(0));
+                        }
+                        OverlayStep::Square => {
+                            // Up and right grow the square
+                            let grow = if vk == VK_UP || vk == VK_RIGHT { 1 } else { -1 };
+                            let max = max_square(&state.monitors[state.m1_idx]);
+                            state.square = (state.square + grow).clamp(MIN_SQUARE_PX, max);
+                            InvalidateRect(hwnd, None, BOOL(0));
+                        }
+                        OverlayStep::Reference(object) => {
+                            let grow = if vk == VK_UP || vk == VK_RIGHT { 1 } else { -1 };
+                            let max = max_outline(&state.monitors[state.m1_idx], object);
+                            state.outline = (state.outline + grow).clamp(MIN_SQUARE_PX, max);
+                            InvalidateRect(hwnd, None, BOOL(0));
+                        }
+                    }
+                }
+                _ => return DefWindowProcW(hwnd, msg, wparam, lparam),
+            }
+            LRESULT(0)
+        }
+        WM_LBUTTONDOWN => {
+            let (mx, my) = mouse_pos(lparam);
+            match state.step {
+                OverlayStep::Scale => {
+                    state.selected = hit_test_scale(state, mx, my);
+                    if let Some(idx) = state.selected {
+                        state.dragging = true;
+                        state.drag_start = if state.bind_horizontal { my } else { mx };
+                        state.drag_start_val = state.segments[idx];
+                        state.last_interacted = state.selected;
+                        SetCapture(hwnd);
+                    }
+                }
+                OverlayStep::Gap => {
+                    state.dragging = true;
+                    state.drag_start = if state.bind_horizontal { mx } else { my };
+                    state.drag_start_val = state.shift;
+                    SetCapture(hwnd);
+                }
+                OverlayStep::Overscan => {
+                    if let Some(marker) = hit_test_overscan(state, mx, my) {
+                        state.overscan_marker = marker;
+                        InvalidateRect(hwnd, None, BOOL(0));
+                    }
+                }
+                OverlayStep::Square => {
+                    if hit_test_square(state, mx, my) {
+                        state.dragging = true;
+                        SetCapture(hwnd);
+                    }
+                }
+                OverlayStep::Reference(object) => {
+                    if hit_test_outline(state, object, mx, my) {
+                        state.dragging = true;
+                        SetCapture(hwnd);
+                    }
+                }
+            }
+            LRESULT(0)
+        }
+        WM_MOUSEMOVE => {
+            let (mx, my) = mouse_pos(lparam);
+
+            state.hovering_line = match state.step {
+                OverlayStep::Scale => hit_test_scale(state, mx, my).is_some(),
+                OverlayStep::Gap => hit_test_gap(state, mx, my),
+                OverlayStep::Overscan => hit_test_overscan(state, mx, my).is_some(),
+                OverlayStep::Square => hit_test_square(state, mx, my),
+                OverlayStep::Reference(object) => hit_test_outline(state, object, mx, my),
+            };
+
+            if state.dragging {
+                if state.step == OverlayStep::Scale {
+                    if let Some(idx) = state.selected {
+                        let pos = if state.bind_horizontal { my } else { mx };
+                        let delta = pos - state.drag_start;
+                        state.segments[idx] = state.drag_start_val + delta;
+                        InvalidateRect(hwnd, None, BOOL(0));
+                    }
+                } else if state.step == OverlayStep::Square {
+                    // The square stays centred; the dragged corner sets its size
+                    let m = &state.monitors[state.m1_idx];
+                    let (cx, cy) = (m.x + m.w / 2, m.y + m.h / 2);
+                    let half = (mx - cx).abs().max((my - cy).abs());
+                    state.square = (2 * half).clamp(MIN_SQUARE_PX, max_square(m));
+                    InvalidateRect(hwnd, None, BOOL(0));
+                } else if let OverlayStep::Reference(object) = state.step {
+                    // Centred like the square; the dragged edge sets the width
+                    let m = &state.monitors[state.m1_idx];
+                    let half = outline_half_width(m, object, mx, my);
+                    state.outline = (2 * half).clamp(MIN_SQUARE_PX, max_outline(m, object));
+                    InvalidateRect(hwnd, None, BOOL(0));
+                } else {
+                    let pos = if state.bind_horizontal { mx } else { my };
+                    let delta = pos - state.drag_start;
+                    state.shift = state.drag_start_val + delta;
+                    InvalidateRect(hwnd, None, BOOL(0));
+                }
+            }
+            LRESULT(0)
+        }
+        WM_LBUTTONUP => {
+            if state.dragging {
+                state.dragging = false;
+                state.selected = None;
+                ReleaseCapture().ok();
+            }
+            LRESULT(0)
+        }
+        WM_DESTROY => {
+            PostQuitMessage(0);
+            LRESULT(0)
+        }
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
+
+fn mouse_pos(lparam: LPARAM) -> (i32, i32) {
+    let x = (lparam.0 & 0xFFFF) as i16 as i32;
+    let y = ((lparam.0 >> 16) & 0xFFFF) as i16 as i32;
+    (x, y)
+}
+
+fn hit_test_scale(state: &State, mx: i32, my: i32) -> Option<usize> {
+    let m1 = &state.monitors[state.m1_idx];
+    let m2 = &state.monitors[state.m2_idx];
+    let threshold = 20;
+
+    if state.bind_horizontal {
+        let rects = [
+            (m1.x, m1.x + m1.w, state.segments[0]),
+            (m2.x, m2.x + m2.w, state.segments[1]),
+            (m1.x, m1.x + m1.w, state.segments[2]),
+            (m2.x, m2.x + m2.w, state.segments[3]),
+        ];
+        for (i, &(left, right, y)) in rects.iter().enumerate() {
+            if mx >= left && mx <= right && (my - y).abs() <= threshold {
+                return Some(i);
+            }
+        }
+    } else {
+        let rects = [
+            (m1.y, m1.y + m1.h, state.segments[0]),
+            (m2.y, m2.y + m2.h, state.segments[1]),
+            (m1.y, m1.y + m1.h, state.segments[2]),
+            (m2.y, m2.y + m2.h, state.segments[3]),
+        ];
+        for (i, &(top, bottom, x)) in rects.iter().enumerate() {
+            if my >= top && my <= bottom && (mx - x).abs() <= threshold {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+fn hit_test_gap(state: &State, mx: i32, my: i32) -> bool {
+    let g = gap_geometry(state);
+    let margin = 30;
+
+    // Anywhere over the lines' bounding box, with a margin
+    let (across, along) = if state.bind_horizontal {
+        (mx - g.boundary, my)
+    } else {
+        (my - g.boundary, mx)
+    };
+    let mids = [g.near_mid, g.far_mid + state.shift];
+    across.abs() <= g.arm + margin
+        && mids.iter().any(|mid| (along - mid).abs() <= g.arm_along + margin)
+}
+
+/// Pick the overscan marker closest to the mouse, by distance from the
+/// nearest monitor edge.
+fn hit_test_overscan(state: &State, mx: i32, my: i32) -> Option<i32> {
+    let m = &state.monitors[state.m1_idx];
+    if mx < m.x || mx > m.x + m.w || my < m.y || my > m.y + m.h {
+        return None;
+    }
+    let step_x = m.w as f64 * OVERSCAN_MARKER_STEP_PCT / 100.0;
+    let step_y = m.h as f64 * OVERSCAN_MARKER_STEP_PCT / 100.0;
+    let marker_x = ((mx - m.x).min(m.x + m.w - mx) as f64 / step_x).round();
+    let marker_y = ((my - m.y).min(m.y + m.h - my) as f64 / step_y).round();
+    let marker = marker_x.min(marker_y) as i32;
+    if marker < OVERSCAN_MARKER_COUNT {
+        Some(marker)
+    } else {
+        None
+    }
+}
+
+/// Whether the mouse is on the outline of the resizable square.
+fn hit_test_square(state: &State, mx: i32, my: i32) -> bool {
+    let m = &state.monitors[state.m1_idx];
+    let (cx, cy) = (m.x + m.w / 2, m.y + m.h / 2);
+    let half = (mx - cx).abs().max((my - cy).abs());
+    (half - state.square / 2).abs() <= 20
+}
+
+/// Half the width of a centred outline of `object` whose edge passes
+/// through the mouse.
+fn outline_half_width(m: &MonitorRect, object: ReferenceObject, mx: i32, my: i32) -> i32 {
+    let (cx, cy) = (m.x + m.w / 2, m.y + m.h / 2);
+    let [w_mm, h_mm] = object.size_mm();
+    if h_mm <= 0.0 {
+        return (mx - cx).abs();
+    }
+    let dy_as_width = ((my - cy).abs() as f64 * w_mm / h_mm).round() as i32;
+    (mx - cx).abs().max(dy_as_width)
+}
+
+/// Whether the mouse is on the edge of the reference outline.
+fn hit_test_outline(state: &State, object: ReferenceObject, mx: i32, my: i32) -> bool {
+    let m = &state.monitors[state.m1_idx];
+    (outline_half_width(m, object, mx, my) - state.outline / 2).abs() <= 20
+}
+
+unsafe fn draw_scale(state: &State, hdc: HDC) {
+    let m1 = &state.monitors[state.m1_idx];
+    let m2 = &state.monitors[state.m2_idx];
+
+    fill_background(hdc, state);
+
+    draw_monitor_frame(hdc, m1);
+    draw_monitor_frame(hdc, m2);
+
+    for (i, m) in state.monitors.iter().enumerate() {
+        if let Some(label) = state.monitor_labels.get(i) {
+            draw_monitor_label(hdc, m, label);
+        }
+    }
+
+    let color1 = rgb(state.color1[0], state.color1[1], state.color1[2]);
+    let color2 = rgb(state.color2[0], state.color2[1], state.color2[2]);
+    let line_h = 6;
+
+    if state.bind_horizontal {
+        fill_rect(hdc, m1.x, state.segments[0] - line_h / 2, m1.w, line_h, color1);
+        fill_rect(hdc, m2.x, state.segments[1] - line_h / 2, m2.w, line_h, color1);
+        fill_rect(hdc, m1.x, state.segments[2] - line_h / 2, m1.w, line_h, color2);
+        fill_rect(hdc, m2.x, state.segments[3] - line_h / 2, m2.w, line_h, color2);
+    } else {
+        let line_w = 6;
+        fill_rect(hdc, state.segments[0] - line_w / 2, m1.y, line_w, m1.h, color1);
+        fill_rect(hdc, state.segments[1] - line_w / 2, m2.y, line_w, m2.h, color1);
+        fill_rect(hdc, state.segments[2] - line_w / 2, m1.y, line_w, m1.h, color2);
+        fill_rect(hdc, state.segments[3] - line_w / 2, m2.y, line_w, m2.h, color2);
+    }
+
+    let text = "Drag each colored line so it sits at the same physical height on both displays. \n\
+                Keep both lines far apart for best accuracy. \n\
+                Arrow keys: \u{00B1}1px  |  Enter: confirm  |  Esc: cancel";
+    draw_text_at(hdc, m1.x + 20, m1.y + m1.h - 60, text);
+    draw_text_at(hdc, m2.x + 20, m2.y + m2.h - 60, text);
+    draw_caption(state, hdc);
+}
+
+unsafe fn draw_gap(state: &State, hdc: HDC) {
+    let m1 = &state.monitors[state.m1_idx];
+    let m2 = &state.monitors[state.m2_idx];
+
+    fill_background(hdc, state);
+    draw_monitor_frame(hdc, m1);
+    draw_monitor_frame(hdc, m2);
+
+    for (i, m) in state.monitors.iter().enumerate() {
+        if let Some(label) = state.monitor_labels.get(i) {
+            draw_monitor_label(hdc, m, label);
+        }
+    }
+
+    let color1 = rgb(state.color1[0], state.color1[1], state.color1[2]);
+    let color2 = rgb(state.color2[0], state.color2[1], state.color2[2]);
+    let shift = state.shift;
+    let pen_w = GAP_PEN_W;
+    let g = gap_geometry(state);
+    let (bx, arm, inset) = (g.boundary, g.arm, g.inset);
+    // Along-edge distance travelled by the lines at `arm` and `inset`
+    let along = |d: i32| (d as f64 * g.slope).round() as i32;
+
+    // All lines stay at the configured angle. The shift translates the
+    // far-side lines along the edge: a line crossing G pixels of gap moves
+    // G * slope pixels along it, so the far-side pair shifts by +shift /
+    // -shift respectively and the gap is shift / slope.
+    if state.bind_horizontal {
+        let (near_mid, far_mid) = (g.near_mid, g.far_mid);
+
+        // Left monitor lines are fixed at near_mid (anchored)
+        draw_line(hdc, bx - arm, near_mid - along(arm), bx - inset, near_mid - along(inset), color1, pen_w);
+        draw_line(hdc, bx - arm, near_mid + along(arm), bx - inset, near_mid + along(inset), color2, pen_w);
+
+        // Right monitor lines translated by the shift
+        draw_line(hdc, bx + inset, far_mid + shift + along(inset), bx + arm, far_mid + shift + along(arm), color1, pen_w);
+        draw_line(hdc, bx + inset, far_mid - shift - along(inset), bx + arm, far_mid - shift - along(arm), color2, pen_w);
+    } else {
+        let (near_mid, far_mid, by) = (g.near_mid, g.far_mid, bx);
+
+        draw_line(hdc, near_mid - along(arm), by - arm, near_mid - along(inset), by - inset, color1, pen_w);
+        draw_line(hdc, near_mid + along(arm), by - arm, near_mid + along(inset), by - inset, color2, pen_w);
+
+        draw_line(hdc, far_mid + shift + along(inset), by + inset, far_mid + shift + along(arm), by + arm, color1, pen_w);
+        draw_line(hdc, far_mid - shift - along(inset), by + inset, far_mid - shift - along(arm), by + arm, color2, pen_w);
+    }
+
+    // The lines may pass each other: overlapping projectors or a monitor
+    // partly in front of another have a negative gap
+    let gap = gap_px(state);
+    let reading = if gap < 0.0 {
+        format!("Overlap: {:.2}px", -gap)
+    } else {
+        format!("Gap: {gap:.2}px")
+    };
+    let text = format!(
+        "{reading}  |  Drag or arrow keys to adjust so both diagonal lines are aligned  |  Enter: confirm  |  Esc: cancel"
+    );
+    draw_text_at(hdc, m1.x + 20, m1.y + m1.h - 40, &text);
+    draw_text_at(hdc, m2.x + 20, m2.y + m2.h - 40, &text);
+    draw_caption(state, hdc);
+}
+
+/// The caption and, for a repeated step, "Trial k of n" in the top corner of
+/// both monitors.
+unsafe fn draw_caption(state: &State, hdc: HDC) {
+    let mut parts = Vec::new();
+    if let Some(caption) = &state.caption {
+        parts.push(caption.clone());
+    }
+    if let Some((trial, of)) = state.trial {
+        parts.push(format!("Trial {trial} of {of}  |  The lines start somewhere new each time"));
+    }
+    if parts.is_empty() {
+        return;
+    }
+    let text = parts.join("  |  ");
+    for m in [&state.monitors[state.m1_idx], &state.monitors[state.m2_idx]] {
+        draw_text_at(hdc, m.x + 20, m.y + 20, &text);
+    }
+}
+
+unsafe fn draw_overscan(state: &State, hdc: HDC) {
+    let m = &state.monitors[state.m1_idx];
+
+    fill_background(hdc, state);
+
+    for (i, mon) in state.monitors.iter().enumerate() {
+        if let Some(label) = state.monitor_labels.get(i) {
+            draw_monitor_label(hdc, mon, label);
+        }
+    }
+
+    let color1 = rgb(state.color1[0], state.color1[1], state.color1[2]);
+    let color2 = rgb(state.color2[0], state.color2[1], state.color2[2]);
+
+    // Nested frames from the very edge inwards. Markers the TV crops away are
+    // simply not visible, so the outermost one that can still be seen on all
+    // four edges gives the overscan.
+    for k in 0..OVERSCAN_MARKER_COUNT {
+        let pct = k as f64 * OVERSCAN_MARKER_STEP_PCT / 100.0;
+        let inset_x = (m.w as f64 * pct).round() as i32;
+        let inset_y = (m.h as f64 * pct).round() as i32;
+        let (color, width) = if k == state.overscan_marker {
+            (rgb(255, 255, 255), 4)
+        } else if k % 2 == 0 {
+            (color1, 1)
+        } else {
+            (color2, 1)
+        };
+        draw_frame(
+            hdc,
+            m.x + inset_x,
+            m.y + inset_y,
+            m.w - 2 * inset_x,
+            m.h - 2 * inset_y,
+            color,
+            width,
+        );
+    }
+
+    let text = format!(
+        "Overscan: {:.1}%  |  Select the outermost frame you can see completely on all four edges  |  \
+         Click or arrow keys to select  |  Enter: confirm  |  Esc: cancel",
+        state.overscan_marker as f64 * OVERSCAN_MARKER_STEP_PCT
+    );
+    let inner = (m.h as f64 * (OVERSCAN_MARKER_COUNT as f64 * OVERSCAN_MARKER_STEP_PCT) / 100.0)
+        .round() as i32;
+    draw_text_at(hdc, m.x + inner + 20, m.y + m.h - inner - 40, &text);
+}
+
+/// Largest square that fits on a monitor with a margin.
+fn max_square(m: &MonitorRect) -> i32 {
+    (m.w.min(m.h) - 40).max(MIN_SQUARE_PX)
+}
+
+unsafe fn draw_square(state: &State, hdc: HDC) {
+    let m1 = &state.monitors[state.m1_idx];
+    let m2 = &state.monitors[state.m2_idx];
+
+    fill_background(hdc, state);
+    draw_monitor_frame(hdc, m1);
+    draw_monitor_frame(hdc, m2);
+
+    for (i, m) in state.monitors.iter().enumerate() {
+        if let Some(label) = state.monitor_labels.get(i) {
+            draw_monitor_label(hdc, m, label);
+        }
+    }
+
+    let color1 = rgb(state.color1[0], state.color1[1], state.color1[2]);
+    for (m, side) in [(m1, state.square), (m2, state.fixed_square)] {
+        let (cx, cy) = (m.x + m.w / 2, m.y + m.h / 2);
+        draw_frame(hdc, cx - side / 2, cy - side / 2, side, side, color1, 3);
+    }
+
+    let text = format!(
+        "Square: {}px  |  Resize this square until it is the same physical size as the one on \
+         the other display; a ruler held against both helps  |  Drag a corner or arrow keys: \
+         \u{00B1}1px  |  Enter: confirm  |  Esc: cancel",
+        state.square
+    );
+    draw_text_at(hdc, m1.x + 20, m1.y + m1.h - 40, &text);
+    draw_text_at(
+        hdc,
+        m2.x + 20,
+        m2.y + m2.h - 40,
+        "Reference square  |  Resize the square on the other display to match this one",
+    );
+    draw_caption(state, hdc);
+}
+
+unsafe fn draw_reference(state: &State, object: ReferenceObject, hdc: HDC) {
+    let m = &state.monitors[state.m1_idx];
+
+    fill_background(hdc, state);
+    draw_monitor_frame(hdc, m);
+
+    for (i, mon) in state.monitors.iter().enumerate() {
+        if let Some(label) = state.monitor_labels.get(i) {
+            draw_monitor_label(hdc, mon, label);
+        }
+    }
+
+    let color1 = rgb(state.color1[0], state.color1[1], state.color1[2]);
+    let color2 = rgb(state.color2[0], state.color2[1], state.color2[2]);
+    let [w_mm, h_mm] = object.size_mm();
+    let (cx, cy) = (m.x + m.w / 2, m.y + m.h / 2);
+    let w = state.outline;
+    let left = cx - w / 2;
+    if h_mm > 0.0 {
+        let h = (w as f64 * h_mm / w_mm).round() as i32;
+        draw_frame(hdc, left, cy - h / 2, w, h, color1, 2);
+    } else {
+        // A line with end ticks to line the ruler's marks up against
+        draw_line(hdc, left, cy, left + w, cy, color1, 2);
+        for x in [left, left + w] {
+            draw_line(hdc, x, cy - 30, x, cy + 30, color2, 2);
+        }
+    }
+
+    let text = format!(
+        "{}: {}px  |  Hold it flat against the screen and resize the outline until its edges \
+         match  |  Drag an edge or arrow keys: \u{00B1}1px  |  Enter: confirm  |  Esc: cancel",
+        object.label(),
+        w
+    );
+    draw_text_at(hdc, m.x + 20, m.y + m.h - 40, &text);
+}
+
+const GAP_PEN_W: i32 = 4;
+
+/// Where the gap-step lines go. "Near" is the left (top) monitor, whose lines
+/// are anchored; "far" is the other one, whose lines move with the shift.
+struct GapGeometry {
+    /// Shared edge: x for side-by-side monitors, y for stacked ones
+    boundary: i32,
+    near_mid: i32,
+    far_mid: i32,
+    /// Across-edge extent of each line and its gap from the edge
+    arm: i32,
+    inset: i32,
+    /// Along-edge extent of each line
+    arm_along: i32,
+    /// Along-edge pixels per across-edge pixel, 1/tan(angle)
+    slope: f64,
+}
+
+fn gap_geometry(state: &State) -> GapGeometry {
+    let m1 = &state.monitors[state.m1_idx];
+    let m2 = &state.monitors[state.m2_idx];
+    let slope = 1.0 / state.gap_angle_deg.to_radians().tan();
+    let inset = GAP_PEN_W + 2; // pull lines back from edges to prevent bleed
+
+    let m1_first = if state.bind_horizontal { m1.x < m2.x } else { m1.y < m2.y };
+    let (near_mid, far_mid, near, far) = if m1_first {
+        (state.mid_m1, state.mid_m2, m1, m2)
+    } else {
+        (state.mid_m2, state.mid_m1, m2, m1)
+    };
+
+    let (boundary, depth, room) = if state.bind_horizontal {
+        (
+            near.x + near.w,
+            near.w.min(far.w),
+            edge_room(near.y, near.h, near_mid).min(edge_room(far.y, far.h, far_mid)),
+        )
+    } else {
+        (
+            near.y + near.h,
+            near.h.min(far.h),
+            edge_room(near.x, near.w, near_mid).min(edge_room(far.x, far.w, far_mid)),
+        )
+    };
+    // Steep lines run further along the edge, so they get less room across it
+    let room_across = (room as f64 / slope) as i32;
+    let arm = gap_arm(depth, room_across, inset);
+
+    GapGeometry {
+        boundary,
+        near_mid,
+        far_mid,
+        arm,
+        inset,
+        arm_along: (arm as f64 * slope).round() as i32,
+        slope,
+    }
+}
+
+/// Gap implied by the current shift, in pixels.
+fn gap_px(state: &State) -> f64 {
+    state.shift as f64 * state.gap_angle_deg.to_radians().tan()
+}
+
+/// Distance from a midpoint to the nearer end of a monitor's edge.
+fn edge_room(start: i32, len: i32, mid: i32) -> i32 {
+    (mid - start).min(start + len - mid)
+}
+
+/// Length of the diagonal gap lines. `depth` is the smaller monitor extent
+/// perpendicular to the shared edge and `room` the space along it; a portrait
+/// monitor next to a landscape one would otherwise push the lines off-screen.
+fn gap_arm(depth: i32, room: i32, inset: i32) -> i32 {
+    (depth * 2 / 5).max(150).min(room).max(inset * 2)
+}
+
+unsafe fn fill_background(hdc: HDC, state: &State) {
+    let vw = GetSystemMetrics(SM_CXVIRTUALSCREEN);
+    let vh = GetSystemMetrics(SM_CYVIRTUALSCREEN);
+
+    let bg = CreateSolidBrush(rgb(10, 12, 18));
+    let full = RECT {
+        left: 0,
+        top: 0,
+        right: vw,
+        bottom: vh,
+    };
+    FillRect(hdc, &full, bg);
+    DeleteObject(HGDIOBJ(bg.0));
+
+    // Slightly lighter bg on paired monitors
+    let light = CreateSolidBrush(rgb(22, 25, 32));
+    for &idx in &[state.m1_idx, state.m2_idx] {
+        let m = &state.monitors[idx];
+        let r = RECT {
+            left: m.x,
+            top: m.y,
+            right: m.x + m.w,
+            bottom: m.y + m.h,
+        };
+        FillRect(hdc, &r, light);
+    }
+    DeleteObject(HGDIOBJ(light.0));
+}
+
+unsafe fn draw_monitor_frame(hdc: HDC, m: &MonitorRect) {
+    let pen = CreatePen(PS_SOLID, 1, rgb(60, 65, 80));
+    let old = SelectObject(hdc, HGDIOBJ(pen.0));
+    let null_brush = GetStockObject(NULL_BRUSH);
+    let old_brush = SelectObject(hdc, null_brush);
+
+    Rectangle(hdc, m.x, m.y, m.x + m.w, m.y + m.h);
+
+    SelectObject(hdc, old_brush);
+    SelectObject(hdc, old);
+    DeleteObject(HGDIOBJ(pen.0));
+}
+
+unsafe fn draw_frame(hdc: HDC, x: i32, y: i32, w: i32, h: i32, color: COLORREF, width: i32) {
+    // Inside-frame pen so the outermost marker isn't clipped by the monitor edge
+    let pen = CreatePen(PS_INSIDEFRAME, width, color);
+    let old = SelectObject(hdc, HGDIOBJ(pen.0));
+    let null_brush = GetStockObject(NULL_BRUSH);
+    let old_brush = SelectObject(hdc, null_brush);
+
+    Rectangle(hdc, x, y, x + w, y + h);
+
+    SelectObject(hdc, old_brush);
+    SelectObject(hdc, old);
+    DeleteObject(HGDIOBJ(pen.0));
+}
+
+unsafe fn fill_rect(hdc: HDC, x: i32, y: i32, w: i32, h: i32, color: COLORREF) {
+    let brush = CreateSolidBrush(color);
+    let r = RECT {
+        left: x,
+        top: y,
+        right: x + w,
+        bottom: y + h,
+    };
+    FillRect(hdc, &r, brush);
+    DeleteObject(HGDIOBJ(brush.0));
+}
+
+unsafe fn draw_line(hdc: HDC, x1: i32, y1: i32, x2: i32, y2: i32, color: COLORREF, width: i32) {
+    let pen = CreatePen(PS_SOLID, width, color);
+    let old = SelectObject(hdc, HGDIOBJ(pen.0));
+    MoveToEx(hdc, x1, y1, None);
+    LineTo(hdc, x2, y2);
+    SelectObject(hdc, old);
+    DeleteObject(HGDIOBJ(pen.0));
+}
+
+unsafe fn draw_text_at(hdc: HDC, x: i32, y: i32, text: &str) {
+    SetBkMode(hdc, TRANSPARENT);
+    SetTextColor(hdc, rgb(160, 165, 175));
+    let wide: Vec<u16> = text.encode_utf16().collect();
+    TextOutW(hdc, x, y, &wide);
+}
+
+unsafe fn draw_monitor_label(hdc: HDC, m: &MonitorRect, label: &str) {
+    if label.is_empty() {
+        return;
+    }
+
+    let font_height = (m.h / 35).clamp(20, 70);
+    let face = encode_wide("Segoe UI");
+    let font = CreateFontW(
+        font_height,
+        0,
+        0,
+        0,
+        FW_NORMAL.0 as i32,
+        0,
+        0,
+        0,
+        DEFAULT_CHARSET.0 as u32,
+        OUT_DEFAULT_PRECIS.0 as u32,
+        CLIP_DEFAULT_PRECIS.0 as u32,
+        CLEARTYPE_QUALITY.0 as u32,
+        0,
+        PCWSTR(face.as_ptr()),
+    );
+    let old_font = SelectObject(hdc, HGDIOBJ(font.0));
+    SetBkMode(hdc, TRANSPARENT);
+    SetTextColor(hdc, rgb(80, 85, 105));
+
+    let lines: Vec<&str> = label.lines().collect();
+    let line_spacing = font_height + 6;
+    let total_height = lines.len() as i32 * line_spacing;
+    let start_y = m.y + (m.h - total_height) / 2;
+
+    for (i, line) in lines.iter().enumerate() {
+        let wide: Vec<u16> = line.encode_utf16().collect();
+        let mut size = SIZE::default();
+        GetTextExtentPoint32W(hdc, &wide, &mut size);
+        let tx = m.x + (m.w - size.cx) / 2;
+        let ty = start_y + i as i32 * line_spacing;
+        TextOutW(hdc, tx, ty, &wide);
+    }
+
+    SelectObject(hdc, old_font);
+    DeleteObject(HGDIOBJ(font.0));
+}
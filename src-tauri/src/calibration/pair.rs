@@ -0,0 +1,202 @@
+//! One calibration pair solved as a pure function: the two monitors'
+//! desktop rectangles, where the scale-step lines were left and what the gap
+//! step read go in, the chained `CalibrationResult` comes out. No Win32 and
+//! no overlay, so the math can be reused outside a calibration run and
+//! tested on any platform.
+
+use super::adjacency::{self, Contact, Rect};
+use super::{solve, CalibrationResult, PairMeasurement, PairMethod, DEFAULT_LINE_SIGMA_PX};
+
+/// Raw result of the scale and gap steps for one pair.
+#[derive(Debug, Clone, Copy)]
+pub struct PairInput {
+    pub unbound: Rect,
+    pub bound: Rect,
+    /// Side by side, so the shared edge is vertical; otherwise stacked
+    pub bind_horizontal: bool,
+    /// Scale-step lines as the overlay reports them, in the rectangles'
+    /// coordinates: y (side by side) or x (stacked) of [blue_unbound,
+    /// blue_bound, red_unbound, red_bound]
+    pub segments: [i32; 4],
+    /// Gap-step readings level with the blue and the red line, in pixels of
+    /// the bound monitor
+    pub gaps: [f64; 2],
+}
+
+/// Scale-step lines as offsets from each monitor's top (or left) edge, the
+/// form `PairMeasurement::line_offsets` keeps them in.
+pub fn line_offsets(
+    unbound: Rect,
+    bound: Rect,
+    bind_horizontal: bool,
+    segments: [i32; 4],
+) -> [f64; 4] {
+    let (edge_u, edge_b) = if bind_horizontal {
+        (unbound.y, bound.y)
+    } else {
+        (unbound.x, bound.x)
+    };
+    [
+        (segments[0] - edge_u) as f64,
+        (segments[1] - edge_b) as f64,
+        (segments[2] - edge_u) as f64,
+        (segments[3] - edge_b) as f64,
+    ]
+}
+
+/// Solve a single pair with the bound monitor as the reference. In the
+/// result, monitor 0 is the unbound monitor and monitor 1 the bound one.
+/// `None` only if the measurement is degenerate enough to leave the layout
+/// undetermined.
+pub fn solve_pair(input: &PairInput) -> Option<CalibrationResult> {
+    let rects = [input.unbound, input.bound];
+    let measurement = PairMeasurement {
+        unbound: 0,
+        bound: 1,
+        bind_horizontal: input.bind_horizontal,
+        contact: adjacency::relate(0, input.unbound, 1, input.bound)
+            .map_or(Contact::Apart, |adj| adj.contact),
+        in_tree: true,
+        line_offsets: line_offsets(
+            input.unbound,
+            input.bound,
+            input.bind_horizontal,
+            input.segments,
+        ),
+        gaps: input.gaps,
+        line_sigma_px: None,
+        spread: None,
+        method: PairMethod::Lines,
+        declared_offset: false,
+        declared_gap: false,
+    };
+    let measurements = std::slice::from_ref(&measurement);
+    let solution = solve::solve_layout(&rects, 1, measurements, DEFAULT_LINE_SIGMA_PX)?;
+    Some(solve::tree_result(&rects, &solution, &measurement))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// A pair whose true layout is known exactly: integer line positions on
+    /// both monitors fix the scale as a ratio of whole spans, and the gap is
+    /// read directly in bound-monitor pixels.
+    #[derive(Debug, Clone)]
+    struct GroundTruth {
+        input: PairInput,
+        /// Unbound over bound pixel density
+        scale: f64,
+        /// Unbound monitor's top (side by side) or left (stacked) edge, in
+        /// bound-monitor pixels from the bound monitor's
+        along: f64,
+        gap: f64,
+    }
+
+    fn ground_truth() -> impl Strategy<Value = GroundTruth> {
+        (
+            (800..4000i32, 600..2400i32, 800..4000i32, 600..2400i32),
+            (any::<bool>(), any::<bool>()),
+            (0.05..0.95f64, 0.05..0.95f64, 0.2..0.9f64, 0.5..2.0f64),
+            (-0.9..0.9f64, -40.0..200.0f64),
+        )
+            .prop_map(|(sizes, layout, lines, placement)| {
+                let (wu, hu, wb, hb) = sizes;
+                let (bind_horizontal, unbound_first) = layout;
+                let (blue_b, blue_u, span_frac, scale_guess) = lines;
+                let (shift, gap) = placement;
+
+                // Edge lengths along the shared edge
+                let (edge_u, edge_b) = if bind_horizontal { (hu, hb) } else { (wu, wb) };
+                let b1 = (blue_b * edge_b as f64 * 0.5) as i32;
+                let span_b = ((edge_b - b1) as f64 * span_frac).max(2.0) as i32;
+                let u1 = (blue_u * edge_u as f64 * 0.5) as i32;
+                let span_u = ((span_b as f64 * scale_guess) as i32).clamp(2, edge_u - u1 - 1);
+                let scale = span_u as f64 / span_b as f64;
+
+                // Windows arrangement: adjacent, slid along the shared edge
+                let bound = Rect { x: 0, y: 0, w: wb, h: hb };
+                let slide = (shift * edge_b as f64) as i32;
+                let unbound = match (bind_horizontal, unbound_first) {
+                    (true, true) => Rect { x: -wu, y: slide, w: wu, h: hu },
+                    (true, false) => Rect { x: wb, y: slide, w: wu, h: hu },
+                    (false, true) => Rect { x: slide, y: -hu, w: wu, h: hu },
+                    (false, false) => Rect { x: slide, y: hb, w: wu, h: hu },
+                };
+                let (start_u, start_b) = if bind_horizontal {
+                    (unbound.y, bound.y)
+                } else {
+                    (unbound.x, bound.x)
+                };
+
+                GroundTruth {
+                    input: PairInput {
+                        unbound,
+                        bound,
+                        bind_horizontal,
+                        segments: [
+                            start_u + u1,
+                            start_b + b1,
+                            start_u + u1 + span_u,
+                            start_b + b1 + span_b,
+                        ],
+                        gaps: [gap; 2],
+                    },
+                    scale,
+                    // The blue lines are at the same physical point
+                    along: b1 as f64 - u1 as f64 / scale,
+                    gap,
+                }
+            })
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-7 * (1.0 + a.abs().max(b.abs()))
+    }
+
+    proptest! {
+        #[test]
+        fn recovers_scale_gap_and_offset(truth in ground_truth()) {
+            let r = solve_pair(&truth.input).expect("a measured pair is always solvable");
+            prop_assert!(close(r.scale, truth.scale), "scale {} vs {}", r.scale, truth.scale);
+            prop_assert!(close(r.gap, truth.gap), "gap {} vs {}", r.gap, truth.gap);
+            let along = r.align_offset_bound - r.align_offset_unbound / r.scale;
+            prop_assert!(close(along, truth.along), "offset {} vs {}", along, truth.along);
+            prop_assert_eq!(r.rotation_deg, 0.0);
+            prop_assert!(!r.scale_only);
+            prop_assert_eq!((r.monitor_id, r.bound_to), (0, 1));
+        }
+
+        #[test]
+        fn swapping_roles_inverts_scale(truth in ground_truth()) {
+            let input = truth.input;
+            let [su, sb, ru, rb] = input.segments;
+            let swapped = PairInput {
+                unbound: input.bound,
+                bound: input.unbound,
+                segments: [sb, su, rb, ru],
+                ..input
+            };
+            let r = solve_pair(&swapped).expect("a measured pair is always solvable");
+            prop_assert!(close(r.scale, 1.0 / truth.scale));
+            // The unbound monitor's edge, now in the other monitor's pixels
+            let along = r.align_offset_bound - r.align_offset_unbound / r.scale;
+            prop_assert!(close(along, -truth.along * truth.scale));
+        }
+
+        #[test]
+        fn uneven_gaps_give_tilt_and_mean_gap(
+            truth in ground_truth(),
+            widening in -20.0..20.0f64,
+        ) {
+            let mut input = truth.input;
+            input.gaps = [truth.gap, truth.gap + widening];
+            let r = solve_pair(&input).expect("a measured pair is always solvable");
+            prop_assert!(close(r.gap, truth.gap + widening / 2.0));
+            let run = (input.segments[3] - input.segments[1]) as f64;
+            let tilt = (widening / run).atan().to_degrees();
+            prop_assert!(close(r.rotation_deg.abs(), tilt.abs()));
+        }
+    }
+}
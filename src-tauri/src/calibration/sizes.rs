@@ -0,0 +1,298 @@
+//! Cross-check of monitor sizes against a calibration. The scale step
+//! measures how many pixels each monitor packs into the same physical
+//! length, which fixes the ratio of every pair of pixel densities. Sizes from
+//! EDID or a guess imply ratios of their own; where the two disagree, one of
+//! the sizes is wrong, and the measured scales say what it should have been.
+
+use super::CalibrationSession;
+use crate::monitors::Monitor;
+use serde::Serialize;
+
+/// Disagreement below this is within EDID's whole-centimetre rounding.
+const TOLERANCE: f64 = 0.03;
+
+/// Diagonals monitors, laptops and TVs are commonly sold in, inches. Used to
+/// decide which of two disagreeing monitors is the wrong one.
+const COMMON_DIAGONALS_IN: &[f64] = &[
+    10.1, 11.6, 12.5, 13.3, 13.5, 14.0, 15.6, 16.0, 17.3, 18.5, 19.5, 21.5, 23.8, 24.0, 24.5,
+    27.0, 28.0, 31.5, 32.0, 34.0, 38.0, 42.0, 43.0, 48.0, 49.0, 50.0, 55.0, 65.0, 75.0, 77.0,
+    85.0,
+];
+
+/// A monitor whose size disagrees with the measured scales.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeSuggestion {
+    pub monitor_id: usize,
+    /// Diagonal the monitor has now, inches
+    pub diagonal_in: f64,
+    /// Diagonal that agrees with the measured scales and the other monitors'
+    /// sizes, inches
+    pub suggested_diagonal_in: f64,
+    /// Current over suggested diagonal, minus one (0.1 = 10% too large)
+    pub error: f64,
+    /// Where the current size came from, as `Monitor::size_source`
+    pub size_source: String,
+}
+
+/// A monitor with a measured scale and a known size.
+struct Sized<'a> {
+    monitor: &'a Monitor,
+    scale: f64,
+    /// One-sigma uncertainty of `scale` as a fraction of it, which makes it
+    /// directly comparable to the log ratios the check is done in
+    scale_sigma: f64,
+    /// Effective pixel density of the reference monitor that this monitor's
+    /// size implies; the same for every monitor when all sizes are right
+    reference_ppi: f64,
+}
+
+impl Sized<'_> {
+    /// Diagonal this monitor would need for the reference density to be
+    /// `reference_ppi`.
+    fn diagonal_for(&self, reference_ppi: f64) -> f64 {
+        let m = self.monitor;
+        let ppi = m.panel_ppi(reference_ppi * self.scale);
+        (m.resolution_x as f64).hypot(m.resolution_y as f64) / ppi
+    }
+}
+
+/// How far a size source is trusted, lowest first.
+fn trust(source: &str) -> u8 {
+    match source {
+        "edid" => 1,
+        "manual" => 2,
+        "reference" => 3,
+        _ => 0,
+    }
+}
+
+/// Relative distance from `diagonal_in` to the nearest common size.
+fn off_common(diagonal_in: f64) -> f64 {
+    COMMON_DIAGONALS_IN
+        .iter()
+        .map(|d| (diagonal_in / d).ln().abs())
+        .fold(f64::MAX, f64::min)
+}
+
+/// Monitors whose size disagrees with the calibration, worst first, each with
+/// the diagonal that would fix it. Scales from a tape measure came from the
+/// sizes in the first place and are left out.
+pub fn suggest_diagonals(
+    monitors: &[Monitor],
+    session: &CalibrationSession,
+) -> Vec<SizeSuggestion> {
+    let measured_scale = |id: usize| {
+        if id == session.reference {
+            return Some((1.0, 0.0));
+        }
+        let r = session.results.iter().find(|r| r.monitor_id == id)?;
+        let from_sizes = session
+            .measurements
+            .iter()
+            .any(|m| m.in_tree && m.unbound == id && m.scale_from_sizes());
+        (!from_sizes).then_some((r.scale, r.scale_sigma))
+    };
+    let ids = std::iter::once(session.reference)
+        .chain(session.results.iter().map(|r| r.monitor_id));
+    let sized: Vec<Sized> = ids
+        .filter_map(|id| {
+            let monitor = monitors.get(id)?;
+            let (scale, scale_sigma) = measured_scale(id)?;
+            let ppi = monitor.effective_ppi()?;
+            (scale > 0.0).then_some(Sized {
+                monitor,
+                scale,
+                scale_sigma,
+                reference_ppi: ppi / scale,
+            })
+        })
+        .collect();
+
+    let consensus = match sized.as_slice() {
+        [] | [_] => return Vec::new(),
+        // Either size could be the wrong one: suspect the less trusted
+        // source, then the one whose corrected diagonal is a usual size
+        [a, b] => {
+            let suspicion = |suspect: &Sized, other: &Sized| {
+                (
+                    trust(&suspect.monitor.size_source),
+                    off_common(suspect.diagonal_for(other.reference_ppi)),
+                )
+            };
+            if suspicion(a, b) <= suspicion(b, a) {
+                b.reference_ppi
+            } else {
+                a.reference_ppi
+            }
+        }
+        // Median, so a single wrong size can't drag the consensus along
+        _ => {
+            let mut values: Vec<f64> = sized.iter().map(|s| s.reference_ppi).collect();
+            values.sort_by(f64::total_cmp);
+            let mid = values.len() / 2;
+            if values.len().is_multiple_of(2) {
+                (values[mid - 1] + values[mid]) / 2.0
+            } else {
+                values[mid]
+            }
+        }
+    };
+
+    let mut suggestions: Vec<SizeSuggestion> = sized
+        .iter()
+        .filter(|s| {
+            let tolerance = TOLERANCE.max(3.0 * s.scale_sigma);
+            (s.reference_ppi / consensus).ln().abs() > tolerance
+        })
+        .map(|s| {
+            let m = s.monitor;
+            let diagonal_in = s.diagonal_for(s.reference_ppi);
+            let suggested_diagonal_in = s.diagonal_for(consensus);
+            SizeSuggestion {
+                monitor_id: m.id,
+                diagonal_in,
+                suggested_diagonal_in,
+                error: diagonal_in / suggested_diagonal_in - 1.0,
+                size_source: m.size_source.clone(),
+            }
+        })
+        .collect();
+    suggestions.sort_by(|a, b| b.error.abs().total_cmp(&a.error.abs()));
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calibration::adjacency::Contact;
+    use crate::calibration::CalibrationResult;
+
+    const MM_27: [u32; 2] = [597, 336];
+    const MM_24: [u32; 2] = [527, 296];
+
+    /// Monitor 0 is a 27" 1440p reference, monitor 1 a 23.8" 1080p and monitor
+    /// 2 a 27" 4K, all with correct EDID sizes.
+    fn desk() -> Vec<Monitor> {
+        vec![
+            Monitor::for_test(0, [0, 0], [2560, 1440], Some(MM_27)),
+            Monitor::for_test(1, [-1920, 200], [1920, 1080], Some(MM_24)),
+            Monitor::for_test(2, [2560, -300], [3840, 2160], Some(MM_27)),
+        ]
+    }
+
+    fn result(monitor_id: usize, scale: f64, scale_sigma: f64) -> CalibrationResult {
+        CalibrationResult {
+            monitor_id,
+            scale,
+            relative_x: 0.0,
+            relative_y: 0.0,
+            gap: 0.0,
+            bound_to: 0,
+            bind_horizontal: true,
+            contact: Contact::Edge,
+            align_offset_unbound: 0.0,
+            align_offset_bound: 0.0,
+            scale_sigma,
+            position_sigma: [0.0; 2],
+            rotation_deg: 0.0,
+            spread: None,
+            scale_only: false,
+        }
+    }
+
+    /// A session whose measured scales match `truth`, monitor 0 being the
+    /// reference.
+    fn session(truth: &[Monitor], scale_sigma: f64) -> CalibrationSession {
+        let reference_ppi = truth[0].effective_ppi().unwrap();
+        let results = truth[1..]
+            .iter()
+            .map(|m| result(m.id, m.effective_ppi().unwrap() / reference_ppi, scale_sigma))
+            .collect();
+        CalibrationSession {
+            reference: 0,
+            line_sigma_px: 1.0,
+            repeats: 1,
+            gap_angle_deg: 45.0,
+            results,
+            measurements: Vec::new(),
+            residuals: Vec::new(),
+            constraints: Vec::new(),
+            violations: Vec::new(),
+        }
+    }
+
+    /// Replace a monitor's size, keeping its resolution.
+    fn resize(m: &Monitor, size_mm: [u32; 2], size_source: &str) -> Monitor {
+        let position = [m.position_x, m.position_y];
+        let resolution = [m.resolution_x, m.resolution_y];
+        let mut resized = Monitor::for_test(m.id, position, resolution, Some(size_mm));
+        resized.size_source = size_source.into();
+        resized
+    }
+
+    #[test]
+    fn correct_sizes_give_no_suggestions() {
+        let truth = desk();
+        assert!(suggest_diagonals(&truth, &session(&truth, 0.005)).is_empty());
+    }
+
+    #[test]
+    fn one_wrong_edid_among_three_is_found() {
+        let truth = desk();
+        let mut monitors = truth.clone();
+        // The 4K monitor's EDID claims 32"
+        monitors[2] = resize(&truth[2], [708, 398], "edid");
+
+        let suggestions = suggest_diagonals(&monitors, &session(&truth, 0.005));
+        assert_eq!(suggestions.len(), 1);
+        let s = &suggestions[0];
+        assert_eq!(s.monitor_id, 2);
+        assert!((s.diagonal_in - 32.0).abs() < 0.1, "{}", s.diagonal_in);
+        assert!((s.suggested_diagonal_in - 27.0).abs() < 0.1, "{}", s.suggested_diagonal_in);
+        assert!(s.error > 0.15);
+    }
+
+    #[test]
+    fn pair_suspects_the_size_whose_fix_is_a_common_diagonal() {
+        let truth = desk()[..2].to_vec();
+        let mut monitors = truth.clone();
+        // The 23.8" monitor claims 27.4". Correcting it gives 23.8"; correcting
+        // the 27" one instead would give an unusual 31.0".
+        monitors[1] = resize(&truth[1], [606, 340], "edid");
+
+        let suggestions = suggest_diagonals(&monitors, &session(&truth, 0.005));
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].monitor_id, 1);
+        assert!((suggestions[0].suggested_diagonal_in - 23.8).abs() < 0.1);
+    }
+
+    #[test]
+    fn pair_suspects_the_less_trusted_size_first() {
+        let truth = desk()[..2].to_vec();
+        let mut monitors = truth.clone();
+        // Same disagreement, but the 27.4" was typed in by the user, which
+        // outranks the other monitor's EDID
+        monitors[1] = resize(&truth[1], [606, 340], "manual");
+
+        let suggestions = suggest_diagonals(&monitors, &session(&truth, 0.005));
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].monitor_id, 0);
+        assert!((suggestions[0].suggested_diagonal_in - 31.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn tolerance_does_not_depend_on_the_size_of_the_scale() {
+        // The 4K monitor packs ~1.3x the reference's pixels per inch; a 5%
+        // size error is within three sigma of a 2% scale, and so is not
+        // flagged, however large the scale itself is
+        let truth = desk();
+        let mut monitors = truth.clone();
+        monitors[2] = resize(&truth[2], [627, 353], "edid");
+        assert!(suggest_diagonals(&monitors, &session(&truth, 0.02)).is_empty());
+        // With a 1% scale the same error is out of tolerance
+        let suggestions = suggest_diagonals(&monitors, &session(&truth, 0.01));
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].monitor_id, 2);
+    }
+}
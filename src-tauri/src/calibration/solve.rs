@@ -0,0 +1,555 @@
+//! Global weighted least-squares solve of monitor scales and positions from
+//! pair measurements. With only spanning-tree pairs this reproduces the
+//! chained result exactly; extra pairs spread the error over the whole
+//! layout instead of letting it pile up down the chain.
+//!
+//! Lengths are in reference pixels: the reference monitor has scale 1 and
+//! sits at (0, 0), and a length of `n` pixels on monitor `k` is `n / s_k`.
+//!
+//! Every equation is weighted by the inverse of its variance, derived from
+//! an assumed line placement uncertainty, so the solution's covariance gives
+//! one-sigma error bars on each monitor's scale and position.
+//!
+//! Monitors enter only through their desktop rectangles, indexed by monitor
+//! id, so the solve has no Win32 dependency.
+
+use super::adjacency::Rect;
+use super::{CalibrationResult, EdgeResidual, PairMeasurement};
+
+/// Weight of the "same scale" fallback used when a pair's scale lines were
+/// left on top of each other; small enough that any real measurement wins.
+const FALLBACK_SCALE_WEIGHT: f64 = 1.0;
+
+/// Weight of the "keep the Windows arrangement" fallback for pairs whose
+/// positions weren't measured: a one-sigma of a thousand pixels.
+const FALLBACK_POSITION_WEIGHT: f64 = 1e-6;
+
+/// Solved layout, indexed by monitor id. Monitors not in any measurement
+/// keep scale 1 at the origin.
+#[derive(Debug, Clone)]
+pub struct LayoutSolution {
+    pub scales: Vec<f64>,
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    /// One-sigma uncertainty of each scale, as a fraction of it
+    pub scale_sigma: Vec<f64>,
+    /// One-sigma uncertainty of each position, in reference pixels
+    pub x_sigma: Vec<f64>,
+    pub y_sigma: Vec<f64>,
+}
+
+/// One weighted linear equation: `sum(coeff * x[var]) = rhs`.
+struct Equation {
+    terms: Vec<(usize, f64)>,
+    rhs: f64,
+    weight: f64,
+}
+
+/// Minimise `sum(weight * (terms . x - rhs)^2)` over `n` unknowns through
+/// the normal equations. Returns the solution and the variance of each
+/// unknown (the diagonal of the inverse normal matrix, valid when weights
+/// are inverse variances), or `None` if the system is underdetermined.
+fn weighted_least_squares(n: usize, equations: &[Equation]) -> Option<(Vec<f64>, Vec<f64>)> {
+    // Normal matrix augmented with the right-hand side and the identity,
+    // reduced Gauss-Jordan style to the solution and the inverse.
+    let width = 2 * n + 1;
+    let mut rows = vec![vec![0.0; width]; n];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[n + 1 + i] = 1.0;
+    }
+    for eq in equations {
+        for &(i, ci) in &eq.terms {
+            rows[i][n] += eq.weight * ci * eq.rhs;
+            for &(j, cj) in &eq.terms {
+                rows[i][j] += eq.weight * ci * cj;
+            }
+        }
+    }
+
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|&a, &b| rows[a][col].abs().total_cmp(&rows[b][col].abs()))?;
+        if rows[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        rows.swap(col, pivot);
+        let scale = rows[col][col];
+        for value in rows[col].iter_mut() {
+            *value /= scale;
+        }
+        let pivot_row = rows[col].clone();
+        for (r, row) in rows.iter_mut().enumerate() {
+            let factor = row[col];
+            if r == col || factor == 0.0 {
+                continue;
+            }
+            for (value, p) in row.iter_mut().zip(&pivot_row) {
+                *value -= factor * p;
+            }
+        }
+    }
+
+    let values = rows.iter().map(|row| row[n]).collect();
+    let variances = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| row[n + 1 + i].max(0.0))
+        .collect();
+    Some((values, variances))
+}
+
+/// Right-hand side of a pair's across-edge equation `pos_u - pos_b = rhs`:
+/// the unbound monitor sits a gap away from the bound monitor's near edge.
+fn gap_rhs(rects: &[Rect], m: &PairMeasurement, s_u: f64, s_b: f64) -> f64 {
+    let (size_u, size_b) = sizes_along_gap(rects, m);
+    let gap = m.gap() / s_b;
+    if unbound_first(rects, m) {
+        -(size_u / s_u) - gap
+    } else {
+        size_b / s_b + gap
+    }
+}
+
+/// Right-hand side of a pair's along-edge equation `pos_u - pos_b = rhs`:
+/// both alignment midpoints are at the same physical height (or x).
+fn offset_rhs(m: &PairMeasurement, s_u: f64, s_b: f64) -> f64 {
+    let (a_u, a_b) = m.align_offsets();
+    a_b / s_b - a_u / s_u
+}
+
+/// Solve scales, then positions, for every monitor in the measurements.
+/// `line_sigma_px` is the placement uncertainty of a single line for
+/// measurements that don't carry their own.
+pub fn solve_layout(
+    rects: &[Rect],
+    reference: usize,
+    measurements: &[PairMeasurement],
+    line_sigma_px: f64,
+) -> Option<LayoutSolution> {
+    // Unknowns: every measured monitor except the fixed reference
+    let mut var_of: Vec<Option<usize>> = vec![None; rects.len()];
+    let mut n = 0;
+    for m in measurements {
+        for idx in [m.unbound, m.bound] {
+            if idx != reference && var_of[idx].is_none() {
+                var_of[idx] = Some(n);
+                n += 1;
+            }
+        }
+    }
+    let terms = |u: usize, b: usize| -> Vec<(usize, f64)> {
+        let mut t = Vec::new();
+        if let Some(v) = var_of[u] {
+            t.push((v, 1.0));
+        }
+        if let Some(v) = var_of[b] {
+            t.push((v, -1.0));
+        }
+        t
+    };
+
+    // Log scales: ln s_u - ln s_b = ln(span_u / span_b)
+    let scale_equations: Vec<Equation> = measurements
+        .iter()
+        .map(|m| {
+            let (span_u, span_b) = m.spans();
+            let sigma = m.line_sigma_px.unwrap_or(line_sigma_px);
+            if span_u > 1.0 && span_b > 1.0 {
+                // Each span is the difference of two line positions
+                let variance =
+                    2.0 * sigma.powi(2) * (1.0 / span_u.powi(2) + 1.0 / span_b.powi(2));
+                Equation {
+                    terms: terms(m.unbound, m.bound),
+                    rhs: (span_u / span_b).ln(),
+                    weight: 1.0 / variance,
+                }
+            } else {
+                Equation {
+                    terms: terms(m.unbound, m.bound),
+                    rhs: 0.0,
+                    weight: FALLBACK_SCALE_WEIGHT,
+                }
+            }
+        })
+        .collect();
+    let (log_scales, log_scale_var) = weighted_least_squares(n, &scale_equations)?;
+
+    let mut scales = vec![1.0; rects.len()];
+    let mut scale_var = vec![0.0; rects.len()];
+    for (idx, var) in var_of.iter().enumerate() {
+        if let Some(v) = var {
+            scales[idx] = log_scales[*v].exp();
+            scale_var[idx] = log_scale_var[*v];
+        }
+    }
+
+    // Positions along each axis, with scales now fixed. Scale uncertainty is
+    // folded into each equation's variance to first order (ignoring the
+    // correlation between equations that share a monitor).
+    let mut x_equations = Vec::new();
+    let mut y_equations = Vec::new();
+    for m in measurements {
+        let (u, b) = (m.unbound, m.bound);
+        let s_u = scales[u];
+        let s_b = scales[b];
+        if !m.method.measures_position() {
+            let (mu, mb) = (&rects[u], &rects[b]);
+            for (equations, delta) in [
+                (&mut x_equations, mu.x - mb.x),
+                (&mut y_equations, mu.y - mb.y),
+            ] {
+                equations.push(Equation {
+                    terms: terms(u, b),
+                    rhs: delta as f64 / s_b,
+                    weight: FALLBACK_POSITION_WEIGHT,
+                });
+            }
+            continue;
+        }
+        let sigma = m.line_sigma_px.unwrap_or(line_sigma_px);
+        let (size_u, size_b) = sizes_along_gap(rects, m);
+        let gap_px = m.gap();
+
+        let gap_variance = if unbound_first(rects, m) {
+            sigma.powi(2) / s_b.powi(2)
+                + (size_u / s_u).powi(2) * scale_var[u]
+                + (gap_px / s_b).powi(2) * scale_var[b]
+        } else {
+            sigma.powi(2) / s_b.powi(2) + ((size_b + gap_px) / s_b).powi(2) * scale_var[b]
+        };
+        let (a_u, a_b) = m.align_offsets();
+        // Each midpoint is the mean of two line positions
+        let offset_variance = sigma.powi(2) / 2.0 * (1.0 / s_u.powi(2) + 1.0 / s_b.powi(2))
+            + (a_u / s_u).powi(2) * scale_var[u]
+            + (a_b / s_b).powi(2) * scale_var[b];
+
+        let gap = Equation {
+            terms: terms(u, b),
+            rhs: gap_rhs(rects, m, s_u, s_b),
+            weight: 1.0 / gap_variance,
+        };
+        let offset = Equation {
+            terms: terms(u, b),
+            rhs: offset_rhs(m, s_u, s_b),
+            weight: 1.0 / offset_variance,
+        };
+        if m.bind_horizontal {
+            x_equations.push(gap);
+            y_equations.push(offset);
+        } else {
+            y_equations.push(gap);
+            x_equations.push(offset);
+        }
+    }
+    let (xs, x_var) = weighted_least_squares(n, &x_equations)?;
+    let (ys, y_var) = weighted_least_squares(n, &y_equations)?;
+
+    let mut x = vec![0.0; rects.len()];
+    let mut y = vec![0.0; rects.len()];
+    let mut x_sigma = vec![0.0; rects.len()];
+    let mut y_sigma = vec![0.0; rects.len()];
+    for (idx, var) in var_of.iter().enumerate() {
+        if let Some(v) = var {
+            x[idx] = xs[*v];
+            y[idx] = ys[*v];
+            x_sigma[idx] = x_var[*v].sqrt();
+            y_sigma[idx] = y_var[*v].sqrt();
+        }
+    }
+
+    Some(LayoutSolution {
+        scales,
+        x,
+        y,
+        scale_sigma: scale_var.iter().map(|v| v.sqrt()).collect(),
+        x_sigma,
+        y_sigma,
+    })
+}
+
+/// Whether the unbound monitor is left of (or above) the bound one.
+fn unbound_first(rects: &[Rect], m: &PairMeasurement) -> bool {
+    let mu = &rects[m.unbound];
+    let mb = &rects[m.bound];
+    if m.bind_horizontal {
+        mu.x < mb.x
+    } else {
+        mu.y < mb.y
+    }
+}
+
+/// In-plane rotation of the unbound monitor relative to the bound one,
+/// degrees clockwise as seen from the front (screen y grows downwards).
+///
+/// The unbound monitor's near edge runs along the bound edge at a slope of
+/// `gap_slope` away from it. For a monitor to the right of (or below) the
+/// bound one, a gap that widens downwards (rightwards) means its near edge
+/// leans counter-clockwise (clockwise); the other side mirrors that.
+pub fn rotation_deg(rects: &[Rect], m: &PairMeasurement) -> f64 {
+    let angle = m.gap_slope().atan().to_degrees();
+    if m.bind_horizontal == unbound_first(rects, m) {
+        angle
+    } else {
+        -angle
+    }
+}
+
+/// Pixel sizes of (unbound, bound) across the shared edge.
+fn sizes_along_gap(rects: &[Rect], m: &PairMeasurement) -> (f64, f64) {
+    let mu = &rects[m.unbound];
+    let mb = &rects[m.bound];
+    if m.bind_horizontal {
+        (mu.w as f64, mb.w as f64)
+    } else {
+        (mu.h as f64, mb.h as f64)
+    }
+}
+
+/// Express the solved layout for one tree pair in the chained
+/// `CalibrationResult` form, so placing monitors pair by pair lands each
+/// one at its solved position.
+pub fn tree_result(
+    rects: &[Rect],
+    solution: &LayoutSolution,
+    m: &PairMeasurement,
+) -> CalibrationResult {
+    let (u, b) = (m.unbound, m.bound);
+    let s_u = solution.scales[u];
+    let s_b = solution.scales[b];
+    if !m.method.measures_position() {
+        return CalibrationResult {
+            monitor_id: u,
+            scale: s_u,
+            relative_x: 0.0,
+            relative_y: 0.0,
+            gap: 0.0,
+            bound_to: b,
+            bind_horizontal: m.bind_horizontal,
+            contact: m.contact,
+            align_offset_unbound: 0.0,
+            align_offset_bound: 0.0,
+            scale_sigma: solution.scale_sigma[u],
+            position_sigma: [0.0; 2],
+            rotation_deg: 0.0,
+            spread: m.spread,
+            scale_only: true,
+        };
+    }
+    let (a_u, _) = m.align_offsets();
+
+    // Across-edge and along-edge distances between the solved positions
+    let (across, along) = if m.bind_horizontal {
+        (solution.x[u] - solution.x[b], solution.y[u] - solution.y[b])
+    } else {
+        (solution.y[u] - solution.y[b], solution.x[u] - solution.x[b])
+    };
+    let (size_u, size_b) = sizes_along_gap(rects, m);
+    let unbound_first = unbound_first(rects, m);
+    let gap = if unbound_first {
+        (-across - size_u / s_u) * s_b
+    } else {
+        (across - size_b / s_b) * s_b
+    };
+    let align_offset_bound = (along + a_u / s_u) * s_b;
+
+    let relative_offset = m.line_offsets[1] - m.line_offsets[0] * s_u;
+    let relative_edge = if unbound_first {
+        -(gap * 2.0) - size_u * s_u
+    } else {
+        size_b * s_b + gap * 2.0
+    };
+    let (relative_x, relative_y) = if m.bind_horizontal {
+        (relative_edge, relative_offset)
+    } else {
+        (relative_offset, relative_edge)
+    };
+
+    CalibrationResult {
+        monitor_id: u,
+        scale: s_u,
+        relative_x,
+        relative_y,
+        gap,
+        bound_to: b,
+        bind_horizontal: m.bind_horizontal,
+        contact: m.contact,
+        align_offset_unbound: a_u,
+        align_offset_bound,
+        scale_sigma: solution.scale_sigma[u],
+        position_sigma: [solution.x_sigma[u], solution.y_sigma[u]],
+        rotation_deg: rotation_deg(rects, m),
+        spread: m.spread,
+        scale_only: false,
+    }
+}
+
+/// How far one pair's measurement is from the solved layout.
+pub fn residual(
+    rects: &[Rect],
+    solution: &LayoutSolution,
+    index: usize,
+    m: &PairMeasurement,
+) -> EdgeResidual {
+    let (u, b) = (m.unbound, m.bound);
+    let s_u = solution.scales[u];
+    let s_b = solution.scales[b];
+
+    let (span_u, span_b) = m.spans();
+    let scale_error = if span_u > 1.0 && span_b > 1.0 {
+        (span_u / span_b) / (s_u / s_b) - 1.0
+    } else {
+        0.0
+    };
+
+    let (across, along) = if m.bind_horizontal {
+        (solution.x[u] - solution.x[b], solution.y[u] - solution.y[b])
+    } else {
+        (solution.y[u] - solution.y[b], solution.x[u] - solution.x[b])
+    };
+    if !m.method.measures_position() {
+        return EdgeResidual {
+            measurement: index,
+            unbound: u,
+            bound: b,
+            in_tree: m.in_tree,
+            scale_error,
+            offset_error_px: 0.0,
+            gap_error_px: 0.0,
+        };
+    }
+
+    EdgeResidual {
+        measurement: index,
+        unbound: u,
+        bound: b,
+        in_tree: m.in_tree,
+        scale_error,
+        offset_error_px: (offset_rhs(m, s_u, s_b) - along) * s_b,
+        gap_error_px: (gap_rhs(rects, m, s_u, s_b) - across) * s_b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calibration::adjacency::Contact;
+    use crate::calibration::PairMethod;
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rect {
+        Rect { x, y, w, h }
+    }
+
+    fn pair(
+        unbound: usize,
+        bound: usize,
+        bind_horizontal: bool,
+        line_offsets: [f64; 4],
+        gap: f64,
+    ) -> PairMeasurement {
+        PairMeasurement {
+            unbound,
+            bound,
+            bind_horizontal,
+            contact: Contact::Edge,
+            in_tree: true,
+            line_offsets,
+            gaps: [gap; 2],
+            line_sigma_px: None,
+            spread: None,
+            method: PairMethod::Lines,
+            declared_offset: false,
+            declared_gap: false,
+        }
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn least_squares_averages_and_reports_variance() {
+        // Two readings of x0 and one of x1 - x0, all with weight 4
+        let eq = |terms: Vec<(usize, f64)>, rhs| Equation { terms, rhs, weight: 4.0 };
+        let equations = [
+            eq(vec![(0, 1.0)], 10.0),
+            eq(vec![(0, 1.0)], 12.0),
+            eq(vec![(1, 1.0), (0, -1.0)], 5.0),
+        ];
+        let (x, var) = weighted_least_squares(2, &equations).unwrap();
+        assert!(close(x[0], 11.0) && close(x[1], 16.0));
+        // Mean of two readings of variance 1/4
+        assert!(close(var[0], 1.0 / 8.0));
+        assert!(close(var[1], 1.0 / 8.0 + 1.0 / 4.0));
+    }
+
+    #[test]
+    fn least_squares_rejects_underdetermined_system() {
+        let equations = [Equation {
+            terms: vec![(0, 1.0), (1, -1.0)],
+            rhs: 3.0,
+            weight: 1.0,
+        }];
+        assert!(weighted_least_squares(2, &equations).is_none());
+    }
+
+    #[test]
+    fn tree_pair_is_solved_exactly() {
+        // Monitor 1 has 0.75 of the reference's density; its blue line at
+        // 100 px matches 400 px on the reference, and the gap is 30 px
+        let rects = [rect(0, 0, 2560, 1440), rect(2560, 0, 1920, 1080)];
+        let m = pair(1, 0, true, [100.0, 400.0, 550.0, 1000.0], 30.0);
+        let solution = solve_layout(&rects, 0, &[m], 1.0).unwrap();
+        assert!(close(solution.scales[1], 0.75));
+        assert!(close(solution.x[1], 2590.0));
+        assert!(close(solution.y[1], 400.0 - 100.0 / 0.75));
+        assert!(solution.x_sigma[1] > 0.0 && solution.scale_sigma[1] > 0.0);
+    }
+
+    #[test]
+    fn extra_pair_spreads_disagreement() {
+        // Two monitors side by side with a third below both, all at one
+        // density. Tree pairs put monitor 2 at x = 960; the extra pair
+        // against monitor 1 says 966 px left of monitor 1 instead of 960.
+        let rects = [
+            rect(0, 0, 1920, 1080),
+            rect(1920, 0, 1920, 1080),
+            rect(960, 1080, 1920, 1080),
+        ];
+        let tree = [
+            pair(1, 0, true, [300.0, 300.0, 800.0, 800.0], 0.0),
+            pair(2, 0, false, [240.0, 1200.0, 840.0, 1800.0], 0.0),
+        ];
+        let extra = pair(2, 1, false, [1066.0, 100.0, 1666.0, 700.0], 0.0);
+
+        let chained = solve_layout(&rects, 0, &tree, 1.0).unwrap();
+        assert!(close(chained.x[2], 960.0) && close(chained.y[2], 1080.0));
+
+        let mut all = tree.to_vec();
+        all.push(PairMeasurement { in_tree: false, ..extra });
+        let solution = solve_layout(&rects, 0, &all, 1.0).unwrap();
+        // Neither the tree pair nor the extra pair is met exactly
+        let tree_error = 960.0 - solution.x[2];
+        let extra_error = (solution.x[2] - solution.x[1]) + 966.0;
+        assert!(tree_error > 0.0 && extra_error > 0.0);
+        assert!(tree_error < 6.0 && extra_error < 6.0);
+    }
+
+    #[test]
+    fn matched_square_gives_only_a_scale() {
+        // Monitor 1 is far to the right; its square matched the reference's
+        // 480 px one at 360 px, so it has 0.75 of the reference's density
+        let rects = [rect(0, 0, 2560, 1440), rect(5000, 0, 1920, 1080)];
+        let m = PairMeasurement {
+            method: PairMethod::MatchedSquare,
+            ..pair(1, 0, true, [0.0, 0.0, 360.0, 480.0], 0.0)
+        };
+        let solution = solve_layout(&rects, 0, std::slice::from_ref(&m), 1.0).unwrap();
+        assert!(close(solution.scales[1], 0.75));
+
+        let result = tree_result(&rects, &solution, &m);
+        assert!(result.scale_only);
+        assert!(close(result.scale, 0.75) && result.scale_sigma > 0.0);
+        assert_eq!(result.position_sigma, [0.0; 2]);
+        assert_eq!([result.relative_x, result.relative_y, result.gap], [0.0; 3]);
+    }
+}
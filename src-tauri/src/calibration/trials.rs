@@ -0,0 +1,212 @@
+//! Combining repeated trials of one calibration step: outlier rejection,
+//! averaging and spread.
+
+use serde::{Deserialize, Serialize};
+
+/// Trials further than this many robust standard deviations from the median
+/// are rejected.
+const OUTLIER_SIGMAS: f64 = 3.0;
+
+/// Never reject a trial for being within this many pixels of the median,
+/// however consistent the other trials were.
+const MIN_OUTLIER_PX: f64 = 2.0;
+
+/// Standard deviation of rounding a position to whole pixels; the floor for
+/// any spread estimate.
+const PIXEL_QUANTIZATION_SIGMA: f64 = 0.29;
+
+/// Averaged values of one step's trials.
+#[derive(Debug, Clone)]
+pub struct Combined {
+    /// Mean of each value over the kept trials
+    pub mean: Vec<f64>,
+    /// Pooled standard deviation of a single trial's value, in pixels
+    pub spread_px: f64,
+    pub kept: u32,
+    pub rejected: u32,
+}
+
+/// How consistent the repeated trials of one pair were.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PairSpread {
+    pub trials: u32,
+    /// Trials dropped as outliers across both steps
+    pub rejected: u32,
+    /// Standard deviation of where a single scale-step trial put the unbound
+    /// lines against the bound ones, px
+    pub line_px: f64,
+    /// Standard deviation of a single gap setting, px
+    pub gap_px: f64,
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    let n = sorted.len();
+    if n % 2 == 1 {
+        sorted[n / 2]
+    } else {
+        (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+    }
+}
+
+/// Average trials (each a vector of the same values, e.g. the four line
+/// offsets) after dropping any trial that has an outlying value. With fewer
+/// than three trials nothing can be called an outlier, so all are kept; so
+/// are all of them when each has some outlying value, since there is then no
+/// consistent majority to trust instead.
+pub fn combine(trials: &[Vec<f64>]) -> Combined {
+    let width = trials.first().map_or(0, |t| t.len());
+    let mut keep = vec![true; trials.len()];
+
+    if trials.len() >= 3 {
+        for i in 0..width {
+            let mut column: Vec<f64> = trials.iter().map(|t| t[i]).collect();
+            column.sort_by(f64::total_cmp);
+            let med = median(&column);
+            let mut deviations: Vec<f64> = column.iter().map(|v| (v - med).abs()).collect();
+            deviations.sort_by(f64::total_cmp);
+            // 1.4826 * MAD estimates the standard deviation for normal data
+            let robust_sd = 1.4826 * median(&deviations);
+            let limit = (OUTLIER_SIGMAS * robust_sd).max(MIN_OUTLIER_PX);
+            for (t, k) in trials.iter().zip(keep.iter_mut()) {
+                if (t[i] - med).abs() > limit {
+                    *k = false;
+                }
+            }
+        }
+        if !keep.contains(&true) {
+            keep.fill(true);
+        }
+    }
+
+    let kept: Vec<&Vec<f64>> = trials
+        .iter()
+        .zip(&keep)
+        .filter(|(_, &k)| k)
+        .map(|(t, _)| t)
+        .collect();
+    let n = kept.len() as f64;
+    let mean: Vec<f64> = (0..width)
+        .map(|i| kept.iter().map(|t| t[i]).sum::<f64>() / n)
+        .collect();
+
+    let spread_px = if kept.len() >= 2 {
+        let sum_sq: f64 = kept
+            .iter()
+            .flat_map(|t| t.iter().zip(&mean).map(|(v, m)| (v - m).powi(2)))
+            .sum();
+        (sum_sq / ((kept.len() - 1) * width) as f64).sqrt()
+    } else {
+        0.0
+    };
+
+    Combined {
+        mean,
+        spread_px: spread_px.max(PIXEL_QUANTIZATION_SIGMA),
+        kept: kept.len() as u32,
+        rejected: (trials.len() - kept.len()) as u32,
+    }
+}
+
+/// Average scale-step trials, each the four line offsets [blue_unbound,
+/// blue_bound, red_unbound, red_bound]. The overlay starts the lines of each
+/// trial somewhere else, so the raw offsets differ by that scatter rather
+/// than by how well the lines were matched. Each trial is first reduced to
+/// where its own two line pairs put the same two points of the bound monitor
+/// (the average bound blue and red lines) on the unbound one; outliers, the
+/// spread and the mean come from those. Needs at least one trial.
+pub fn combine_lines(trials: &[[f64; 4]]) -> Combined {
+    let n = trials.len() as f64;
+    let blue = trials.iter().map(|t| t[1]).sum::<f64>() / n;
+    let red = trials.iter().map(|t| t[3]).sum::<f64>() / n;
+    let solved: Vec<Vec<f64>> = trials
+        .iter()
+        .map(|t| {
+            let run = t[3] - t[1];
+            if run.abs() < 1.0 {
+                return vec![t[0], t[2]];
+            }
+            let scale = (t[2] - t[0]) / run;
+            vec![t[0] + scale * (blue - t[1]), t[0] + scale * (red - t[1])]
+        })
+        .collect();
+    let mut combined = combine(&solved);
+    combined.mean = vec![combined.mean[0], blue, combined.mean[1], red];
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::overlay::{self, MonitorRect};
+    use super::*;
+
+    #[test]
+    fn fewer_than_three_trials_are_all_kept() {
+        let c = combine(&[vec![10.0, 20.0], vec![30.0, 20.0]]);
+        assert_eq!((c.kept, c.rejected), (2, 0));
+        assert_eq!(c.mean, vec![20.0, 20.0]);
+    }
+
+    #[test]
+    fn outlying_trial_is_dropped() {
+        let trials = [
+            vec![100.0, 500.0],
+            vec![101.0, 501.0],
+            vec![100.0, 500.0],
+            vec![120.0, 500.0],
+        ];
+        let c = combine(&trials);
+        assert_eq!((c.kept, c.rejected), (3, 1));
+        assert!((c.mean[0] - 301.0 / 3.0).abs() < 1e-9);
+        assert!(c.spread_px >= PIXEL_QUANTIZATION_SIGMA);
+    }
+
+    #[test]
+    fn small_deviations_are_never_outliers() {
+        // Identical trials give a zero MAD; a pixel off is still kept
+        let c = combine(&[vec![50.0], vec![50.0], vec![50.0], vec![51.0]]);
+        assert_eq!(c.rejected, 0);
+    }
+
+    #[test]
+    fn all_trials_kept_when_each_has_an_outlier() {
+        // Each trial has a different line 3 px away from the other two
+        let trials = [
+            vec![3.0, 0.0, 0.0, 0.0],
+            vec![0.0, 3.0, 0.0, 0.0],
+            vec![0.0, 0.0, 3.0, 0.0],
+        ];
+        let c = combine(&trials);
+        assert_eq!((c.kept, c.rejected), (3, 0));
+        assert_eq!(c.mean, vec![1.0, 1.0, 1.0, 0.0]);
+        assert!(c.spread_px.is_finite() && c.spread_px > PIXEL_QUANTIZATION_SIGMA);
+    }
+
+    #[test]
+    fn scattered_trials_are_combined_by_their_solutions() {
+        // 1080p unbound beside a 1440p bound monitor; the unbound line
+        // matching a bound offset b sits at 12 + 0.75 b
+        let rect_u = MonitorRect { x: 0, y: 0, w: 1920, h: 1080 };
+        let rect_b = MonitorRect { x: 1920, y: -100, w: 2560, h: 1440 };
+        let matched = |b: f64| 12.0 + 0.75 * b;
+        let mut trials = Vec::new();
+        for t in 1..=5u32 {
+            let mut seed = overlay::trial_seed(0x5eed_1234, Some((t, 5)));
+            let lines = overlay::start_lines(&rect_u, &rect_b, true, &mut seed);
+            let [blue, red] = [lines[1] - rect_b.y, lines[3] - rect_b.y].map(f64::from);
+            // Within half a pixel either way, except the last trial's red line
+            let error = if t % 2 == 0 { 0.5 } else { -0.5 };
+            let red_error = if t == 5 { 15.0 } else { -error };
+            trials.push([matched(blue) + error, blue, matched(red) + red_error, red]);
+        }
+        let starts: Vec<f64> = trials.iter().map(|t| t[1]).collect();
+        let scatter = starts.iter().fold(0.0f64, |m, b| m.max((b - starts[0]).abs()));
+        assert!(scatter > 20.0, "start lines barely moved: {starts:?}");
+
+        let c = combine_lines(&trials);
+        assert_eq!((c.kept, c.rejected), (4, 1));
+        assert!(c.spread_px < 1.0, "spread {}", c.spread_px);
+        assert!((c.mean[0] - matched(c.mean[1])).abs() < 0.5);
+        assert!((c.mean[2] - matched(c.mean[3])).abs() < 0.5);
+    }
+}
@@ -0,0 +1,125 @@
+//! Curved panels laid out on a flat plan. Pixels are spread evenly along the
+//! arc, so a curved monitor takes up its chord in the layout, bulges towards
+//! the viewer by the arc's depth, and positions along the curve have to be
+//! projected onto the chord before they line up with a neighbour.
+
+use crate::monitors::Monitor;
+
+/// The curve of one panel, in inches.
+#[derive(Debug, Clone, Copy)]
+pub struct Curve {
+    pub radius_in: f64,
+    /// Length of the panel along the curve
+    pub arc_in: f64,
+    /// Straight-line distance between the curved edges
+    pub chord_in: f64,
+    /// How far the middle of the panel sits behind its edges
+    pub depth_in: f64,
+    /// True when the curve runs down the desktop (a portrait-mounted panel)
+    pub vertical: bool,
+}
+
+impl Curve {
+    fn new(radius_in: f64, arc_in: f64, vertical: bool) -> Self {
+        let half_angle = arc_in / (2.0 * radius_in);
+        Curve {
+            radius_in,
+            arc_in,
+            chord_in: 2.0 * radius_in * half_angle.sin(),
+            depth_in: radius_in * (1.0 - half_angle.cos()),
+            vertical,
+        }
+    }
+
+    /// Distance along the chord from its start to the point `s` inches along
+    /// the arc from the same end.
+    pub fn chord_position(&self, s: f64) -> f64 {
+        let angle = (s - self.arc_in / 2.0) / self.radius_in;
+        self.chord_in / 2.0 + self.radius_in * angle.sin()
+    }
+}
+
+/// The curve of `m` with `ppi` pixels per inch along the arc, if it is curved.
+pub fn for_monitor(m: &Monitor, ppi: f64) -> Option<Curve> {
+    let radius_in = m.curvature_radius_mm? as f64 / 25.4;
+    let vertical = m.orientation.is_portrait();
+    let px = if vertical { m.resolution_y } else { m.resolution_x };
+    let arc_in = px as f64 / ppi;
+    // A half-angle past 90° would be a panel wrapped more than halfway round
+    if radius_in <= 0.0 || arc_in >= std::f64::consts::PI * radius_in {
+        return None;
+    }
+    Some(Curve::new(radius_in, arc_in, vertical))
+}
+
+/// Flat-layout offset of a point `s` inches from a monitor's top or left
+/// edge, where `vertical` says which. Only the axis the curve runs along is
+/// affected.
+pub fn flatten(curve: Option<&Curve>, vertical: bool, s: f64) -> f64 {
+    match curve {
+        Some(c) if c.vertical == vertical => c.chord_position(s),
+        _ => s,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mm(inches: f64) -> f64 {
+        inches * 25.4
+    }
+
+    fn curve_mm(radius_mm: f64, arc_mm: f64) -> Curve {
+        Curve::new(radius_mm / 25.4, arc_mm / 25.4, false)
+    }
+
+    #[test]
+    fn known_chords() {
+        // 2 R sin(arc / 2R) and R (1 - cos(arc / 2R)), worked by hand
+        let c = curve_mm(1000.0, 800.0);
+        assert!((mm(c.chord_in) - 778.84).abs() < 0.01, "{}", mm(c.chord_in));
+        assert!((mm(c.depth_in) - 78.94).abs() < 0.01, "{}", mm(c.depth_in));
+        let c = curve_mm(1800.0, 600.0);
+        assert!((mm(c.chord_in) - 597.23).abs() < 0.01, "{}", mm(c.chord_in));
+        assert!((mm(c.depth_in) - 24.94).abs() < 0.01, "{}", mm(c.depth_in));
+    }
+
+    #[test]
+    fn chord_is_never_longer_than_the_arc() {
+        for radius_mm in [800.0, 1000.0, 1500.0, 1800.0, 4000.0] {
+            for arc_mm in [300.0, 600.0, 800.0, 1200.0] {
+                let c = curve_mm(radius_mm, arc_mm);
+                assert!(c.chord_in <= c.arc_in, "{c:?}");
+                // Positions along the arc land on the chord in order, ends
+                // on ends and the middle in the middle
+                assert!(c.chord_position(0.0).abs() < 1e-9);
+                assert!((c.chord_position(c.arc_in) - c.chord_in).abs() < 1e-9);
+                assert!((c.chord_position(c.arc_in / 2.0) - c.chord_in / 2.0).abs() < 1e-9);
+                for s in [0.1, 0.25, 0.4].map(|f| f * c.arc_in) {
+                    assert!(c.chord_position(s) <= s);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn flat_panel_is_unchanged() {
+        assert_eq!(flatten(None, false, 7.5), 7.5);
+        assert_eq!(flatten(None, true, 7.5), 7.5);
+        // A curve across the desktop leaves positions down it alone
+        let c = curve_mm(1000.0, 800.0);
+        assert_eq!(flatten(Some(&c), true, 7.5), 7.5);
+        assert!(flatten(Some(&c), false, 7.5) < 7.5);
+
+        let flat = Monitor::for_test(0, [0, 0], [3440, 1440], Some([800, 335]));
+        let ppi = 3440.0 / (800.0 / 25.4);
+        assert!(for_monitor(&flat, ppi).is_none());
+        let curved = Monitor {
+            curvature_radius_mm: Some(1000),
+            ..flat
+        };
+        let c = for_monitor(&curved, ppi).unwrap();
+        assert!((mm(c.chord_in) - 778.84).abs() < 0.01, "{}", mm(c.chord_in));
+    }
+}
@@ -1,6 +1,19 @@
 use crate::calibration::CalibrationResult;
 use crate::monitors::Monitor;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+
+pub mod curvature;
+pub mod perspective;
+
+/// What to include in the export beyond the physical layout.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ExportOptions {
+    /// Add each monitor's Windows pixel position and scaling
+    pub include_virtual_layout: bool,
+    /// Add one-sigma error bars (JSON export only)
+    pub include_uncertainty: bool,
+}
 
 /// Matches Spanright's MonitorPreset
 #[derive(Debug, Clone, Serialize)]
@@ -20,10 +33,108 @@ pub struct SpanrightMonitor {
     pub preset: SpanrightPreset,
     pub physical_x: f64,
     pub physical_y: f64,
+    /// Clockwise rotation in degrees (90, 180 or 270); omitted for landscape
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rotation: Option<u32>,
+    /// Measured in-plane tilt of the panel about its centre, degrees
+    /// clockwise relative to the reference monitor; omitted when level
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tilt_deg: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub display_name: Option<String>,
+    /// Percent of the image cropped at each edge by TV overscan. The preset
+    /// and position above describe the full desktop image, which extends past
+    /// the panel by this much on every side.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overscan: Option<f64>,
+    /// One-sigma error bars on the physical position and size; only
+    /// included when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uncertainty: Option<SpanrightUncertainty>,
+    /// Toed-in position as seen from the viewer; only when a viewing
+    /// distance is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub view_3d: Option<SpanrightView3d>,
+    /// Curved panels only. The preset's diagonal is measured along the arc;
+    /// `physical_x`/`physical_y` place the chord.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub curvature: Option<SpanrightCurvature>,
+    /// Outer frame of the monitor; only when its bezels are known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub housing: Option<SpanrightHousing>,
+    /// Only the monitor's size was calibrated; its position follows the
+    /// Windows arrangement
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub position_unknown: bool,
+}
+
+/// The monitor's housing: the lit area grown by its bezels, in the same
+/// coordinates as `physical_x`/`physical_y`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanrightHousing {
+    pub x_in: f64,
+    pub y_in: f64,
+    pub width_in: f64,
+    pub height_in: f64,
+    /// Top, bottom, left, right
+    pub bezel_mm: [f64; 4],
+    /// Air between this housing and that of the monitor it was calibrated
+    /// against: the measured gap less both facing bezels. Only when both
+    /// monitors' bezels are known; negative when one housing sits in front
+    /// of the other or the bezels entered are wider than the gap allows.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub air_gap_in: Option<f64>,
+}
+
+/// A curved panel's footprint, in inches. `axis` is "horizontal" when the
+/// curve runs across the desktop and "vertical" for portrait mounting.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanrightCurvature {
+    pub radius_mm: u32,
+    pub axis: &'static str,
+    pub arc_in: f64,
+    pub chord_in: f64,
+    /// How far the middle of the panel sits behind its edges
+    pub depth_in: f64,
+}
+
+/// A monitor in the viewer-centred 3D model, in inches. `center_*` is the
+/// panel centre with the eye at the origin looking along +z. `projected_*`
+/// is the flat-equivalent rectangle on the plane of the reference monitor,
+/// in the same coordinates as `physical_x`/`physical_y`, for spanning a
+/// wallpaper so it looks continuous from the eye.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanrightView3d {
+    pub yaw_deg: f64,
+    pub center_x_in: f64,
+    pub center_y_in: f64,
+    pub center_z_in: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projected_x_in: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projected_y_in: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projected_width_in: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub projected_height_in: Option<f64>,
+}
+
+/// One-sigma calibration uncertainty of a monitor's placement, in inches and
+/// millimetres.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanrightUncertainty {
+    pub x_in: f64,
+    pub y_in: f64,
+    pub width_in: f64,
+    pub height_in: f64,
+    pub x_mm: f64,
+    pub y_mm: f64,
+    pub width_mm: f64,
+    pub height_mm: f64,
 }
 
 /// Per-monitor Windows virtual-desktop position (pixel coords).
@@ -32,6 +143,12 @@ pub struct SpanrightMonitor {
 pub struct SpanrightWindowsPosition {
     pub pixel_x: i32,
     pub pixel_y: i32,
+    /// Coordinate space of `pixel_x`/`pixel_y`; always "physical" (unscaled
+    /// device pixels, as seen by a per-monitor DPI aware process)
+    pub pixel_space: &'static str,
+    /// Windows display scaling for this monitor, e.g. 1.5 for 150%
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale_factor: Option<f64>,
 }
 
 /// Matches Spanright's SavedConfig
@@ -44,15 +161,117 @@ pub struct SpanrightSavedConfig {
     pub monitors: Vec<SpanrightMonitor>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub windows_arrangement: Option<Vec<SpanrightWindowsPosition>>,
+    /// Eye distance from the reference monitor used for `view3d`, inches
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub viewing_distance_in: Option<f64>,
+    /// Where lit areas overlap: edge-blended projectors, or a monitor partly
+    /// in front of another. Omitted when nothing overlaps.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub overlaps: Vec<SpanrightOverlap>,
+    /// "referencePixels" when no monitor's size is known: every length
+    /// labelled inches is then in pixels of the reference monitor, and a
+    /// single known size rescales the whole layout. Omitted for inches.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub units: Option<&'static str>,
+}
+
+/// Region covered by two monitors at once, in the same coordinates as
+/// `physical_x`/`physical_y`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpanrightOverlap {
+    /// Indices into `monitors`
+    pub monitors: [usize; 2],
+    pub x_in: f64,
+    pub y_in: f64,
+    pub width_in: f64,
+    pub height_in: f64,
+    /// Direction across the blend zone: "horizontal" for side-by-side
+    /// monitors, "vertical" for stacked ones
+    pub axis: &'static str,
+    /// Width of the blend zone in each monitor's own pixels, in the order of
+    /// `monitors`, for edge-blending software
+    pub blend_px: [f64; 2],
 }
 
+/// Overlaps narrower than this are taken as monitors merely touching.
+const MIN_OVERLAP_IN: f64 = 0.01;
+
 #[derive(Clone)]
-struct PhysicalPlacement {
-    monitor_idx: usize,
-    x: f64, // inches, relative layout coords (before centering)
-    y: f64,
-    w: f64,
-    h: f64,
+pub struct PhysicalPlacement {
+    pub monitor_idx: usize,
+    pub x: f64, // inches, relative layout coords (before centering)
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+    /// One-sigma uncertainty of (x, y, w, h), inches
+    sigma: [f64; 4],
+    /// In-plane tilt about the centre, degrees clockwise
+    pub tilt_deg: f64,
+    /// 3D pose, when placed with a viewer model
+    pose: Option<perspective::Pose>,
+    /// Flat-equivalent rectangle seen from the eye, flat layout coordinates
+    projected: Option<[f64; 4]>,
+    /// Curve of the panel; `w` or `h` is then its chord
+    curve: Option<curvature::Curve>,
+    /// Gap to the bound monitor with both facing bezels taken off, inches
+    air_gap: Option<f64>,
+    /// Pixels per inch along the panel surface
+    ppi: f64,
+    /// False when only the monitor's size was measured
+    pub positioned: bool,
+}
+
+impl PhysicalPlacement {
+    /// Width and height measured along the panel surface.
+    fn surface_size(&self) -> (f64, f64) {
+        match self.curve {
+            Some(c) if c.vertical => (self.w, c.arc_in),
+            Some(c) => (c.arc_in, self.h),
+            None => (self.w, self.h),
+        }
+    }
+}
+
+/// Footprint of `m` at `ppi`: width and height with a curved side replaced
+/// by its chord.
+fn panel_size(m: &Monitor, ppi: f64) -> (f64, f64, Option<curvature::Curve>) {
+    let (w, h) = (m.resolution_x as f64 / ppi, m.resolution_y as f64 / ppi);
+    match curvature::for_monitor(m, ppi) {
+        Some(c) if c.vertical => (w, c.chord_in, Some(c)),
+        Some(c) => (c.chord_in, h, Some(c)),
+        None => (w, h, None),
+    }
+}
+
+// Indices into per-side bezel arrays
+const TOP: usize = 0;
+const BOTTOM: usize = 1;
+const LEFT: usize = 2;
+const RIGHT: usize = 3;
+
+/// Air gap in inches left from `gap_in` once the bezels on the facing sides
+/// are taken off. `side` is the unbound monitor's side facing the bound one.
+fn air_gap(gap_in: f64, unbound: &Monitor, bound: &Monitor, side: usize) -> Option<f64> {
+    let (a, b) = (unbound.bezel_mm?, bound.bezel_mm?);
+    // TOP/BOTTOM and LEFT/RIGHT differ only in the lowest bit
+    Some(gap_in - (a[side] + b[side ^ 1]) / 25.4)
+}
+
+/// Outer frame (x, y, w, h) of a monitor's housing, inches.
+fn housing_rect(p: &PhysicalPlacement, bezels_mm: [f64; 4]) -> [f64; 4] {
+    let [t, b, l, r] = bezels_mm.map(|v| v / 25.4);
+    [p.x - l, p.y - t, p.w + l + r, p.h + t + b]
+}
+
+/// Tilts smaller than this are within measurement noise and reported as
+/// level. The UI hides them at the same threshold (`panelGeometry.ts`).
+const MIN_TILT_DEG: f64 = 0.1;
+
+/// Rotate `v` clockwise by `deg` degrees, with y pointing down.
+fn rotate(v: [f64; 2], deg: f64) -> [f64; 2] {
+    let (sin, cos) = deg.to_radians().sin_cos();
+    [v[0] * cos - v[1] * sin, v[0] * sin + v[1] * cos]
 }
 
 fn gcd(mut a: u32, mut b: u32) -> u32 {
@@ -66,55 +285,77 @@ fn gcd(mut a: u32, mut b: u32) -> u32 {
 
 fn aspect_ratio(rx: u32, ry: u32) -> [u32; 2] {
     let g = gcd(rx, ry);
-    if g == 0 {
-        [16, 9]
-    } else {
-        [rx / g, ry / g]
+    match (rx.checked_div(g), ry.checked_div(g)) {
+        (Some(ax), Some(ay)) => [ax, ay],
+        _ => [16, 9],
     }
 }
 
 pub fn build_spanright_config(
     monitors: &[Monitor],
     results: &[CalibrationResult],
-    include_virtual_layout: bool,
+    options: &ExportOptions,
+    selected: &[usize],
+    viewer: Option<perspective::Viewer>,
 ) -> SpanrightSavedConfig {
-    let placements = compute_physical_placements(monitors, results);
+    let measured = with_measured_clone_members(monitors, results, selected);
+    let Layout {
+        placements,
+        reference_pixels,
+    } = compute_physical_placements(monitors, results, &measured, viewer);
+    let viewer = viewer.filter(|_| !reference_pixels);
 
     // Center the layout on Spanright's canvas (144" × 96")
     const CANVAS_CX: f64 = 72.0;
     const CANVAS_CY: f64 = 48.0;
 
-    let (layout_cx, layout_cy) = if placements.is_empty() {
+    let (offset_x, offset_y) = if placements.is_empty() {
         (0.0, 0.0)
     } else {
         let min_x = placements.iter().map(|p| p.x).fold(f64::MAX, f64::min);
         let max_x = placements.iter().map(|p| p.x + p.w).fold(f64::MIN, f64::max);
         let min_y = placements.iter().map(|p| p.y).fold(f64::MAX, f64::min);
         let max_y = placements.iter().map(|p| p.y + p.h).fold(f64::MIN, f64::max);
-        ((min_x + max_x) / 2.0, (min_y + max_y) / 2.0)
+        if reference_pixels {
+            // Not in inches, so not on the canvas; start at the origin
+            (-min_x, -min_y)
+        } else {
+            (
+                CANVAS_CX - (min_x + max_x) / 2.0,
+                CANVAS_CY - (min_y + max_y) / 2.0,
+            )
+        }
     };
 
-    let offset_x = CANVAS_CX - layout_cx;
-    let offset_y = CANVAS_CY - layout_cy;
-
     let spanright_monitors: Vec<SpanrightMonitor> = placements
         .iter()
         .map(|p| {
             let m = &monitors[p.monitor_idx];
-            let diagonal = m.diagonal_in.unwrap_or_else(|| {
-                let w_in = p.w;
-                let h_in = p.h;
-                (w_in * w_in + h_in * h_in).sqrt()
-            });
-            let display_name = if !m.friendly_name.is_empty() {
-                Some(m.friendly_name.clone())
+            let overscan = m.overscan_pct.filter(|&pct| pct > 0.0);
+            // With overscan the desktop image is larger than the panel, so the
+            // preset must describe the image or wallpaper crops end up off-screen.
+            let diagonal = match (m.diagonal_in, overscan) {
+                (Some(diag), None) => diag,
+                _ => {
+                    let (w, h) = p.surface_size();
+                    (w * w + h * h).sqrt()
+                }
+            };
+            let shown = &monitors[chosen_clone_member(monitors, selected, p.monitor_idx)];
+            let display_name = if !shown.friendly_name.is_empty() {
+                Some(shown.friendly_name.clone())
             } else {
                 None
             };
 
             SpanrightMonitor {
                 preset: SpanrightPreset {
-                    name: format!("{:.0}\" {}", diagonal, format_resolution(m.resolution_x, m.resolution_y)),
+                    name: format!(
+                        "{:.0}{} {}",
+                        diagonal,
+                        if reference_pixels { "px" } else { "\"" },
+                        format_resolution(m.resolution_x, m.resolution_y)
+                    ),
                     diagonal: round2(diagonal),
                     aspect_ratio: aspect_ratio(m.resolution_x, m.resolution_y),
                     resolution_x: m.resolution_x,
@@ -122,13 +363,63 @@ pub fn build_spanright_config(
                 },
                 physical_x: round4(p.x + offset_x),
                 physical_y: round4(p.y + offset_y),
-                rotation: if m.orientation == 1 { Some(90) } else { None },
+                rotation: Some(m.orientation.degrees()).filter(|&deg| deg != 0),
+                tilt_deg: Some(round2(p.tilt_deg)).filter(|t| t.abs() >= MIN_TILT_DEG),
                 display_name,
+                overscan: overscan.map(round2),
+                // Millimetres mean nothing without a size
+                uncertainty: (options.include_uncertainty && !reference_pixels).then(|| {
+                    let [sx, sy, sw, sh] = p.sigma;
+                    SpanrightUncertainty {
+                        x_in: round4(sx),
+                        y_in: round4(sy),
+                        width_in: round4(sw),
+                        height_in: round4(sh),
+                        x_mm: round2(sx * 25.4),
+                        y_mm: round2(sy * 25.4),
+                        width_mm: round2(sw * 25.4),
+                        height_mm: round2(sh * 25.4),
+                    }
+                }),
+                view_3d: p.pose.map(|pose| {
+                    let projected = |i: usize| p.projected.map(|r| round4(r[i]));
+                    SpanrightView3d {
+                        yaw_deg: round2(pose.yaw_deg),
+                        center_x_in: round4(pose.center[0]),
+                        center_y_in: round4(pose.center[1]),
+                        center_z_in: round4(pose.center[2]),
+                        projected_x_in: projected(0).map(|x| round4(x + offset_x)),
+                        projected_y_in: projected(1).map(|y| round4(y + offset_y)),
+                        projected_width_in: projected(2),
+                        projected_height_in: projected(3),
+                    }
+                }),
+                curvature: p.curve.zip(m.curvature_radius_mm).map(|(c, radius_mm)| {
+                    SpanrightCurvature {
+                        radius_mm,
+                        axis: if c.vertical { "vertical" } else { "horizontal" },
+                        arc_in: round4(c.arc_in),
+                        chord_in: round4(c.chord_in),
+                        depth_in: round4(c.depth_in),
+                    }
+                }),
+                housing: m.bezel_mm.filter(|_| !reference_pixels).map(|bezels| {
+                    let [hx, hy, hw, hh] = housing_rect(p, bezels);
+                    SpanrightHousing {
+                        x_in: round4(hx + offset_x),
+                        y_in: round4(hy + offset_y),
+                        width_in: round4(hw),
+                        height_in: round4(hh),
+                        bezel_mm: bezels.map(round2),
+                        air_gap_in: p.air_gap.map(round4),
+                    }
+                }),
+                position_unknown: !p.positioned,
             }
         })
         .collect();
 
-    let windows_arrangement = if include_virtual_layout {
+    let windows_arrangement = if options.include_virtual_layout {
         Some(
             placements
                 .iter()
@@ -137,6 +428,8 @@ pub fn build_spanright_config(
                     SpanrightWindowsPosition {
                         pixel_x: m.position_x,
                         pixel_y: m.position_y,
+                        pixel_space: "physical",
+                        scale_factor: m.scale_factor.map(round2),
                     }
                 })
                 .collect(),
@@ -156,55 +449,198 @@ pub fn build_spanright_config(
         saved_at: now,
         monitors: spanright_monitors,
         windows_arrangement,
+        viewing_distance_in: viewer.map(|v| round2(v.distance_in)),
+        overlaps: find_overlaps(&placements, offset_x, offset_y),
+        units: reference_pixels.then_some("referencePixels"),
     }
 }
 
-fn compute_physical_placements(
+/// Every pair of placed monitors whose lit areas overlap. The blend runs
+/// across the narrower side of the shared region.
+fn find_overlaps(
+    placements: &[PhysicalPlacement],
+    offset_x: f64,
+    offset_y: f64,
+) -> Vec<SpanrightOverlap> {
+    let mut overlaps = Vec::new();
+    for (i, a) in placements.iter().enumerate() {
+        for (j, b) in placements.iter().enumerate().skip(i + 1) {
+            if !a.positioned || !b.positioned {
+                continue;
+            }
+            let x0 = a.x.max(b.x);
+            let y0 = a.y.max(b.y);
+            let w = (a.x + a.w).min(b.x + b.w) - x0;
+            let h = (a.y + a.h).min(b.y + b.h) - y0;
+            if w < MIN_OVERLAP_IN || h < MIN_OVERLAP_IN {
+                continue;
+            }
+            let horizontal = w <= h;
+            let across = if horizontal { w } else { h };
+            overlaps.push(SpanrightOverlap {
+                monitors: [i, j],
+                x_in: round4(x0 + offset_x),
+                y_in: round4(y0 + offset_y),
+                width_in: round4(w),
+                height_in: round4(h),
+                axis: if horizontal { "horizontal" } else { "vertical" },
+                blend_px: [round2(across * a.ppi), round2(across * b.ppi)],
+            });
+        }
+    }
+    overlaps
+}
+
+/// The member of `idx`'s mirror group that is selected now, which names the
+/// group in the export; `idx` itself if none is.
+fn chosen_clone_member(monitors: &[Monitor], selected: &[usize], idx: usize) -> usize {
+    match monitors[idx].clone_group {
+        Some(group) => selected
+            .iter()
+            .copied()
+            .find(|&s| monitors[s].clone_group == Some(group))
+            .unwrap_or(idx),
+        None => idx,
+    }
+}
+
+/// A mirror group is calibrated through whichever member was selected at the
+/// time, and its scale and gaps only hold for that panel. The user may pick a
+/// different member to represent the group afterwards; lay the group out
+/// with the measured member all the same, so its own density applies.
+fn with_measured_clone_members(
     monitors: &[Monitor],
     results: &[CalibrationResult],
-) -> Vec<PhysicalPlacement> {
-    // Derive PPI for all monitors through calibration chain
-    let mut ppi_map: Vec<Option<f64>> = monitors.iter().map(|m| m.ppi).collect();
+    selected: &[usize],
+) -> Vec<usize> {
+    selected
+        .iter()
+        .map(|&s| match monitors[s].clone_group {
+            Some(group) => results
+                .iter()
+                .flat_map(|r| [r.monitor_id, r.bound_to])
+                .find(|&i| monitors[i].clone_group == Some(group))
+                .unwrap_or(s),
+            None => s,
+        })
+        .collect()
+}
+
+/// Fill in the PPI of every monitor reachable through the calibration chain
+/// from one whose PPI is known. Scales are relative to the reference
+/// monitor, so a pair's density ratio is the ratio of both monitors' scales.
+fn propagate_ppi(ppi_map: &mut [Option<f64>], results: &[CalibrationResult]) {
+    let scale_of = |idx: usize| {
+        results
+            .iter()
+            .find(|r| r.monitor_id == idx)
+            .map_or(1.0, |r| r.scale)
+    };
     let mut changed = true;
     while changed {
         changed = false;
         for r in results {
-            if ppi_map[r.monitor_id].is_none() && ppi_map[r.bound_to].is_some() {
-                ppi_map[r.monitor_id] = Some(ppi_map[r.bound_to].unwrap() * r.scale);
-                changed = true;
-            }
-            if ppi_map[r.bound_to].is_none() && ppi_map[r.monitor_id].is_some() {
-                ppi_map[r.bound_to] = Some(ppi_map[r.monitor_id].unwrap() / r.scale);
-                changed = true;
+            let ratio = r.scale / scale_of(r.bound_to);
+            match (ppi_map[r.monitor_id], ppi_map[r.bound_to]) {
+                (None, Some(bound)) => {
+                    ppi_map[r.monitor_id] = Some(bound * ratio);
+                    changed = true;
+                }
+                (Some(unbound), None) => {
+                    ppi_map[r.bound_to] = Some(unbound / ratio);
+                    changed = true;
+                }
+                _ => {}
             }
         }
     }
+}
+
+/// Monitor placements and the unit they are measured in.
+pub struct Layout {
+    pub placements: Vec<PhysicalPlacement>,
+    /// No monitor's size is known, so lengths are in pixels of the reference
+    /// monitor instead of inches. Curvature, bezels and the viewer model are
+    /// given in real units and are left out.
+    reference_pixels: bool,
+}
+
+pub fn compute_physical_placements(
+    monitors: &[Monitor],
+    results: &[CalibrationResult],
+    selected: &[usize],
+    viewer: Option<perspective::Viewer>,
+) -> Layout {
+    if monitors.is_empty() {
+        return Layout { placements: Vec::new(), reference_pixels: false };
+    }
+    // Derive PPI for all monitors through calibration chain. Overscanned
+    // monitors use the PPI of the visible image, not of the panel.
+    let mut ppi_map: Vec<Option<f64>> = monitors.iter().map(|m| m.effective_ppi()).collect();
+    propagate_ppi(&mut ppi_map, results);
 
     let mut placements: Vec<Option<PhysicalPlacement>> = vec![None; monitors.len()];
 
-    // Place reference monitor at (0, 0)
+    // Place reference monitor at (0, 0). Only selected monitors qualify, so a
+    // disabled or left-out display never becomes the anchor.
     let calibrated_ids: std::collections::HashSet<usize> =
         results.iter().map(|r| r.monitor_id).collect();
-    let ref_idx = monitors
+    let ref_idx = selected
         .iter()
-        .position(|m| !calibrated_ids.contains(&m.id) && ppi_map[m.id].is_some())
+        .copied()
+        .find(|&i| !calibrated_ids.contains(&i) && ppi_map[i].is_some())
+        .or_else(|| selected.first().copied())
         .unwrap_or(0);
 
+    // Without any size the calibration still fixes the layout up to scale:
+    // one unit per reference pixel
+    let reference_pixels = ppi_map[ref_idx].is_none();
+    let viewer = viewer.filter(|_| !reference_pixels);
+    let panel_size = |m: &Monitor, ppi: f64| {
+        if reference_pixels {
+            (m.resolution_x as f64 / ppi, m.resolution_y as f64 / ppi, None)
+        } else {
+            panel_size(m, ppi)
+        }
+    };
+    if reference_pixels {
+        ppi_map[ref_idx] = Some(1.0);
+        propagate_ppi(&mut ppi_map, results);
+    }
+
+    // The eye faces the reference monitor's centre; 3D x and y are flat
+    // layout coordinates shifted by `eye`
+    let mut eye = (0.0, 0.0);
     if let Some(ppi) = ppi_map[ref_idx] {
         let m = &monitors[ref_idx];
+        let (w, h, curve) = panel_size(m, ppi);
+        eye = (w / 2.0, h / 2.0);
         placements[ref_idx] = Some(PhysicalPlacement {
             monitor_idx: ref_idx,
             x: 0.0,
             y: 0.0,
-            w: m.resolution_x as f64 / ppi,
-            h: m.resolution_y as f64 / ppi,
+            w,
+            h,
+            sigma: [0.0; 4],
+            tilt_deg: 0.0,
+            pose: viewer.map(|v| perspective::Pose {
+                center: [0.0, 0.0, v.distance_in],
+                yaw_deg: m.yaw_deg.unwrap_or(0.0),
+            }),
+            projected: None,
+            curve,
+            air_gap: None,
+            ppi,
+            positioned: true,
         });
     }
+    // Position uncertainties are in reference-monitor pixels
+    let ppi_ref = ppi_map[ref_idx];
 
     // Place calibrated monitors
     for r in results {
-        let bound_placement = match &placements[r.bound_to] {
-            Some(p) => (p.x, p.y, p.w, p.h),
+        let (bound_placement, bound_tilt, bound_pose, bound_curve) = match &placements[r.bound_to] {
+            Some(p) => ((p.x, p.y, p.w, p.h), p.tilt_deg, p.pose, p.curve),
             None => continue,
         };
 
@@ -219,31 +655,125 @@ fn compute_physical_placements(
 
         let m = &monitors[r.monitor_id];
         let m_bound = &monitors[r.bound_to];
-        let w = m.resolution_x as f64 / ppi_unbound;
-        let h = m.resolution_y as f64 / ppi_unbound;
-
-        let (x, y) = if r.bind_horizontal {
-            let gap_in = (r.gap as f64).abs() / ppi_bound;
-            let offset_in =
-                r.align_offset_bound / ppi_bound - r.align_offset_unbound / ppi_unbound;
+        let (w, h, curve) = panel_size(m, ppi_unbound);
+        // Alignment offsets are measured along each panel's surface
+        let along_unbound = |vertical: bool| {
+            curvature::flatten(curve.as_ref(), vertical, r.align_offset_unbound / ppi_unbound)
+        };
+        let offset_in = |vertical: bool| {
+            curvature::flatten(bound_curve.as_ref(), vertical, r.align_offset_bound / ppi_bound)
+                - along_unbound(vertical)
+        };
+        let yaw = m.yaw_deg.unwrap_or(0.0);
+        let mut pose = None;
+
+        // Besides the placement, the point on the unbound monitor's edge
+        // where the alignment was measured, which the tilt turns about
+        let (x, y, air_gap, pivot) = if r.scale_only {
+            // Only the size is known; keep the Windows arrangement relative
+            // to the bound monitor, at the bound monitor's density
+            let dx = (m.position_x - m_bound.position_x) as f64 / ppi_bound;
+            let dy = (m.position_y - m_bound.position_y) as f64 / ppi_bound;
+            (bound_placement.0 + dx, bound_placement.1 + dy, None, None)
+        } else if r.bind_horizontal {
+            let mut gap_in = r.gap / ppi_bound;
+            let unbound_left = m.position_x < m_bound.position_x;
+            let py = bound_placement.1 + offset_in(true);
+
+            // Poses exist only with a viewer. Hinge the unbound monitor on the
+            // seam: the gap runs halfway between both panels' directions, then
+            // the panel continues at its own yaw.
+            if let Some(bp) = bound_pose {
+                let side = if unbound_left { -1.0 } else { 1.0 };
+                let seam = bp.edge(bound_placement.2, !unbound_left);
+                let dir = perspective::across(yaw);
+                let gap_dir = perspective::bisector(bp.across(), dir);
+                gap_in = perspective::seam_gap(gap_in, seam, bp.across(), gap_dir);
+                let reach = [
+                    gap_in * gap_dir[0] + w / 2.0 * dir[0],
+                    gap_in * gap_dir[1] + w / 2.0 * dir[1],
+                ];
+                let center = [
+                    seam[0] + side * reach[0],
+                    py + h / 2.0 - eye.1,
+                    seam[1] + side * reach[1],
+                ];
+                pose = Some(perspective::Pose { center, yaw_deg: yaw });
+            }
 
-            let px = if m.position_x < m_bound.position_x {
+            let px = if unbound_left {
                 bound_placement.0 - w - gap_in
             } else {
                 bound_placement.0 + bound_placement.2 + gap_in
             };
-            (px, bound_placement.1 + offset_in)
+            let pivot = [if unbound_left { px + w } else { px }, py + along_unbound(true)];
+            let side = if unbound_left { RIGHT } else { LEFT };
+            (px, py, air_gap(gap_in, m, m_bound, side), Some(pivot))
         } else {
-            let gap_in = (r.gap as f64).abs() / ppi_bound;
-            let offset_in =
-                r.align_offset_bound / ppi_bound - r.align_offset_unbound / ppi_unbound;
-
-            let py = if m.position_y < m_bound.position_y {
+            let gap_in = r.gap / ppi_bound;
+            let unbound_above = m.position_y < m_bound.position_y;
+            let py = if unbound_above {
                 bound_placement.1 - h - gap_in
             } else {
                 bound_placement.1 + bound_placement.3 + gap_in
             };
-            (bound_placement.0 + offset_in, py)
+            let px = bound_placement.0 + offset_in(false);
+
+            // Stacked monitors follow the bound panel's direction sideways
+            if let Some(bp) = bound_pose {
+                let shift = (px + w / 2.0) - (bound_placement.0 + bound_placement.2 / 2.0);
+                let [ux, uz] = bp.across();
+                let center = [
+                    bp.center[0] + shift * ux,
+                    py + h / 2.0 - eye.1,
+                    bp.center[2] + shift * uz,
+                ];
+                pose = Some(perspective::Pose { center, yaw_deg: yaw });
+            }
+            let pivot = [px + along_unbound(false), if unbound_above { py + h } else { py }];
+            let side = if unbound_above { BOTTOM } else { TOP };
+            (px, py, air_gap(gap_in, m, m_bound, side), Some(pivot))
+        };
+
+        // Placements carry their tilt as a turn about the centre. Move the
+        // centre so the measured edge point stays on the seam, then carry the
+        // monitor into the bound one's frame, which is itself tilted.
+        let (x, y) = match pivot {
+            Some(pivot) => {
+                let to_center = [x + w / 2.0 - pivot[0], y + h / 2.0 - pivot[1]];
+                let to_center = rotate(to_center, r.rotation_deg);
+                let (bx, by, bw, bh) = bound_placement;
+                let bound_center = [bx + bw / 2.0, by + bh / 2.0];
+                let from_bound = rotate(
+                    [
+                        pivot[0] + to_center[0] - bound_center[0],
+                        pivot[1] + to_center[1] - bound_center[1],
+                    ],
+                    bound_tilt,
+                );
+                (
+                    bound_center[0] + from_bound[0] - w / 2.0,
+                    bound_center[1] + from_bound[1] - h / 2.0,
+                )
+            }
+            None => (x, y),
+        };
+
+        let air_gap = air_gap.filter(|_| !reference_pixels);
+        let projected = match (viewer, &pose) {
+            (Some(viewer), Some(pose)) => perspective::projected_rect(pose, w, h, &viewer)
+                .map(|[px, py, pw, ph]| [px + eye.0, py + eye.1, pw, ph]),
+            _ => None,
+        };
+
+        let sigma = match ppi_ref {
+            Some(ppi_ref) => [
+                r.position_sigma[0] / ppi_ref,
+                r.position_sigma[1] / ppi_ref,
+                w * r.scale_sigma,
+                h * r.scale_sigma,
+            ],
+            None => [0.0; 4],
         };
 
         placements[r.monitor_id] = Some(PhysicalPlacement {
@@ -252,18 +782,31 @@ fn compute_physical_placements(
             y,
             w,
             h,
+            sigma,
+            tilt_deg: bound_tilt + r.rotation_deg,
+            pose,
+            projected,
+            curve,
+            air_gap,
+            ppi: ppi_unbound,
+            positioned: !r.scale_only,
         });
     }
 
-    placements.into_iter().flatten().collect()
+    Layout {
+        placements: placements.into_iter().flatten().collect(),
+        reference_pixels,
+    }
 }
 
 pub fn export_json(
     monitors: &[Monitor],
     results: &[CalibrationResult],
-    include_virtual_layout: bool,
+    options: &ExportOptions,
+    selected: &[usize],
+    viewer: Option<perspective::Viewer>,
 ) -> Result<String, String> {
-    let config = build_spanright_config(monitors, results, include_virtual_layout);
+    let config = build_spanright_config(monitors, results, options, selected, viewer);
     serde_json::to_string_pretty(&[config]).map_err(|e| format!("JSON serialization: {e}"))
 }
 
@@ -304,3 +847,99 @@ fn generate_uuid() -> String {
         (nanos.wrapping_mul(6364136223846793005) & 0xFFFFFFFFFFFF) as u64
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calibration::adjacency::Contact;
+
+    /// Result placing `monitor_id` flush against the right edge of `bound_to`,
+    /// tops level.
+    fn right_of(monitor_id: usize, bound_to: usize, scale: f64) -> CalibrationResult {
+        CalibrationResult {
+            monitor_id,
+            scale,
+            relative_x: 0.0,
+            relative_y: 0.0,
+            gap: 0.0,
+            bound_to,
+            bind_horizontal: true,
+            contact: Contact::Edge,
+            align_offset_unbound: 0.0,
+            align_offset_bound: 0.0,
+            scale_sigma: 0.0,
+            position_sigma: [0.0; 2],
+            rotation_deg: 0.0,
+            spread: None,
+            scale_only: false,
+        }
+    }
+
+    #[test]
+    fn mirror_group_keeps_the_measured_members_size() {
+        // A laptop panel mirrored to a 100" projector, calibrated through
+        // the laptop and exported with the projector selected
+        let mut monitors = vec![
+            Monitor::for_test(0, [0, 0], [2560, 1440], Some([597, 336])),
+            Monitor::for_test(1, [2560, 0], [1920, 1080], Some([344, 194])),
+            Monitor::for_test(2, [2560, 0], [1920, 1080], Some([2214, 1245])),
+        ];
+        monitors[1].friendly_name = "Laptop".into();
+        monitors[2].friendly_name = "Projector".into();
+        monitors[1].clone_group = Some(1);
+        monitors[2].clone_group = Some(1);
+        let ppi = [0, 1].map(|i| monitors[i].effective_ppi().unwrap());
+        let results = [right_of(1, 0, ppi[1] / ppi[0])];
+
+        let config = build_spanright_config(
+            &monitors,
+            &results,
+            &ExportOptions::default(),
+            &[0, 2],
+            None,
+        );
+        assert_eq!(config.monitors.len(), 2);
+        let group = &config.monitors[1];
+        assert_eq!(group.display_name.as_deref(), Some("Projector"));
+        assert!((group.preset.diagonal - 15.6).abs() < 0.1, "{}", group.preset.diagonal);
+    }
+
+    /// A 27" 1440p reference with a 23.8" 1080p monitor `gap_px` reference
+    /// pixels to its right, tops level, as exported.
+    fn pair_with_gap(gap_px: f64) -> (SpanrightSavedConfig, [f64; 2]) {
+        let monitors = [
+            Monitor::for_test(0, [0, 0], [2560, 1440], Some([597, 336])),
+            Monitor::for_test(1, [2560, 0], [1920, 1080], Some([527, 296])),
+        ];
+        let ppi = [0, 1].map(|i| monitors[i].effective_ppi().unwrap());
+        let results = [CalibrationResult {
+            gap: gap_px,
+            ..right_of(1, 0, ppi[1] / ppi[0])
+        }];
+        let options = ExportOptions::default();
+        let config = build_spanright_config(&monitors, &results, &options, &[0, 1], None);
+        (config, ppi)
+    }
+
+    #[test]
+    fn negative_gap_is_an_overlap() {
+        let (config, ppi) = pair_with_gap(-20.0);
+        assert_eq!(config.overlaps.len(), 1);
+        let overlap = &config.overlaps[0];
+        assert_eq!(overlap.monitors, [0, 1]);
+        assert_eq!(overlap.axis, "horizontal");
+        // 20 px of the reference across, in each monitor's own pixels
+        let blend = [20.0, 20.0 / ppi[0] * ppi[1]];
+        for (got, want) in overlap.blend_px.iter().zip(blend) {
+            assert!((got - want).abs() < 0.02, "{:?} vs {blend:?}", overlap.blend_px);
+        }
+    }
+
+    #[test]
+    fn touching_or_apart_monitors_do_not_overlap() {
+        for gap_px in [0.0, 0.5, 30.0] {
+            let (config, _) = pair_with_gap(gap_px);
+            assert!(config.overlaps.is_empty(), "gap {gap_px}");
+        }
+    }
+}
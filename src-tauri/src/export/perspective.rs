@@ -0,0 +1,189 @@
+//! Toed-in monitors seen from the viewer's eye. Each monitor is hinged on the
+//! edge it shares with the monitor it was calibrated against and turned by
+//! its yaw. The gap step measured what the eye sees across the seam, which
+//! for angled monitors is not the physical gap, so it is corrected here.
+//!
+//! 3D coordinates are inches with the eye at the origin looking along +z;
+//! x and y run like the flat layout (right and down). Only horizontal
+//! foreshortening is modelled: monitors turn about the vertical axis and the
+//! eye is assumed level with each seam.
+
+/// Where the viewer sits: centred on the reference monitor, `distance_in`
+/// in front of it.
+#[derive(Debug, Clone, Copy)]
+pub struct Viewer {
+    pub distance_in: f64,
+}
+
+/// Largest yaw the model accepts; beyond it a monitor is nearly edge-on.
+pub const MAX_YAW_DEG: f64 = 60.0;
+
+/// Position and heading of a monitor in 3D.
+#[derive(Debug, Clone, Copy)]
+pub struct Pose {
+    /// Centre of the panel, inches
+    pub center: [f64; 3],
+    /// Rotation about the vertical axis, degrees; positive turns the right
+    /// edge away from the viewer
+    pub yaw_deg: f64,
+}
+
+/// Unit (x, z) direction across a panel turned by `yaw_deg`, from its left
+/// to its right edge.
+pub fn across(yaw_deg: f64) -> [f64; 2] {
+    let yaw = yaw_deg.to_radians();
+    [yaw.cos(), yaw.sin()]
+}
+
+impl Pose {
+    pub fn across(&self) -> [f64; 2] {
+        across(self.yaw_deg)
+    }
+
+    /// (x, z) of the panel's left or right edge.
+    pub fn edge(&self, width: f64, right: bool) -> [f64; 2] {
+        let [ux, uz] = self.across();
+        let half = if right { width / 2.0 } else { -width / 2.0 };
+        [self.center[0] + ux * half, self.center[2] + uz * half]
+    }
+}
+
+/// Apparent length, as seen from the eye, of a unit step along the
+/// horizontal direction `dir` at `point` (both (x, z)).
+fn foreshortening(dir: [f64; 2], point: [f64; 2]) -> f64 {
+    let dist = point[0].hypot(point[1]);
+    if dist < 1e-9 {
+        return 1.0;
+    }
+    ((dir[0] * point[1] - dir[1] * point[0]) / dist).abs()
+}
+
+/// Physical gap across a vertical seam at `seam` from the gap read on
+/// screen. The 45° lines on the bound monitor look steeper by its
+/// foreshortening, and the eye continued them across the gap, which is
+/// foreshortened by the direction it runs in.
+pub fn seam_gap(measured: f64, seam: [f64; 2], bound_dir: [f64; 2], gap_dir: [f64; 2]) -> f64 {
+    let k_bound = foreshortening(bound_dir, seam);
+    let k_gap = foreshortening(gap_dir, seam);
+    if k_gap < 1e-3 {
+        return measured;
+    }
+    measured * k_bound / k_gap
+}
+
+/// Unit (x, z) direction halfway between two directions.
+pub fn bisector(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    let (x, z) = (a[0] + b[0], a[1] + b[1]);
+    let len = x.hypot(z);
+    if len < 1e-9 {
+        a
+    } else {
+        [x / len, z / len]
+    }
+}
+
+/// Bounding box (x, y, w, h) of a `width` × `height` panel at `pose`,
+/// projected through the eye onto the plane `viewer.distance_in` away. This
+/// is the flat-equivalent rectangle a wallpaper has to fill for the image to
+/// look continuous from the eye. `None` if part of the panel is level with
+/// or behind the eye.
+pub fn projected_rect(pose: &Pose, width: f64, height: f64, viewer: &Viewer) -> Option<[f64; 4]> {
+    let mut xs = Vec::new();
+    let mut ys = Vec::new();
+    for right in [false, true] {
+        let [x, z] = pose.edge(width, right);
+        if z < 1e-3 {
+            return None;
+        }
+        let scale = viewer.distance_in / z;
+        xs.push(x * scale);
+        ys.push((pose.center[1] - height / 2.0) * scale);
+        ys.push((pose.center[1] + height / 2.0) * scale);
+    }
+    let min_x = xs.iter().copied().fold(f64::MAX, f64::min);
+    let max_x = xs.iter().copied().fold(f64::MIN, f64::max);
+    let min_y = ys.iter().copied().fold(f64::MAX, f64::min);
+    let max_y = ys.iter().copied().fold(f64::MIN, f64::max);
+    Some([min_x, min_y, max_x - min_x, max_y - min_y])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VIEWER: Viewer = Viewer { distance_in: 30.0 };
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    /// Pose of a 20" wide panel hinged at `seam` (x, z) and turned by
+    /// `yaw_deg`, to the right of the seam or to its left.
+    fn hinged(seam: [f64; 2], yaw_deg: f64, right_of_seam: bool) -> Pose {
+        let [ux, uz] = across(yaw_deg);
+        let half = if right_of_seam { 10.0 } else { -10.0 };
+        Pose {
+            center: [seam[0] + ux * half, 0.0, seam[1] + uz * half],
+            yaw_deg,
+        }
+    }
+
+    #[test]
+    fn flat_monitors_are_left_as_measured() {
+        let flat = across(0.0);
+        assert!(close(seam_gap(12.0, [10.0, 30.0], flat, flat), 12.0));
+        assert!(close(seam_gap(12.0, [-25.0, 30.0], flat, flat), 12.0));
+
+        let pose = Pose {
+            center: [25.0, 2.0, VIEWER.distance_in],
+            yaw_deg: 0.0,
+        };
+        let rect = projected_rect(&pose, 20.0, 12.0, &VIEWER).unwrap();
+        assert!(rect.iter().zip([15.0, -4.0, 20.0, 12.0]).all(|(&a, b)| close(a, b)));
+    }
+
+    #[test]
+    fn symmetric_toe_in_projects_symmetrically() {
+        // A flat 20" centre panel with one 20" panel turned 30° in on
+        // each side
+        let (left_seam, right_seam) = ([-10.0, 30.0], [10.0, 30.0]);
+        let left = hinged(left_seam, 30.0, false);
+        let right = hinged(right_seam, -30.0, true);
+        let [lx, ly, lw, lh] = projected_rect(&left, 20.0, 12.0, &VIEWER).unwrap();
+        let [rx, ry, rw, rh] = projected_rect(&right, 20.0, 12.0, &VIEWER).unwrap();
+        assert!(close(lx, -(rx + rw)) && close(lw, rw));
+        assert!(close(ly, ry) && close(lh, rh));
+        // Turned towards the eye, each looks wider than a flat panel there
+        assert!(rw > 20.0, "{rw}");
+
+        let flat = across(0.0);
+        let left_gap = seam_gap(12.0, left_seam, flat, bisector(flat, across(30.0)));
+        let right_gap = seam_gap(12.0, right_seam, flat, bisector(flat, across(-30.0)));
+        assert!(close(left_gap, right_gap));
+        assert!(!close(right_gap, 12.0));
+    }
+
+    #[test]
+    fn viewer_at_the_seam() {
+        // Straight ahead of the eye, a flat bound panel isn't foreshortened
+        // and a gap running at 15° is, by cos 15°
+        let seam = [0.0, 30.0];
+        let flat = across(0.0);
+        let gap_dir = bisector(flat, across(-30.0));
+        let gap = seam_gap(12.0, seam, flat, gap_dir);
+        assert!(close(gap, 12.0 / 15f64.to_radians().cos()), "{gap}");
+        // A gap running straight at the eye can't be read; it is kept
+        assert_eq!(seam_gap(12.0, seam, flat, [0.0, 1.0]), 12.0);
+    }
+
+    #[test]
+    fn panel_reaching_behind_the_eye_has_no_projection() {
+        let pose = Pose {
+            center: [0.0, 0.0, 5.0],
+            yaw_deg: 0.0,
+        };
+        assert!(projected_rect(&pose, 20.0, 12.0, &VIEWER).is_some());
+        let turned = Pose { yaw_deg: -60.0, ..pose };
+        assert!(projected_rect(&turned, 20.0, 12.0, &VIEWER).is_none());
+    }
+}
@@ -3,21 +3,84 @@
 mod calibration;
 mod export;
 mod monitors;
+// Ground-truth desks run through the whole calibration pipeline, for
+// checking changes to the math
+pub mod simulate;
+
+// Calibration math with no Win32 dependency, usable outside the app
+pub use calibration::{adjacency, pair};
 
 use std::collections::HashMap;
 use std::sync::Mutex;
 
-struct DiagonalOverrides(Mutex<HashMap<usize, f64>>);
+/// A panel size from the user, replacing EDID. The latest one entered wins.
+enum SizeOverride {
+    /// Diagonal in inches, typed in
+    Diagonal(f64),
+    /// Pixels per inch of the visible image, measured against a reference
+    /// object held to the screen
+    MeasuredPpi(f64),
+}
+struct SizeOverrides(Mutex<HashMap<usize, SizeOverride>>);
+struct OverscanOverrides(Mutex<HashMap<usize, f64>>);
+/// How a panel is mounted and shaped, as entered by the user
+#[derive(Default)]
+struct PanelOverride {
+    yaw_deg: Option<f64>,
+    /// `Some(0)` marks a panel as flat, overriding the model database
+    curvature_radius_mm: Option<u32>,
+    /// Top, bottom, left, right in desktop orientation; all zero marks a
+    /// frameless panel
+    bezel_mm: Option<[f64; 4]>,
+}
+struct PanelOverrides(Mutex<HashMap<usize, PanelOverride>>);
+/// Eye distance from the reference monitor in millimetres, if the user
+/// entered one to model toed-in monitors
+struct ViewingDistance(Mutex<Option<f64>>);
 struct OverlayColors(Mutex<[[u8; 3]; 2]>);
 
-fn get_monitors(overrides: &DiagonalOverrides) -> Result<Vec<monitors::Monitor>, String> {
+fn get_monitors(
+    overrides: &SizeOverrides,
+    overscan: &OverscanOverrides,
+    panel: &PanelOverrides,
+) -> Result<Vec<monitors::Monitor>, String> {
     let mut mons = monitors::discover_all()?;
     let map = overrides.0.lock().unwrap();
+    let overscan_map = overscan.0.lock().unwrap();
+    let panel_map = panel.0.lock().unwrap();
     for m in &mut mons {
-        if let Some(&diag) = map.get(&m.id) {
-            monitors::set_physical_from_diagonal(m, diag);
-            m.size_source = "manual".into();
-            m.compute_derived();
+        if let Some(&pct) = overscan_map.get(&m.id) {
+            m.overscan_pct = Some(pct);
+        }
+        match map.get(&m.id) {
+            Some(&SizeOverride::Diagonal(diag)) => {
+                monitors::set_physical_from_diagonal(m, diag);
+                m.size_source = "manual".into();
+                m.compute_derived();
+            }
+            Some(&SizeOverride::MeasuredPpi(ppi)) => {
+                // The measurement saw the overscanned image; the panel itself
+                // is denser by the cropped share
+                let panel_ppi = m.panel_ppi(ppi);
+                let diagonal_px = (m.resolution_x as f64).hypot(m.resolution_y as f64);
+                monitors::set_physical_from_diagonal(m, diagonal_px / panel_ppi);
+                m.size_source = "reference".into();
+                m.compute_derived();
+                // Whole millimetres would round the measurement away
+                m.ppi = Some(panel_ppi);
+            }
+            None => {}
+        }
+        if let Some(p) = panel_map.get(&m.id) {
+            m.yaw_deg = p.yaw_deg;
+            if let Some(radius) = p.curvature_radius_mm {
+                m.curvature_radius_mm = Some(radius).filter(|&r| r > 0);
+                m.curvature_source = "manual".into();
+            }
+            if let Some(bezels) = p.bezel_mm {
+                m.bezel_mm = Some(bezels);
+                m.bezel_source = "manual".into();
+            }
         }
     }
     Ok(mons)
@@ -25,24 +88,160 @@ fn get_monitors(overrides: &DiagonalOverrides) -> Result<Vec<monitors::Monitor>,
 
 #[tauri::command]
 fn discover_monitors(
-    overrides: tauri::State<'_, DiagonalOverrides>,
+    overrides: tauri::State<'_, SizeOverrides>,
+    overscan: tauri::State<'_, OverscanOverrides>,
+    panel: tauri::State<'_, PanelOverrides>,
 ) -> Result<Vec<monitors::Monitor>, String> {
-    get_monitors(&overrides)
+    get_monitors(&overrides, &overscan, &panel)
 }
 
 #[tauri::command]
 fn set_monitor_diagonal(
     id: usize,
     diagonal: f64,
-    overrides: tauri::State<'_, DiagonalOverrides>,
+    overrides: tauri::State<'_, SizeOverrides>,
 ) -> Result<(), String> {
     if diagonal <= 0.0 || diagonal > 200.0 {
         return Err("Diagonal must be between 0 and 200 inches".into());
     }
-    overrides.0.lock().unwrap().insert(id, diagonal);
+    overrides
+        .0
+        .lock()
+        .unwrap()
+        .insert(id, SizeOverride::Diagonal(diagonal));
     Ok(())
 }
 
+#[tauri::command]
+fn set_monitor_overscan(
+    id: usize,
+    percent: f64,
+    overscan: tauri::State<'_, OverscanOverrides>,
+) -> Result<(), String> {
+    if !(0.0..=10.0).contains(&percent) {
+        return Err("Overscan must be between 0% and 10%".into());
+    }
+    overscan.0.lock().unwrap().insert(id, percent);
+    Ok(())
+}
+
+#[tauri::command]
+fn set_monitor_yaw(
+    id: usize,
+    degrees: f64,
+    panel: tauri::State<'_, PanelOverrides>,
+) -> Result<(), String> {
+    let max = export::perspective::MAX_YAW_DEG;
+    if !(-max..=max).contains(&degrees) {
+        return Err(format!("Angle must be between -{max}° and {max}°"));
+    }
+    let mut map = panel.0.lock().unwrap();
+    map.entry(id).or_default().yaw_deg = Some(degrees).filter(|&d| d != 0.0);
+    Ok(())
+}
+
+/// Set a panel's curvature radius in millimetres; 0 marks it flat and `None`
+/// goes back to the model database.
+#[tauri::command]
+fn set_monitor_curvature(
+    id: usize,
+    radius_mm: Option<u32>,
+    panel: tauri::State<'_, PanelOverrides>,
+) -> Result<(), String> {
+    if let Some(r) = radius_mm {
+        if r != 0 && !(300..=10000).contains(&r) {
+            return Err("Curvature radius must be between 300 and 10000 mm".into());
+        }
+    }
+    panel.0.lock().unwrap().entry(id).or_default().curvature_radius_mm = radius_mm;
+    Ok(())
+}
+
+/// Set the housing widths around a panel's lit area in millimetres (top,
+/// bottom, left, right as seen on the desk); `None` goes back to the model
+/// database.
+#[tauri::command]
+fn set_monitor_bezels(
+    id: usize,
+    bezels_mm: Option<[f64; 4]>,
+    panel: tauri::State<'_, PanelOverrides>,
+) -> Result<(), String> {
+    if let Some(b) = bezels_mm {
+        if b.iter().any(|v| !(0.0..=100.0).contains(v)) {
+            return Err("Bezels must be between 0 and 100 mm".into());
+        }
+    }
+    panel.0.lock().unwrap().entry(id).or_default().bezel_mm = bezels_mm;
+    Ok(())
+}
+
+#[tauri::command]
+fn set_viewing_distance(
+    millimetres: Option<f64>,
+    distance: tauri::State<'_, ViewingDistance>,
+) -> Result<(), String> {
+    if let Some(mm) = millimetres {
+        if !(100.0..=5000.0).contains(&mm) {
+            return Err("Viewing distance must be between 100 and 5000 mm".into());
+        }
+    }
+    *distance.0.lock().unwrap() = millimetres;
+    Ok(())
+}
+
+#[tauri::command]
+fn get_viewing_distance(
+    distance: tauri::State<'_, ViewingDistance>,
+) -> Result<Option<f64>, String> {
+    Ok(*distance.0.lock().unwrap())
+}
+
+/// Viewer model for the export, when a viewing distance is set.
+fn viewer(distance: &ViewingDistance) -> Option<export::perspective::Viewer> {
+    distance
+        .0
+        .lock()
+        .unwrap()
+        .map(|mm| export::perspective::Viewer { distance_in: mm / 25.4 })
+}
+
+#[tauri::command]
+fn measure_overscan(
+    id: usize,
+    overrides: tauri::State<'_, SizeOverrides>,
+    overscan: tauri::State<'_, OverscanOverrides>,
+    panel: tauri::State<'_, PanelOverrides>,
+    colors: tauri::State<'_, OverlayColors>,
+) -> Result<f64, String> {
+    let monitors = get_monitors(&overrides, &overscan, &panel)?;
+    let c = *colors.0.lock().unwrap();
+    let percent = calibration::run_overscan_measurement(&monitors, id, c[0], c[1])?;
+    overscan.0.lock().unwrap().insert(id, percent);
+    Ok(percent)
+}
+
+/// Measure a monitor's pixel density against a real object held to the
+/// screen; the result replaces its EDID size.
+#[tauri::command]
+fn measure_reference_ppi(
+    id: usize,
+    object: calibration::ReferenceObject,
+    overrides: tauri::State<'_, SizeOverrides>,
+    overscan: tauri::State<'_, OverscanOverrides>,
+    panel: tauri::State<'_, PanelOverrides>,
+    colors: tauri::State<'_, OverlayColors>,
+) -> Result<f64, String> {
+    let monitors = get_monitors(&overrides, &overscan, &panel)?;
+    let c = *colors.0.lock().unwrap();
+    let ppi = calibration::run_reference_measurement(&monitors, id, object, c[0], c[1])?;
+    overrides
+        .0
+        .lock()
+        .unwrap()
+        .insert(id, SizeOverride::MeasuredPpi(ppi));
+    Ok(ppi)
+}
+
 #[tauri::command]
 fn set_overlay_colors(
     color1: [u8; 3],
@@ -64,32 +263,104 @@ fn get_overlay_colors(
 
 #[tauri::command]
 fn start_calibration(
-    overrides: tauri::State<'_, DiagonalOverrides>,
+    selected: Option<Vec<usize>>,
+    options: Option<calibration::CalibrationOptions>,
+    overrides: tauri::State<'_, SizeOverrides>,
+    overscan: tauri::State<'_, OverscanOverrides>,
+    panel: tauri::State<'_, PanelOverrides>,
+    colors: tauri::State<'_, OverlayColors>,
+) -> Result<calibration::CalibrationSession, String> {
+    let monitors = get_monitors(&overrides, &overscan, &panel)?;
+    let selected = selected.unwrap_or_else(|| calibration::flow::default_selection(&monitors));
+    let c = colors.0.lock().unwrap();
+    let options = options.unwrap_or_default();
+    calibration::run_calibration(&monitors, &selected, &options, c[0], c[1])
+}
+
+/// Pairs a calibration with these options would measure, so each can be
+/// given tape measurements beforehand.
+#[tauri::command]
+fn get_calibration_pairs(
+    selected: Option<Vec<usize>>,
+    options: Option<calibration::CalibrationOptions>,
+    overrides: tauri::State<'_, SizeOverrides>,
+    overscan: tauri::State<'_, OverscanOverrides>,
+    panel: tauri::State<'_, PanelOverrides>,
+) -> Result<Vec<calibration::flow::CalibrationPair>, String> {
+    let monitors = get_monitors(&overrides, &overscan, &panel)?;
+    let selected = selected.unwrap_or_else(|| calibration::flow::default_selection(&monitors));
+    let (_, tree) =
+        calibration::calibration_tree(&monitors, &selected, &options.unwrap_or_default())?;
+    Ok(tree)
+}
+
+/// Monitors whose size disagrees with a calibration's measured scales, with
+/// the diagonal each should have.
+#[tauri::command]
+fn check_monitor_sizes(
+    session: calibration::CalibrationSession,
+    overrides: tauri::State<'_, SizeOverrides>,
+    overscan: tauri::State<'_, OverscanOverrides>,
+    panel: tauri::State<'_, PanelOverrides>,
+) -> Result<Vec<calibration::sizes::SizeSuggestion>, String> {
+    let monitors = get_monitors(&overrides, &overscan, &panel)?;
+    Ok(calibration::sizes::suggest_diagonals(&monitors, &session))
+}
+
+#[tauri::command]
+fn remeasure_pair(
+    session: calibration::CalibrationSession,
+    index: usize,
+    overrides: tauri::State<'_, SizeOverrides>,
+    overscan: tauri::State<'_, OverscanOverrides>,
+    panel: tauri::State<'_, PanelOverrides>,
     colors: tauri::State<'_, OverlayColors>,
-) -> Result<Vec<calibration::CalibrationResult>, String> {
-    let monitors = get_monitors(&overrides)?;
+) -> Result<calibration::CalibrationSession, String> {
+    let monitors = get_monitors(&overrides, &overscan, &panel)?;
     let c = colors.0.lock().unwrap();
-    calibration::run_calibration(&monitors, c[0], c[1])
+    calibration::remeasure_pair(&monitors, &session, index, c[0], c[1])
 }
 
 #[tauri::command]
 fn export_calibration_json(
     results: Vec<calibration::CalibrationResult>,
-    include_virtual_layout: bool,
-    overrides: tauri::State<'_, DiagonalOverrides>,
+    options: Option<export::ExportOptions>,
+    selected: Option<Vec<usize>>,
+    overrides: tauri::State<'_, SizeOverrides>,
+    overscan: tauri::State<'_, OverscanOverrides>,
+    panel: tauri::State<'_, PanelOverrides>,
+    distance: tauri::State<'_, ViewingDistance>,
 ) -> Result<String, String> {
-    let monitors = get_monitors(&overrides)?;
-    export::export_json(&monitors, &results, include_virtual_layout)
+    let monitors = get_monitors(&overrides, &overscan, &panel)?;
+    let selected = selected.unwrap_or_else(|| calibration::flow::default_selection(&monitors));
+    export::export_json(
+        &monitors,
+        &results,
+        &options.unwrap_or_default(),
+        &selected,
+        viewer(&distance),
+    )
 }
 
 #[tauri::command]
 fn save_calibration_file(
     results: Vec<calibration::CalibrationResult>,
-    include_virtual_layout: bool,
-    overrides: tauri::State<'_, DiagonalOverrides>,
+    options: Option<export::ExportOptions>,
+    selected: Option<Vec<usize>>,
+    overrides: tauri::State<'_, SizeOverrides>,
+    overscan: tauri::State<'_, OverscanOverrides>,
+    panel: tauri::State<'_, PanelOverrides>,
+    distance: tauri::State<'_, ViewingDistance>,
 ) -> Result<String, String> {
-    let monitors = get_monitors(&overrides)?;
-    let json = export::export_json(&monitors, &results, include_virtual_layout)?;
+    let monitors = get_monitors(&overrides, &overscan, &panel)?;
+    let selected = selected.unwrap_or_else(|| calibration::flow::default_selection(&monitors));
+    let json = export::export_json(
+        &monitors,
+        &results,
+        &options.unwrap_or_default(),
+        &selected,
+        viewer(&distance),
+    )?;
 
     let file = rfd::FileDialog::new()
         .set_title("Save Spanright Layout")
@@ -106,6 +377,7 @@ fn save_calibration_file(
     }
 }
 
+#[cfg(windows)]
 #[derive(serde::Serialize)]
 #[serde(rename_all = "camelCase")]
 struct MonitorDetailInfo {
@@ -114,6 +386,7 @@ struct MonitorDetailInfo {
     connection_type: Option<String>,
 }
 
+#[cfg(windows)]
 #[tauri::command]
 fn get_monitor_info(id: usize) -> Result<Option<MonitorDetailInfo>, String> {
     let mons = monitors::discover_all()?;
@@ -140,6 +413,13 @@ fn get_monitor_info(id: usize) -> Result<Option<MonitorDetailInfo>, String> {
     }))
 }
 
+/// EDID and connection details are read through Win32.
+#[cfg(not(windows))]
+#[tauri::command]
+fn get_monitor_info(_id: usize) -> Result<(), String> {
+    Err("Monitor details need Windows".to_string())
+}
+
 #[tauri::command]
 fn open_url(url: String) -> Result<(), String> {
     std::process::Command::new("cmd")
@@ -150,16 +430,33 @@ fn open_url(url: String) -> Result<(), String> {
 }
 
 pub fn run() {
+    #[cfg(windows)]
+    monitors::discovery::enable_per_monitor_dpi_awareness();
+
     tauri::Builder::default()
-        .manage(DiagonalOverrides(Mutex::new(HashMap::new())))
+        .manage(SizeOverrides(Mutex::new(HashMap::new())))
+        .manage(OverscanOverrides(Mutex::new(HashMap::new())))
+        .manage(PanelOverrides(Mutex::new(HashMap::new())))
+        .manage(ViewingDistance(Mutex::new(None)))
         .manage(OverlayColors(Mutex::new([[0, 229, 255], [255, 109, 0]])))
         .invoke_handler(tauri::generate_handler![
             discover_monitors,
             set_monitor_diagonal,
+            set_monitor_overscan,
+            set_monitor_yaw,
+            set_monitor_curvature,
+            set_monitor_bezels,
+            set_viewing_distance,
+            get_viewing_distance,
+            measure_overscan,
+            measure_reference_ppi,
             get_monitor_info,
             set_overlay_colors,
             get_overlay_colors,
             start_calibration,
+            get_calibration_pairs,
+            check_monitor_sizes,
+            remeasure_pair,
             export_calibration_json,
             save_calibration_file,
             open_url
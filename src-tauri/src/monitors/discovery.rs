@@ -1,14 +1,65 @@
-use super::models::Monitor;
+use super::models::{DisplayKind, Monitor, MonitorStatus, Orientation};
 use std::mem;
 use windows::core::PCWSTR;
 use windows::Win32::Devices::Display::*;
+use windows::Win32::Foundation::POINT;
 use windows::Win32::Graphics::Gdi::*;
+use windows::Win32::UI::HiDpi::*;
+use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_REMOTESESSION};
+
+// DISPLAYCONFIG_VIDEO_OUTPUT_TECHNOLOGY values used for classification
+const OUTPUT_TECH_MIRACAST: i32 = 15;
+const OUTPUT_TECH_INDIRECT_VIRTUAL: i32 = 17;
+
+/// Lowercase fragments of device IDs, adapter and monitor names belonging to
+/// indirect display drivers that have no panel behind them.
+const VIRTUAL_DISPLAY_HINTS: &[&str] = &[
+    "spacedesk",
+    "parsec",
+    "psccdd",
+    "mttvdd",
+    "iddsampledriver",
+    "usbmmidd",
+    "amyuni",
+    "virtual display",
+    "virtual monitor",
+    "deskreen",
+    "splashtop",
+];
+
+/// Fragments identifying Remote Desktop display adapters and monitors.
+const REMOTE_DISPLAY_HINTS: &[&str] = &["rdpudd", "rdpidd", "remote display adapter"];
 
 fn wchar_to_string(wchars: &[u16]) -> String {
     let len = wchars.iter().position(|&c| c == 0).unwrap_or(wchars.len());
     String::from_utf16_lossy(&wchars[..len])
 }
 
+/// Declare the process per-monitor-v2 DPI aware, so that DEVMODE positions,
+/// virtual-screen metrics and overlay mouse coordinates all come back in
+/// physical pixels. Fails harmlessly if the manifest already set it.
+pub fn enable_per_monitor_dpi_awareness() {
+    unsafe {
+        let _ = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+    }
+}
+
+/// Effective DPI of the monitor containing the given physical point.
+fn query_effective_dpi(x: i32, y: i32) -> Option<u32> {
+    let hmon = unsafe { MonitorFromPoint(POINT { x, y }, MONITOR_DEFAULTTONULL) };
+    if hmon.is_invalid() {
+        return None;
+    }
+    let mut dpi_x = 0u32;
+    let mut dpi_y = 0u32;
+    unsafe { GetDpiForMonitor(hmon, MDT_EFFECTIVE_DPI, &mut dpi_x, &mut dpi_y) }.ok()?;
+    if dpi_x == 0 {
+        None
+    } else {
+        Some(dpi_x)
+    }
+}
+
 pub fn enumerate_monitors() -> Result<Vec<Monitor>, String> {
     let mut monitors = Vec::new();
     let mut id = 0usize;
@@ -27,12 +78,10 @@ pub fn enumerate_monitors() -> Result<Vec<Monitor>, String> {
         }
         adapter_idx += 1;
 
-        if adapter.StateFlags & DISPLAY_DEVICE_ATTACHED_TO_DESKTOP == 0 {
-            continue;
-        }
         if adapter.StateFlags & DISPLAY_DEVICE_MIRRORING_DRIVER != 0 {
             continue;
         }
+        let attached = adapter.StateFlags & DISPLAY_DEVICE_ATTACHED_TO_DESKTOP != 0;
 
         let device_name = wchar_to_string(&adapter.DeviceName);
         let adapter_string = wchar_to_string(&adapter.DeviceString);
@@ -51,26 +100,43 @@ pub fn enumerate_monitors() -> Result<Vec<Monitor>, String> {
             )
         };
 
-        if !settings_ok.as_bool() {
+        // Disabled outputs may have no saved mode; they are listed anyway
+        // with a zero resolution.
+        if attached && !settings_ok.as_bool() {
             continue;
         }
 
-        // Get monitor device info (sub-device of the adapter)
-        let mut monitor_dev = DISPLAY_DEVICEW {
-            cb: mem::size_of::<DISPLAY_DEVICEW>() as u32,
-            ..Default::default()
-        };
-        let has_monitor = unsafe {
-            EnumDisplayDevicesW(PCWSTR(adapter.DeviceName.as_ptr()), 0, &mut monitor_dev, 1)
-        };
+        let children = monitor_children(&adapter.DeviceName);
 
-        let (monitor_name, monitor_device_id) = if has_monitor.as_bool() {
-            (
-                wchar_to_string(&monitor_dev.DeviceString),
-                wchar_to_string(&monitor_dev.DeviceID),
-            )
+        // A detached output with no monitor behind it is just an empty port
+        if !attached && children.is_empty() {
+            continue;
+        }
+
+        // A source cloned to several displays has one active monitor per
+        // target; each is listed separately at the same desktop position.
+        let mut targets: Vec<(String, String)> = if attached {
+            children
+                .iter()
+                .filter(|c| c.active)
+                .map(|c| (c.name.clone(), c.device_id.clone()))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        if targets.is_empty() {
+            targets.push(
+                children
+                    .into_iter()
+                    .next()
+                    .map(|c| (c.name, c.device_id))
+                    .unwrap_or_default(),
+            );
+        }
+        let status = if attached {
+            MonitorStatus::Active
         } else {
-            (String::new(), String::new())
+            MonitorStatus::Inactive
         };
 
         let (pos_x, pos_y) = unsafe {
@@ -78,35 +144,105 @@ pub fn enumerate_monitors() -> Result<Vec<Monitor>, String> {
             (pos.x, pos.y)
         };
 
-        let orientation = unsafe { devmode.Anonymous1.Anonymous2.dmDisplayOrientation.0 };
-
-        monitors.push(Monitor {
-            id,
-            device_name,
-            friendly_name: String::new(),
-            monitor_name,
-            adapter_name: adapter_string,
-            monitor_device_id,
-            is_primary,
-            resolution_x: devmode.dmPelsWidth,
-            resolution_y: devmode.dmPelsHeight,
-            position_x: pos_x,
-            position_y: pos_y,
-            orientation,
-            physical_width_mm: None,
-            physical_height_mm: None,
-            physical_width_in: None,
-            physical_height_in: None,
-            diagonal_in: None,
-            ppi: None,
-            size_source: "none".into(),
+        let orientation = Orientation::from_dmdo(unsafe {
+            devmode.Anonymous1.Anonymous2.dmDisplayOrientation.0
         });
-        id += 1;
+
+        let output_technology = if attached {
+            query_output_technology(&device_name)
+        } else {
+            None
+        };
+
+        let effective_dpi = if attached {
+            query_effective_dpi(
+                pos_x + devmode.dmPelsWidth as i32 / 2,
+                pos_y + devmode.dmPelsHeight as i32 / 2,
+            )
+        } else {
+            None
+        };
+
+        for (monitor_name, monitor_device_id) in targets {
+            let kind = classify_display(
+                output_technology,
+                &monitor_device_id,
+                &adapter_string,
+                &monitor_name,
+            );
+
+            monitors.push(Monitor {
+                id,
+                device_name: device_name.clone(),
+                friendly_name: String::new(),
+                monitor_name,
+                adapter_name: adapter_string.clone(),
+                monitor_device_id,
+                is_primary: is_primary && attached,
+                status,
+                kind,
+                last_seen: None,
+                resolution_x: devmode.dmPelsWidth,
+                resolution_y: devmode.dmPelsHeight,
+                position_x: pos_x,
+                position_y: pos_y,
+                orientation,
+                effective_dpi,
+                scale_factor: effective_dpi.map(|dpi| dpi as f64 / 96.0),
+                physical_width_mm: None,
+                physical_height_mm: None,
+                physical_width_in: None,
+                physical_height_in: None,
+                diagonal_in: None,
+                ppi: None,
+                size_source: "none".into(),
+                overscan_pct: None,
+                yaw_deg: None,
+                curvature_radius_mm: None,
+                curvature_source: "none".into(),
+                bezel_mm: None,
+                bezel_source: "none".into(),
+                clone_group: None,
+            });
+            id += 1;
+        }
     }
 
     Ok(monitors)
 }
 
+struct MonitorChild {
+    name: String,
+    /// Device interface path, e.g. "\\?\DISPLAY#DEL41A3#5&2b7c1a&0&UID4353#{guid}"
+    device_id: String,
+    active: bool,
+}
+
+/// Monitor devices attached to a display adapter output.
+fn monitor_children(adapter_device_name: &[u16]) -> Vec<MonitorChild> {
+    let mut children = Vec::new();
+    let mut idx = 0u32;
+    loop {
+        let mut monitor_dev = DISPLAY_DEVICEW {
+            cb: mem::size_of::<DISPLAY_DEVICEW>() as u32,
+            ..Default::default()
+        };
+        let ok = unsafe {
+            EnumDisplayDevicesW(PCWSTR(adapter_device_name.as_ptr()), idx, &mut monitor_dev, 1)
+        };
+        if !ok.as_bool() {
+            break;
+        }
+        idx += 1;
+        children.push(MonitorChild {
+            name: wchar_to_string(&monitor_dev.DeviceString),
+            device_id: wchar_to_string(&monitor_dev.DeviceID),
+            active: monitor_dev.StateFlags & DISPLAY_DEVICE_ACTIVE != 0,
+        });
+    }
+    children
+}
+
 /// Get refresh rate and connection type for a given GDI device name.
 pub fn get_display_extras(device_name: &str) -> (Option<u32>, Option<String>) {
     let mut refresh = None;
@@ -129,12 +265,65 @@ pub fn get_display_extras(device_name: &str) -> (Option<u32>, Option<String>) {
     }
 
     // Connection type from DisplayConfig
-    let conn = get_connection_type(device_name);
+    let conn =
+        query_output_technology(device_name).map(|tech| connection_type_name(tech).to_string());
 
     (refresh, conn)
 }
 
-fn get_connection_type(device_name: &str) -> Option<String> {
+/// Classify a display from its DisplayConfig output technology (when it has
+/// an active path) and its device ID and names.
+pub fn classify_display(
+    output_technology: Option<i32>,
+    device_id: &str,
+    adapter_name: &str,
+    monitor_name: &str,
+) -> DisplayKind {
+    let haystack = format!("{device_id}|{adapter_name}|{monitor_name}").to_lowercase();
+    let matches_any = |hints: &[&str]| hints.iter().any(|h| haystack.contains(h));
+
+    // Inside an RDP session every active output is a session display
+    let remote_session =
+        output_technology.is_some() && unsafe { GetSystemMetrics(SM_REMOTESESSION) } != 0;
+    if remote_session || matches_any(REMOTE_DISPLAY_HINTS) {
+        return DisplayKind::Remote;
+    }
+    match output_technology {
+        Some(OUTPUT_TECH_MIRACAST) => DisplayKind::Wireless,
+        Some(OUTPUT_TECH_INDIRECT_VIRTUAL) => DisplayKind::Virtual,
+        // Indirect wired covers USB docks (physical) as well as most
+        // virtual display drivers, so only the name tells them apart.
+        _ if matches_any(VIRTUAL_DISPLAY_HINTS) => DisplayKind::Virtual,
+        _ => DisplayKind::Physical,
+    }
+}
+
+fn connection_type_name(tech: i32) -> &'static str {
+    match tech {
+        0 => "VGA",
+        1 => "S-Video",
+        2 => "Composite",
+        3 => "Component",
+        4 => "DVI",
+        5 => "HDMI",
+        6 => "LVDS",
+        8 => "D-JPeg",
+        9 => "SDI",
+        10 => "DisplayPort (External)",
+        11 => "DisplayPort (Embedded)",
+        12 => "UDI (External)",
+        13 => "UDI (Embedded)",
+        14 => "SDTV Dongle",
+        15 => "Miracast",
+        16 => "Indirect Wired",
+        17 => "Indirect Virtual",
+        -2147483648_i32 => "Internal",
+        _ => "Unknown",
+    }
+}
+
+/// Raw `outputTechnology` of the active path driving a GDI device.
+fn query_output_technology(device_name: &str) -> Option<i32> {
     let mut path_count = 0u32;
     let mut mode_count = 0u32;
 
@@ -185,28 +374,7 @@ fn get_connection_type(device_name: &str) -> Option<String> {
             continue;
         }
 
-        let tech = path.targetInfo.outputTechnology;
-        let name = match tech.0 {
-            0 => "VGA",
-            1 => "S-Video",
-            2 => "Composite",
-            3 => "Component",
-            4 => "DVI",
-            5 => "HDMI",
-            6 => "LVDS",
-            8 => "D-JPeg",
-            9 => "SDI",
-            10 => "DisplayPort (External)",
-            11 => "DisplayPort (Embedded)",
-            12 => "UDI (External)",
-            13 => "UDI (Embedded)",
-            14 => "SDTV Dongle",
-            15 => "Miracast",
-            16 => "Indirect Wired",
-            -2147483648_i32 => "Internal",
-            _ => "Unknown",
-        };
-        return Some(name.to_string());
+        return Some(path.targetInfo.outputTechnology.0);
     }
     None
 }
@@ -279,10 +447,18 @@ pub fn populate_friendly_names(monitors: &mut [Monitor]) -> Result<(), String> {
         }
 
         let friendly = wchar_to_string(&target_name.monitorFriendlyDeviceName);
-
-        if let Some(monitor) = monitors.iter_mut().find(|m| m.device_name == gdi_name) {
+        let target_path = wchar_to_string(&target_name.monitorDevicePath);
+
+        // Cloned targets share one source, so match the target path first
+        let monitor = monitors
+            .iter()
+            .position(|m| {
+                m.device_name == gdi_name && m.monitor_device_id.eq_ignore_ascii_case(&target_path)
+            })
+            .or_else(|| monitors.iter().position(|m| m.device_name == gdi_name));
+        if let Some(idx) = monitor {
             if !friendly.is_empty() {
-                monitor.friendly_name = friendly;
+                monitors[idx].friendly_name = friendly;
             }
         }
     }
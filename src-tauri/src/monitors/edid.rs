@@ -1,7 +1,8 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use serde::Serialize;
 use windows::core::PCWSTR;
 use windows::Win32::Devices::DeviceAndDriverInstallation::*;
+use windows::Win32::Devices::Properties::*;
 use windows::Win32::Foundation::*;
 use windows::Win32::System::Registry::*;
 
@@ -127,6 +128,138 @@ pub fn read_all_edid_info() -> Result<HashMap<String, EdidInfo>, String> {
     Ok(result)
 }
 
+/// A monitor Windows has seen before that is not connected right now.
+#[derive(Debug, Clone)]
+pub struct KnownMonitor {
+    /// PnP instance ID, e.g. "DISPLAY\DEL4109\5&2b4f1a&0&UID4353"
+    pub instance_id: String,
+    pub name: Option<String>,
+    pub size: Option<EdidPhysicalSize>,
+    pub native_resolution: Option<[u32; 2]>,
+    /// Last time the monitor was connected or removed (Unix time, ms)
+    pub last_seen: Option<u64>,
+}
+
+/// List monitors that are in the device registry but not present, and were
+/// last connected within `max_age_days`.
+pub fn read_disconnected_monitors(max_age_days: u64) -> Result<Vec<KnownMonitor>, String> {
+    let present = present_monitor_instance_ids()?;
+    let mut result = Vec::new();
+
+    let dev_info = unsafe {
+        SetupDiGetClassDevsW(
+            Some(&GUID_DEVCLASS_MONITOR),
+            PCWSTR::null(),
+            HWND::default(),
+            SETUP_DI_GET_CLASS_DEVS_FLAGS(0),
+        )
+        .map_err(|e| format!("SetupDiGetClassDevsW: {e}"))?
+    };
+
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    let max_age_ms = max_age_days * 24 * 60 * 60 * 1000;
+
+    let mut idx = 0u32;
+    loop {
+        let mut dev_info_data = SP_DEVINFO_DATA {
+            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ..Default::default()
+        };
+        let ok = unsafe { SetupDiEnumDeviceInfo(dev_info, idx, &mut dev_info_data) };
+        if ok.is_err() {
+            break;
+        }
+        idx += 1;
+
+        let instance_id = get_device_instance_id(dev_info, &dev_info_data);
+        if instance_id.is_empty() || present.contains(&instance_id.to_uppercase()) {
+            continue;
+        }
+
+        let last_seen = [DEVPKEY_Device_LastArrivalDate, DEVPKEY_Device_LastRemovalDate]
+            .iter()
+            .filter_map(|key| read_device_filetime_ms(dev_info, &dev_info_data, key))
+            .max();
+        match last_seen {
+            Some(t) if now_ms.saturating_sub(t) <= max_age_ms => {}
+            _ => continue,
+        }
+
+        let edid = read_edid_from_registry(dev_info, &mut dev_info_data);
+        let info = edid.as_deref().and_then(parse_edid_full);
+        result.push(KnownMonitor {
+            instance_id,
+            name: info.as_ref().and_then(|i| i.monitor_name.clone()),
+            size: edid.as_deref().and_then(parse_edid_physical_size),
+            native_resolution: info.and_then(|i| i.native_resolution),
+            last_seen,
+        });
+    }
+
+    unsafe {
+        let _ = SetupDiDestroyDeviceInfoList(dev_info);
+    }
+
+    Ok(result)
+}
+
+fn present_monitor_instance_ids() -> Result<HashSet<String>, String> {
+    let mut result = HashSet::new();
+
+    let dev_info = unsafe {
+        SetupDiGetClassDevsW(
+            Some(&GUID_DEVCLASS_MONITOR),
+            PCWSTR::null(),
+            HWND::default(),
+            DIGCF_PRESENT,
+        )
+        .map_err(|e| format!("SetupDiGetClassDevsW: {e}"))?
+    };
+
+    let mut idx = 0u32;
+    loop {
+        let mut dev_info_data = SP_DEVINFO_DATA {
+            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ..Default::default()
+        };
+        let ok = unsafe { SetupDiEnumDeviceInfo(dev_info, idx, &mut dev_info_data) };
+        if ok.is_err() {
+            break;
+        }
+        idx += 1;
+        result.insert(get_device_instance_id(dev_info, &dev_info_data).to_uppercase());
+    }
+
+    unsafe {
+        let _ = SetupDiDestroyDeviceInfoList(dev_info);
+    }
+
+    Ok(result)
+}
+
+/// Read a FILETIME device property and convert it to Unix time in ms.
+fn read_device_filetime_ms(
+    dev_info: HDEVINFO,
+    data: &SP_DEVINFO_DATA,
+    key: &DEVPROPKEY,
+) -> Option<u64> {
+    let mut prop_type = DEVPROPTYPE::default();
+    let mut buf = [0u8; 8];
+    unsafe {
+        SetupDiGetDevicePropertyW(dev_info, data, key, &mut prop_type, Some(&mut buf), None, 0)
+    }
+    .ok()?;
+    // 100 ns intervals since 1601-01-01
+    const FILETIME_UNIX_EPOCH: u64 = 116_444_736_000_000_000;
+    let ticks = u64::from_le_bytes(buf);
+    ticks
+        .checked_sub(FILETIME_UNIX_EPOCH)
+        .map(|t| t / 10_000)
+}
+
 pub fn apply_edid_to_monitors(
     monitors: &mut [super::models::Monitor],
     edid_map: &HashMap<String, EdidPhysicalSize>,
@@ -134,8 +267,15 @@ pub fn apply_edid_to_monitors(
     for monitor in monitors.iter_mut() {
         for (key, size) in edid_map {
             if monitor.monitor_device_id.contains(key) {
-                monitor.physical_width_mm = Some(size.width_mm);
-                monitor.physical_height_mm = Some(size.height_mm);
+                // EDID describes the unrotated panel; the rotated resolution
+                // needs the dimensions swapped to match.
+                let (w, h) = if monitor.orientation.is_portrait() {
+                    (size.height_mm, size.width_mm)
+                } else {
+                    (size.width_mm, size.height_mm)
+                };
+                monitor.physical_width_mm = Some(w);
+                monitor.physical_height_mm = Some(h);
                 monitor.size_source = "edid".into();
                 break;
             }
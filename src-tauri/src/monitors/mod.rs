@@ -1,16 +1,55 @@
 pub mod models;
+#[cfg(windows)]
 pub mod discovery;
+#[cfg(windows)]
 pub mod edid;
+pub mod model_db;
 
-pub use models::Monitor;
+pub use models::{Monitor, MonitorStatus, Orientation};
+
+/// Disconnected monitors are listed if they were connected within this many days.
+#[cfg(windows)]
+const RECENTLY_SEEN_DAYS: u64 = 30;
 
 pub fn discover_all() -> Result<Vec<Monitor>, String> {
+    let mut monitors = system_monitors()?;
+
+    for m in &mut monitors {
+        // A virtual display has no panel, so any size found in its name is meaningless
+        if m.physical_width_mm.is_none() && m.is_physical() {
+            if let Some(diag) = guess_diagonal_from_names(m) {
+                set_physical_from_diagonal(m, diag);
+                m.size_source = "guessed".into();
+            }
+        }
+        if let Some(known) = model_db::lookup(m) {
+            if let Some(radius) = known.curvature_radius_mm {
+                m.curvature_radius_mm = Some(radius);
+                m.curvature_source = "model".into();
+            }
+            if let Some(bezels) = known.bezel_mm {
+                m.bezel_mm = Some(m.orientation.sides_to_desktop(bezels));
+                m.bezel_source = "model".into();
+            }
+        }
+        m.compute_derived();
+    }
+
+    Ok(monitors)
+}
+
+/// Active monitors from Win32, with friendly names, mirror groups and EDID
+/// sizes, followed by recently disconnected ones.
+#[cfg(windows)]
+fn system_monitors() -> Result<Vec<Monitor>, String> {
     let mut monitors = discovery::enumerate_monitors()?;
 
     if let Err(e) = discovery::populate_friendly_names(&mut monitors) {
         eprintln!("Warning: could not get friendly names: {e}");
     }
 
+    assign_clone_groups(&mut monitors);
+
     match edid::read_all_edid() {
         Ok(edid_map) => {
             edid::apply_edid_to_monitors(&mut monitors, &edid_map);
@@ -20,19 +59,105 @@ pub fn discover_all() -> Result<Vec<Monitor>, String> {
         }
     }
 
-    for m in &mut monitors {
-        if m.physical_width_mm.is_none() {
-            if let Some(diag) = guess_diagonal_from_names(m) {
-                set_physical_from_diagonal(m, diag);
-                m.size_source = "guessed".into();
-            }
+    match edid::read_disconnected_monitors(RECENTLY_SEEN_DAYS) {
+        Ok(known) => append_disconnected(&mut monitors, known),
+        Err(e) => {
+            eprintln!("Warning: could not list disconnected monitors: {e}");
         }
-        m.compute_derived();
     }
 
     Ok(monitors)
 }
 
+#[cfg(not(windows))]
+fn system_monitors() -> Result<Vec<Monitor>, String> {
+    Err("Monitor discovery needs Windows".to_string())
+}
+
+#[cfg(windows)]
+fn append_disconnected(monitors: &mut Vec<Monitor>, known: Vec<edid::KnownMonitor>) {
+    for k in known {
+        // The interface path of a listed monitor embeds its instance ID
+        let path_fragment = k.instance_id.replace('\\', "#").to_uppercase();
+        if monitors
+            .iter()
+            .any(|m| m.monitor_device_id.to_uppercase().contains(&path_fragment))
+        {
+            continue;
+        }
+
+        let name = k.name.unwrap_or_default();
+        let kind = discovery::classify_display(None, &k.instance_id, "", &name);
+        let [res_x, res_y] = k.native_resolution.unwrap_or([0, 0]);
+        let (width_mm, height_mm, size_source) = match k.size {
+            Some(size) => (Some(size.width_mm), Some(size.height_mm), "edid"),
+            None => (None, None, "none"),
+        };
+
+        monitors.push(Monitor {
+            id: monitors.len(),
+            device_name: String::new(),
+            friendly_name: name.clone(),
+            monitor_name: name,
+            adapter_name: String::new(),
+            monitor_device_id: k.instance_id,
+            is_primary: false,
+            status: MonitorStatus::Disconnected,
+            kind,
+            last_seen: k.last_seen,
+            resolution_x: res_x,
+            resolution_y: res_y,
+            position_x: 0,
+            position_y: 0,
+            orientation: Orientation::Landscape,
+            effective_dpi: None,
+            scale_factor: None,
+            physical_width_mm: width_mm,
+            physical_height_mm: height_mm,
+            physical_width_in: None,
+            physical_height_in: None,
+            diagonal_in: None,
+            ppi: None,
+            size_source: size_source.into(),
+            overscan_pct: None,
+            yaw_deg: None,
+            curvature_radius_mm: None,
+            curvature_source: "none".into(),
+            bezel_mm: None,
+            bezel_source: "none".into(),
+            clone_group: None,
+        });
+    }
+}
+
+/// Group active monitors that mirror each other: targets sharing one
+/// display-config source (same GDI device), or separate sources placed at
+/// the identical desktop rectangle.
+#[cfg(windows)]
+fn assign_clone_groups(monitors: &mut [Monitor]) {
+    for i in 0..monitors.len() {
+        if !monitors[i].is_active() || monitors[i].clone_group.is_some() {
+            continue;
+        }
+        let mut group_found = false;
+        for j in (i + 1)..monitors.len() {
+            let (a, b) = (&monitors[i], &monitors[j]);
+            let same_source = a.device_name == b.device_name;
+            let same_rect = a.position_x == b.position_x
+                && a.position_y == b.position_y
+                && a.resolution_x == b.resolution_x
+                && a.resolution_y == b.resolution_y;
+            if b.is_active() && b.clone_group.is_none() && (same_source || same_rect) {
+                monitors[j].clone_group = Some(monitors[i].id);
+                group_found = true;
+            }
+        }
+        if group_found {
+            monitors[i].clone_group = Some(monitors[i].id);
+        }
+    }
+}
+
 /// Try to extract a plausible diagonal (inches) from monitor/adapter names.
 /// Looks for numbers 10-65 in the friendly name, monitor name, and adapter name.
 fn guess_diagonal_from_names(m: &Monitor) -> Option<f64> {
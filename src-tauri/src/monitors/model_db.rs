@@ -0,0 +1,71 @@
+//! Panel geometry of known monitor models that EDID does not describe.
+//! EDID reports a curved panel's size along the arc and says nothing about
+//! its curvature or its bezels, so common models are listed here by the
+//! model numbers that show up in their EDID or friendly names.
+
+use super::Monitor;
+
+pub struct KnownModel {
+    /// Case-insensitive substrings of the monitor or friendly name
+    pub names: &'static [&'static str],
+    /// Radius of the panel's curve in millimetres (1800 for an "1800R" panel)
+    pub curvature_radius_mm: Option<u32>,
+    /// Housing around the lit area in millimetres, with the panel in its
+    /// native orientation: top, bottom, left, right. Nominal values from
+    /// spec sheets; individual units differ by a millimetre or so.
+    pub bezel_mm: Option<[f64; 4]>,
+}
+
+const fn curved(names: &'static [&'static str], radius_mm: u32) -> KnownModel {
+    KnownModel { names, curvature_radius_mm: Some(radius_mm), bezel_mm: None }
+}
+
+const fn flat(names: &'static [&'static str]) -> KnownModel {
+    KnownModel { names, curvature_radius_mm: None, bezel_mm: None }
+}
+
+impl KnownModel {
+    const fn bezels(self, top: f64, bottom: f64, left: f64, right: f64) -> Self {
+        KnownModel { bezel_mm: Some([top, bottom, left, right]), ..self }
+    }
+}
+
+static MODELS: &[KnownModel] = &[
+    // Samsung
+    curved(&["C49G95T", "G95T", "ODYSSEY G9"], 1000),
+    curved(&["S49AG95", "G95NA", "NEO G9"], 1000),
+    curved(&["C27G75T", "C32G75T", "ODYSSEY G7"], 1000),
+    curved(&["C49RG9", "C49RG90"], 1800),
+    curved(&["C49HG90"], 1800),
+    curved(&["C34J79", "C34H89"], 1500),
+    curved(&["C27F39", "C24F39", "C27F59", "C24F59"], 1800),
+    // Dell / Alienware
+    curved(&["AW3423DW"], 1800),
+    curved(&["AW3420DW", "AW3418DW"], 1900),
+    curved(&["AW3821DW"], 2300),
+    curved(&["S3422DWG", "S3222DGM"], 1800),
+    curved(&["U3421WE", "U3423WE", "U3818DW", "U4919DW"], 3800),
+    flat(&["U2419H", "U2719D", "U2720Q", "U2421E", "P2419H", "P2719H"]).bezels(7.3, 18.0, 7.3, 7.3),
+    flat(&["U2723QE", "U2424H", "U2724D"]).bezels(6.0, 17.0, 6.0, 6.0),
+    // LG
+    curved(&["34GN850", "34GK950"], 1900),
+    curved(&["38WN95C", "38GN950"], 2300),
+    curved(&["34WP85C", "34WN80C", "49WL95C"], 3800),
+    curved(&["45GR95QE"], 800),
+    flat(&["27GL850", "27GP850"]).bezels(8.0, 19.0, 8.0, 8.0),
+    // Others
+    curved(&["PG35VQ", "XG35VQ"], 1800),
+    curved(&["499P9H"], 1800),
+    curved(&["MI CURVED", "XMMNTWQ34"], 1500),
+];
+
+/// The known model matching a monitor's names, if any.
+pub fn lookup(m: &Monitor) -> Option<&'static KnownModel> {
+    let names = [m.monitor_name.to_uppercase(), m.friendly_name.to_uppercase()];
+    MODELS.iter().find(|model| {
+        model
+            .names
+            .iter()
+            .any(|pattern| names.iter().any(|n| n.contains(pattern)))
+    })
+}
@@ -1,5 +1,80 @@
 use serde::{Deserialize, Serialize};
 
+/// Whether a monitor is part of the desktop right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MonitorStatus {
+    /// Attached to the desktop; has a real position and resolution
+    Active,
+    /// Connected but disabled in Display Settings
+    Inactive,
+    /// Not connected, but seen by Windows recently (from the device registry)
+    Disconnected,
+}
+
+/// What kind of device is behind a display output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DisplayKind {
+    /// A panel on the desk, cabled directly or through a USB dock
+    Physical,
+    /// Indirect display driver with no panel: Spacedesk, Parsec, dummy plug emulators
+    Virtual,
+    /// Miracast receiver
+    Wireless,
+    /// Remote Desktop session display
+    Remote,
+}
+
+/// Display rotation, from DEVMODE's `dmDisplayOrientation`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Orientation {
+    Landscape,
+    Portrait,
+    LandscapeFlipped,
+    PortraitFlipped,
+}
+
+impl Orientation {
+    /// Map a `DMDO_*` value (0, 1, 2, 3) to an orientation.
+    pub fn from_dmdo(value: u32) -> Self {
+        match value {
+            1 => Orientation::Portrait,
+            2 => Orientation::LandscapeFlipped,
+            3 => Orientation::PortraitFlipped,
+            _ => Orientation::Landscape,
+        }
+    }
+
+    /// Rotation in degrees: 0, 90, 180 or 270.
+    pub fn degrees(self) -> u32 {
+        match self {
+            Orientation::Landscape => 0,
+            Orientation::Portrait => 90,
+            Orientation::LandscapeFlipped => 180,
+            Orientation::PortraitFlipped => 270,
+        }
+    }
+
+    /// Turn per-side values (top, bottom, left, right) of the panel as
+    /// built into desktop orientation. Portrait is taken to mean the panel
+    /// was turned a quarter turn clockwise, so its native left edge is on top.
+    pub fn sides_to_desktop(self, [t, b, l, r]: [f64; 4]) -> [f64; 4] {
+        match self {
+            Orientation::Landscape => [t, b, l, r],
+            Orientation::Portrait => [l, r, b, t],
+            Orientation::LandscapeFlipped => [b, t, r, l],
+            Orientation::PortraitFlipped => [r, l, t, b],
+        }
+    }
+
+    /// True when the panel's native width runs vertically on the desktop.
+    pub fn is_portrait(self) -> bool {
+        matches!(self, Orientation::Portrait | Orientation::PortraitFlipped)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Monitor {
@@ -11,13 +86,25 @@ pub struct Monitor {
     /// Hardware path from EnumDisplayDevices, e.g. "MONITOR\HPN3645\{guid}\0001"
     pub monitor_device_id: String,
     pub is_primary: bool,
+    pub status: MonitorStatus,
+    pub kind: DisplayKind,
+    /// When a disconnected monitor was last connected (Unix time, ms)
+    pub last_seen: Option<u64>,
 
     pub resolution_x: u32,
     pub resolution_y: u32,
     pub position_x: i32,
     pub position_y: i32,
-    pub orientation: u32,
+    pub orientation: Orientation,
+
+    /// Effective DPI Windows uses for this monitor (96 = 100% scaling)
+    pub effective_dpi: Option<u32>,
+    /// Display scaling factor, e.g. 1.5 for 150%. Resolution and position
+    /// above are always in physical pixels regardless of this value.
+    pub scale_factor: Option<f64>,
 
+    /// Physical size in desktop orientation: for portrait monitors these are
+    /// the panel's native height and width, matching `resolution_x/y`.
     pub physical_width_mm: Option<u32>,
     pub physical_height_mm: Option<u32>,
 
@@ -26,11 +113,49 @@ pub struct Monitor {
     pub diagonal_in: Option<f64>,
     pub ppi: Option<f64>,
 
-    /// How physical size was determined: "edid", "guessed", "manual", or "none"
+    /// How physical size was determined: "edid", "guessed", "manual",
+    /// "reference" (measured against an object held to the screen), or "none"
     pub size_source: String,
+
+    /// Percentage of the image cropped at each edge by TV overscan (0 = none)
+    pub overscan_pct: Option<f64>,
+
+    /// Toe-in angle about the vertical axis in degrees, set by the user for
+    /// angled side monitors; positive turns the right edge away from the viewer
+    pub yaw_deg: Option<f64>,
+
+    /// Radius of a curved panel in millimetres ("1800R" is 1800). The curve
+    /// runs along the panel's native width, and the EDID size is measured
+    /// along the arc. `None` for flat panels.
+    pub curvature_radius_mm: Option<u32>,
+    /// How the curvature was determined: "model", "manual", or "none"
+    pub curvature_source: String,
+
+    /// Width of the housing around the lit area in millimetres, in desktop
+    /// orientation: top, bottom, left, right
+    pub bezel_mm: Option<[f64; 4]>,
+    /// How the bezels were determined: "model", "manual", or "none"
+    pub bezel_source: String,
+
+    /// Lowest monitor id of the mirror group this monitor belongs to, if it
+    /// duplicates another display. Members share one desktop position.
+    pub clone_group: Option<usize>,
 }
 
 impl Monitor {
+    pub fn is_active(&self) -> bool {
+        self.status == MonitorStatus::Active
+    }
+
+    pub fn is_physical(&self) -> bool {
+        self.kind == DisplayKind::Physical
+    }
+
+    /// True for every mirror group member except the one with the lowest id.
+    pub fn is_secondary_clone(&self) -> bool {
+        self.clone_group.is_some_and(|g| g != self.id)
+    }
+
     pub fn compute_derived(&mut self) {
         if let (Some(w_mm), Some(h_mm)) = (self.physical_width_mm, self.physical_height_mm) {
             if w_mm == 0 || h_mm == 0 {
@@ -51,4 +176,72 @@ impl Monitor {
             }
         }
     }
+
+    /// Pixels per inch of the desktop image as actually shown on the panel.
+    /// Overscan stretches the visible part of the image over the whole panel,
+    /// so each pixel is physically larger than the EDID size implies.
+    pub fn effective_ppi(&self) -> Option<f64> {
+        let ppi = self.ppi?;
+        match self.overscan_pct {
+            Some(pct) if pct > 0.0 => Some(ppi * (1.0 - 2.0 * pct / 100.0)),
+            _ => Some(ppi),
+        }
+    }
+
+    /// Panel pixels per inch that show the desktop image at `effective_ppi`;
+    /// the inverse of `effective_ppi`.
+    pub fn panel_ppi(&self, effective_ppi: f64) -> f64 {
+        match self.overscan_pct {
+            Some(pct) if pct > 0.0 => effective_ppi / (1.0 - 2.0 * pct / 100.0),
+            _ => effective_ppi,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Monitor {
+    /// An active physical landscape monitor at a desktop position, with an
+    /// EDID size in millimetres if `size_mm` is given.
+    pub fn for_test(
+        id: usize,
+        position: [i32; 2],
+        resolution: [u32; 2],
+        size_mm: Option<[u32; 2]>,
+    ) -> Self {
+        let mut m = Monitor {
+            id,
+            device_name: format!("\\\\.\\DISPLAY{}", id + 1),
+            friendly_name: String::new(),
+            monitor_name: String::new(),
+            adapter_name: String::new(),
+            monitor_device_id: String::new(),
+            is_primary: id == 0,
+            status: MonitorStatus::Active,
+            kind: DisplayKind::Physical,
+            last_seen: None,
+            resolution_x: resolution[0],
+            resolution_y: resolution[1],
+            position_x: position[0],
+            position_y: position[1],
+            orientation: Orientation::Landscape,
+            effective_dpi: None,
+            scale_factor: None,
+            physical_width_mm: size_mm.map(|s| s[0]),
+            physical_height_mm: size_mm.map(|s| s[1]),
+            physical_width_in: None,
+            physical_height_in: None,
+            diagonal_in: None,
+            ppi: None,
+            size_source: if size_mm.is_some() { "edid" } else { "none" }.into(),
+            overscan_pct: None,
+            yaw_deg: None,
+            curvature_radius_mm: None,
+            curvature_source: "none".into(),
+            bezel_mm: None,
+            bezel_source: "none".into(),
+            clone_group: None,
+        };
+        m.compute_derived();
+        m
+    }
 }
@@ -0,0 +1,569 @@
+//! Ground-truth simulator for the calibration pipeline. A desk whose
+//! physical layout is known exactly is measured by a simulated user who puts
+//! the scale and gap lines where a perfect eye would, optionally with
+//! human-like jitter. The readings go through the same `run_calibration`
+//! and `compute_physical_placements` as a real run, and the placements that
+//! come out are compared with the truth in millimetres.
+
+use crate::calibration::adjacency::Rect;
+use crate::calibration::flow::CalibrationPair;
+use crate::calibration::overlay::{self, MonitorRect};
+use crate::calibration::{self, CalibrationOptions, MeasurementSource};
+use crate::export;
+use crate::monitors::models::DisplayKind;
+use crate::monitors::{Monitor, MonitorStatus, Orientation};
+use serde::Serialize;
+
+/// One monitor of the simulated desk.
+#[derive(Debug, Clone, Copy)]
+pub struct SimMonitor {
+    /// Where Windows has it: desktop position and resolution
+    pub rect: Rect,
+    /// Lit area in millimetres. Pixels are square, so this fixes the density.
+    pub size_mm: [f64; 2],
+    /// Top-left corner of the lit area before tilting, mm; x grows
+    /// rightwards and y downwards
+    pub position_mm: [f64; 2],
+    /// In-plane tilt about the lit area's centre, degrees clockwise
+    pub tilt_deg: f64,
+    /// Whether discovery finds the size in EDID. Without it the monitor's
+    /// density only follows from the calibration chain.
+    pub edid_size: bool,
+}
+
+impl SimMonitor {
+    fn px_per_mm(&self) -> f64 {
+        self.rect.w as f64 / self.size_mm[0]
+    }
+
+    fn center_mm(&self) -> [f64; 2] {
+        [
+            self.position_mm[0] + self.size_mm[0] / 2.0,
+            self.position_mm[1] + self.size_mm[1] / 2.0,
+        ]
+    }
+
+    /// The monitor as discovery would report it, with an exact EDID size
+    /// if it has one.
+    fn monitor(&self, id: usize) -> Monitor {
+        let [w_mm, h_mm] = self.size_mm;
+        let (w_in, h_in) = (w_mm / 25.4, h_mm / 25.4);
+        let edid = |value| self.edid_size.then_some(value);
+        let name = format!("Simulated {}", id + 1);
+        Monitor {
+            id,
+            device_name: format!("\\\\.\\DISPLAY{}", id + 1),
+            friendly_name: name.clone(),
+            monitor_name: name,
+            adapter_name: String::new(),
+            monitor_device_id: String::new(),
+            is_primary: id == 0,
+            status: MonitorStatus::Active,
+            kind: DisplayKind::Physical,
+            last_seen: None,
+            resolution_x: self.rect.w as u32,
+            resolution_y: self.rect.h as u32,
+            position_x: self.rect.x,
+            position_y: self.rect.y,
+            orientation: Orientation::Landscape,
+            effective_dpi: None,
+            scale_factor: None,
+            physical_width_mm: edid(w_mm.round()).map(|mm| mm as u32),
+            physical_height_mm: edid(h_mm.round()).map(|mm| mm as u32),
+            physical_width_in: edid(w_in),
+            physical_height_in: edid(h_in),
+            diagonal_in: edid(w_in.hypot(h_in)),
+            ppi: edid(self.rect.w as f64 / w_in),
+            size_source: if self.edid_size { "edid" } else { "none" }.into(),
+            overscan_pct: None,
+            yaw_deg: None,
+            curvature_radius_mm: None,
+            curvature_source: "none".into(),
+            bezel_mm: None,
+            bezel_source: "none".into(),
+            clone_group: None,
+        }
+    }
+}
+
+/// Human-like jitter of the simulated user.
+#[derive(Debug, Clone, Copy)]
+pub struct Noise {
+    /// One-sigma error of every line the user places, in pixels
+    pub line_sigma_px: f64,
+    /// Seed of the jitter, so a run can be repeated exactly
+    pub seed: u64,
+}
+
+/// How far one monitor ended up from where it really is.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MonitorError {
+    pub monitor: usize,
+    /// Centre error relative to the reference monitor's centre, mm
+    pub dx_mm: f64,
+    pub dy_mm: f64,
+    /// Tilt error relative to the reference monitor, degrees
+    pub tilt_deg: f64,
+    /// False when only the monitor's size was measured, so its position is
+    /// the Windows arrangement's and the error says little
+    pub positioned: bool,
+    /// The calibration's own uncertainty of the monitor's scale, as a
+    /// fraction of it, and of its position, mm (zero for the reference)
+    pub scale_sigma: f64,
+    pub position_sigma_mm: [f64; 2],
+}
+
+impl MonitorError {
+    pub fn distance_mm(&self) -> f64 {
+        self.dx_mm.hypot(self.dy_mm)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimulationReport {
+    pub reference: usize,
+    /// Every placed monitor, the reference included
+    pub monitors: Vec<MonitorError>,
+}
+
+impl SimulationReport {
+    /// Largest centre error of any positioned monitor, mm.
+    pub fn max_error_mm(&self) -> f64 {
+        self.monitors
+            .iter()
+            .filter(|m| m.positioned)
+            .map(MonitorError::distance_mm)
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Calibrate the simulated desk with `options` and compare the placements
+/// with the truth. Every monitor is selected; monitor 0 is the primary.
+pub fn simulate(
+    desk: &[SimMonitor],
+    options: &CalibrationOptions,
+    noise: Option<Noise>,
+) -> Result<SimulationReport, String> {
+    if let Some(i) = desk.iter().position(|m| {
+        m.rect.w <= 0 || m.rect.h <= 0 || !(m.size_mm[0] > 0.0 && m.size_mm[1] > 0.0)
+    }) {
+        return Err(format!("Simulated display {} has no area", i + 1));
+    }
+    let monitors: Vec<Monitor> = desk.iter().enumerate().map(|(i, m)| m.monitor(i)).collect();
+    let selected: Vec<usize> = (0..desk.len()).collect();
+
+    let mut user = SimulatedUser {
+        desk,
+        gap_step: calibration::gap_angle_deg(options).to_radians().tan(),
+        noise,
+        seed: noise.map_or(0, |n| n.seed.max(1)),
+    };
+    let session = calibration::run_calibration_with(&monitors, &selected, options, &mut user)?;
+    let layout = export::compute_physical_placements(&monitors, &session.results, &selected, None);
+
+    let center_in = |idx: usize| {
+        let p = layout.placements.iter().find(|p| p.monitor_idx == idx)?;
+        Some([(p.x + p.w / 2.0) * 25.4, (p.y + p.h / 2.0) * 25.4])
+    };
+    let reference = session.reference;
+    let ref_placed = center_in(reference).ok_or("The reference display wasn't placed")?;
+    let ref_true = desk[reference].center_mm();
+    let ref_px_per_mm = desk[reference].px_per_mm();
+
+    let monitors = layout
+        .placements
+        .iter()
+        .map(|p| {
+            let truth = &desk[p.monitor_idx];
+            let placed = center_in(p.monitor_idx).unwrap_or(ref_placed);
+            let center = truth.center_mm();
+            let result = session.results.iter().find(|r| r.monitor_id == p.monitor_idx);
+            let position_sigma = result.map_or([0.0; 2], |r| r.position_sigma);
+            MonitorError {
+                monitor: p.monitor_idx,
+                dx_mm: (placed[0] - ref_placed[0]) - (center[0] - ref_true[0]),
+                dy_mm: (placed[1] - ref_placed[1]) - (center[1] - ref_true[1]),
+                tilt_deg: p.tilt_deg - (truth.tilt_deg - desk[reference].tilt_deg),
+                positioned: p.positioned,
+                scale_sigma: result.map_or(0.0, |r| r.scale_sigma),
+                position_sigma_mm: position_sigma.map(|px| px / ref_px_per_mm),
+            }
+        })
+        .collect();
+    Ok(SimulationReport { reference, monitors })
+}
+
+/// A monitor's edge facing its partner, in coordinates that run along the
+/// shared edge and across it. For stacked pairs these are x and y swapped,
+/// which mirrors the tilt.
+struct Edge {
+    /// Centre of the lit area, mm
+    along: f64,
+    across: f64,
+    /// Half the lit area's length along and across the shared edge, mm
+    half_along: f64,
+    half_across: f64,
+    /// Which side of the centre the edge is on, -1 or 1
+    side: f64,
+    tilt_rad: f64,
+    px_per_mm: f64,
+}
+
+impl Edge {
+    fn of(m: &SimMonitor, bind_horizontal: bool, side: f64) -> Self {
+        let [cx, cy] = m.center_mm();
+        let [w, h] = m.size_mm;
+        let tilt_rad = m.tilt_deg.to_radians();
+        let (along, across, half_along, half_across, tilt_rad) = if bind_horizontal {
+            (cy, cx, h / 2.0, w / 2.0, tilt_rad)
+        } else {
+            (cx, cy, w / 2.0, h / 2.0, -tilt_rad)
+        };
+        Edge {
+            along,
+            across,
+            half_along,
+            half_across,
+            side,
+            tilt_rad,
+            px_per_mm: m.px_per_mm(),
+        }
+    }
+
+    /// Along-edge coordinate on the desk of the point `px` pixels from the
+    /// edge's top (or left) end.
+    fn desk_along(&self, px: f64) -> f64 {
+        let t = px / self.px_per_mm - self.half_along;
+        let (sin, cos) = self.tilt_rad.sin_cos();
+        self.along + self.side * self.half_across * sin + t * cos
+    }
+
+    /// Distance along the edge, from the centre, of where it crosses the desk
+    /// coordinate `along`.
+    fn t_at(&self, along: f64) -> f64 {
+        let (sin, cos) = self.tilt_rad.sin_cos();
+        (along - self.along - self.side * self.half_across * sin) / cos
+    }
+
+    /// Pixels from the edge's top (or left) end to the desk coordinate `along`.
+    fn px_at(&self, along: f64) -> f64 {
+        (self.t_at(along) + self.half_along) * self.px_per_mm
+    }
+
+    /// Across-edge coordinate on the desk where the edge crosses `along`.
+    fn across_at(&self, along: f64) -> f64 {
+        let (sin, cos) = self.tilt_rad.sin_cos();
+        self.across + self.side * self.half_across * cos - self.t_at(along) * sin
+    }
+
+    /// Desk span of the edge along the shared edge.
+    fn span(&self) -> (f64, f64) {
+        let ends = [
+            self.desk_along(0.0),
+            self.desk_along(2.0 * self.half_along * self.px_per_mm),
+        ];
+        (ends[0].min(ends[1]), ends[0].max(ends[1]))
+    }
+}
+
+/// Readings as a user with perfect eyes would give them, with optional
+/// jitter. Positions are in desktop coordinates.
+struct SimulatedUser<'a> {
+    desk: &'a [SimMonitor],
+    /// Gap change per pixel of line shift in the gap step
+    gap_step: f64,
+    noise: Option<Noise>,
+    seed: u64,
+}
+
+impl SimulatedUser<'_> {
+    /// Normally distributed jitter, in pixels.
+    fn jitter(&mut self) -> f64 {
+        let Some(noise) = self.noise else {
+            return 0.0;
+        };
+        let mut uniform = || {
+            self.seed ^= self.seed << 13;
+            self.seed ^= self.seed >> 7;
+            self.seed ^= self.seed << 17;
+            // In (0, 1], so the logarithm stays finite
+            ((self.seed >> 11) as f64 + 1.0) / (1u64 << 53) as f64
+        };
+        let (u1, u2) = (uniform(), uniform());
+        noise.line_sigma_px * (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+    }
+
+    /// Facing edges of the unbound and the bound monitor, and where each
+    /// one's top (or left) end is on the desktop.
+    fn edges(&self, pair: &CalibrationPair) -> ((Edge, i32), (Edge, i32)) {
+        let (mu, mb) = (&self.desk[pair.unbound], &self.desk[pair.bound]);
+        let unbound_first = if pair.bind_horizontal {
+            mu.rect.x < mb.rect.x
+        } else {
+            mu.rect.y < mb.rect.y
+        };
+        let side = if unbound_first { 1.0 } else { -1.0 };
+        let start = |m: &SimMonitor| if pair.bind_horizontal { m.rect.y } else { m.rect.x };
+        (
+            (Edge::of(mu, pair.bind_horizontal, side), start(mu)),
+            (Edge::of(mb, pair.bind_horizontal, -side), start(mb)),
+        )
+    }
+
+    /// Place a line `px` pixels along an edge of `len` pixels, as a whole
+    /// pixel on screen.
+    fn place(&mut self, px: f64, len: f64) -> i32 {
+        (px + self.jitter()).round().clamp(0.0, len - 1.0) as i32
+    }
+}
+
+impl MeasurementSource for SimulatedUser<'_> {
+    fn rect(&self, idx: usize) -> Rect {
+        self.desk[idx].rect
+    }
+
+    fn scale_lines(
+        &mut self,
+        pair: &CalibrationPair,
+        trial: Option<(u32, u32)>,
+    ) -> Result<[i32; 4], String> {
+        let ((edge_u, start_u), (edge_b, start_b)) = self.edges(pair);
+        let (lo_u, hi_u) = edge_u.span();
+        let (lo_b, hi_b) = edge_b.span();
+        let (lo, hi) = (lo_u.max(lo_b), hi_u.min(hi_b));
+        if hi - lo < 1.0 {
+            return Err(format!(
+                "Simulated displays {} and {} don't face each other",
+                pair.unbound + 1,
+                pair.bound + 1
+            ));
+        }
+        let len = |e: &Edge| 2.0 * e.half_along * e.px_per_mm;
+
+        // The lines start where the overlay would put them, scattered the
+        // same way when the step is repeated
+        let rect = |idx: usize| {
+            let Rect { x, y, w, h } = self.desk[idx].rect;
+            MonitorRect { x, y, w, h }
+        };
+        let mut seed = overlay::trial_seed(self.seed, trial);
+        let starts = overlay::start_lines(
+            &rect(pair.unbound),
+            &rect(pair.bound),
+            pair.bind_horizontal,
+            &mut seed,
+        );
+
+        // The bound line stays where it started if that's well inside its
+        // half of the part of the edge both monitors share (blue the first,
+        // red the second); otherwise it's dragged to the middle of that half.
+        // Then the unbound line is matched to it.
+        let (span, margin) = (hi - lo, (hi - lo) / 16.0);
+        let mut line = |start: i32, half: (f64, f64)| {
+            let started = edge_b.desk_along((start - start_b) as f64);
+            let bound = if (half.0..=half.1).contains(&started) {
+                start - start_b
+            } else {
+                let middle = (half.0 + half.1) / 2.0;
+                self.place(edge_b.px_at(middle), len(&edge_b))
+            };
+            let matched = edge_b.desk_along(bound as f64);
+            let unbound = self.place(edge_u.px_at(matched), len(&edge_u));
+            (start_u + unbound, start_b + bound)
+        };
+        let mid = lo + span / 2.0;
+        let (blue_u, blue_b) = line(starts[1], (lo + margin, mid));
+        let (red_u, red_b) = line(starts[3], (mid, hi - margin));
+        Ok([blue_u, blue_b, red_u, red_b])
+    }
+
+    fn gap(
+        &mut self,
+        pair: &CalibrationPair,
+        _line: usize,
+        anchor: [i32; 2],
+        _trial: Option<(u32, u32)>,
+    ) -> Result<f64, String> {
+        let ((edge_u, _), (edge_b, start_b)) = self.edges(pair);
+        // Read level with the bound monitor's line, across whatever the
+        // monitors' tilt makes of the gap there
+        let along = edge_b.desk_along((anchor[1] - start_b) as f64);
+        let gap_mm = edge_b.side * (edge_u.across_at(along) - edge_b.across_at(along));
+        let shift = (gap_mm * edge_b.px_per_mm / self.gap_step + self.jitter()).round();
+        Ok(shift * self.gap_step)
+    }
+
+    fn squares(
+        &mut self,
+        pair: &CalibrationPair,
+        _trial: Option<(u32, u32)>,
+    ) -> Result<[i32; 2], String> {
+        let (mu, mb) = (&self.desk[pair.unbound], &self.desk[pair.bound]);
+        // Any fixed square works; the overlay shows a third of the shorter side
+        let fixed = mb.rect.w.min(mb.rect.h) / 3;
+        let side = fixed as f64 / mb.px_per_mm() * mu.px_per_mm() + self.jitter();
+        Ok([side.round() as i32, fixed])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calibration::constraints::Constraint;
+    use crate::calibration::sizes;
+
+    fn monitor(rect: [i32; 4], size_mm: [f64; 2], position_mm: [f64; 2]) -> SimMonitor {
+        let [x, y, w, h] = rect;
+        SimMonitor {
+            rect: Rect { x, y, w, h },
+            size_mm,
+            position_mm,
+            tilt_deg: 0.0,
+            edid_size: true,
+        }
+    }
+
+    /// A 27" 1440p monitor in the middle, a 24" 1080p one on its left set
+    /// lower, and a 27" 4K one on its right, with uneven gaps.
+    fn triple() -> Vec<SimMonitor> {
+        vec![
+            monitor([0, 0, 2560, 1440], [596.7, 335.6], [0.0, 0.0]),
+            monitor([-1920, 200, 1920, 1080], [527.0, 296.4], [-549.0, 31.0]),
+            monitor([2560, -300, 3840, 2160], [596.7, 335.6], [614.2, -12.5]),
+        ]
+    }
+
+    #[test]
+    fn perfect_user_recovers_side_by_side_desk() {
+        let report = simulate(&triple(), &CalibrationOptions::default(), None).unwrap();
+        assert_eq!(report.monitors.len(), 3);
+        assert!(report.max_error_mm() < 0.5, "{report:?}");
+        assert!(report.monitors.iter().all(|m| m.tilt_deg.abs() < 0.05));
+    }
+
+    #[test]
+    fn perfect_user_recovers_laptop_below() {
+        let desk = [
+            monitor([0, 0, 2560, 1440], [596.7, 335.6], [0.0, 0.0]),
+            monitor([400, 1440, 1920, 1200], [286.5, 179.1], [180.3, 402.0]),
+        ];
+        let report = simulate(&desk, &CalibrationOptions::default(), None).unwrap();
+        assert!(report.max_error_mm() < 0.5, "{report:?}");
+    }
+
+    #[test]
+    fn tilt_is_recovered() {
+        let mut desk = triple();
+        desk[2].tilt_deg = 1.5;
+        let report = simulate(&desk, &CalibrationOptions::default(), None).unwrap();
+        let tilted = report.monitors.iter().find(|m| m.monitor == 2).unwrap();
+        assert!(tilted.tilt_deg.abs() < 0.1, "{report:?}");
+        assert!(report.max_error_mm() < 0.5, "{report:?}");
+    }
+
+    #[test]
+    fn density_follows_a_chain_of_monitors_without_sizes() {
+        // Reference, then a 23.8" 1080p and a 27" 4K further right, neither
+        // reporting a size: the 4K one's density comes from its scale
+        // relative to the middle monitor, whose own density is derived
+        let mut desk = vec![
+            monitor([0, 0, 2560, 1440], [596.7, 335.6], [0.0, 0.0]),
+            monitor([2560, 180, 1920, 1080], [527.0, 296.4], [612.0, 24.0]),
+            monitor([4480, -300, 3840, 2160], [596.7, 335.6], [1153.5, -8.0]),
+        ];
+        desk[1].edid_size = false;
+        desk[2].edid_size = false;
+        let options = CalibrationOptions {
+            pairs: Some(vec![(1, 0), (2, 1)]),
+            ..Default::default()
+        };
+        let report = simulate(&desk, &options, None).unwrap();
+        assert_eq!(report.monitors.len(), 3);
+        // Each unbound line is rounded to a whole pixel, and the scale error
+        // that leaves compounds down the chain
+        assert!(report.max_error_mm() < 1.5, "{report:?}");
+    }
+
+    #[test]
+    fn tilt_carries_down_a_chain() {
+        // Two 23.8" monitors right of the reference, both turned 2°, and a
+        // laptop below the first turned 1° further
+        let mut desk = vec![
+            monitor([0, 0, 2560, 1440], [596.7, 335.6], [0.0, 0.0]),
+            monitor([2560, 0, 1920, 1080], [527.0, 296.4], [615.0, 20.0]),
+            monitor([4480, 0, 1920, 1080], [527.0, 296.4], [1160.0, 39.0]),
+            monitor([2800, 1080, 1920, 1200], [286.5, 179.1], [735.0, 345.0]),
+        ];
+        desk[1].tilt_deg = 2.0;
+        desk[2].tilt_deg = 2.0;
+        desk[3].tilt_deg = 3.0;
+        let options = CalibrationOptions {
+            pairs: Some(vec![(1, 0), (2, 1), (3, 1)]),
+            ..Default::default()
+        };
+        let report = simulate(&desk, &options, None).unwrap();
+        assert_eq!(report.monitors.len(), 4);
+        assert!(report.monitors.iter().all(|m| m.tilt_deg.abs() < 0.1), "{report:?}");
+        assert!(report.max_error_mm() < 1.5, "{report:?}");
+    }
+
+    #[test]
+    fn noisy_user_stays_within_a_few_millimetres() {
+        let options = CalibrationOptions {
+            repeats: Some(5),
+            ..Default::default()
+        };
+        let noise = Some(Noise {
+            line_sigma_px: 1.5,
+            seed: 7,
+        });
+        let report = simulate(&triple(), &options, noise).unwrap();
+        assert!(report.max_error_mm() < 3.0, "{report:?}");
+        // The trials start scattered, but the uncertainty reflects only the
+        // jitter, and it covers the errors actually made
+        for m in report.monitors.iter().filter(|m| m.monitor != report.reference) {
+            assert!(m.scale_sigma > 0.0 && m.scale_sigma < 0.01, "{m:?}");
+            let [sx, sy] = m.position_sigma_mm;
+            assert!(sx < 3.0 && sy < 3.0, "{m:?}");
+            assert!(m.dx_mm.abs() < 3.0 * sx + 0.5 && m.dy_mm.abs() < 3.0 * sy + 0.5, "{m:?}");
+        }
+        // The same seed gives the same run
+        let again = simulate(&triple(), &options, noise).unwrap();
+        assert_eq!(report.max_error_mm(), again.max_error_mm());
+    }
+
+    #[test]
+    fn declared_level_bottoms_still_measure_the_scale() {
+        // Bottoms level on the desk, but the 23.8" monitor's EDID claims
+        // 27.4": the declared fact moves the lines, and the measured scale
+        // still exposes the wrong size
+        let desk = [
+            monitor([0, 0, 2560, 1440], [596.7, 335.6], [0.0, 0.0]),
+            monitor([-1920, 200, 1920, 1080], [527.0, 296.4], [-549.0, 39.2]),
+        ];
+        let monitors = [
+            desk[0].monitor(0),
+            Monitor::for_test(1, [-1920, 200], [1920, 1080], Some([606, 340])),
+        ];
+        let options = CalibrationOptions {
+            constraints: vec![Constraint::BottomsLevel { monitors: vec![0, 1] }],
+            ..Default::default()
+        };
+        let mut user = SimulatedUser {
+            desk: &desk,
+            gap_step: 1.0,
+            noise: None,
+            seed: 0,
+        };
+        let session = calibration::run_calibration_with(&monitors, &[0, 1], &options, &mut user)
+            .unwrap();
+        assert!(session.measurements[0].declared_offset);
+
+        let suggestions = sizes::suggest_diagonals(&monitors, &session);
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].monitor_id, 1);
+        assert!((suggestions[0].suggested_diagonal_in - 23.8).abs() < 0.1, "{suggestions:?}");
+    }
+}
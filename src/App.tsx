@@ -1,4 +1,4 @@
-import { useCallback, useEffect, useState } from "react";
+import { useCallback, useEffect, useMemo, useState } from "react";
 import MonitorLayoutMap from "./components/MonitorLayoutMap";
 import PhysicalLayoutMap from "./components/PhysicalLayoutMap";
 import MonitorList from "./components/MonitorList";
@@ -8,17 +8,82 @@ import StatusBar from "./components/StatusBar";
 import {
   discoverMonitors,
   startCalibration,
+  getCalibrationPairs,
+  checkMonitorSizes,
+  setMonitorDiagonal,
+  remeasurePair,
   exportCalibrationJson,
   saveCalibrationFile,
+  getViewingDistance,
+  setViewingDistance,
   openUrl,
 } from "./hooks/useTauriCommands";
 import AboutDialog from "./components/AboutDialog";
 import SettingsDialog from "./components/SettingsDialog";
 import { buildSpanrightUrl, SPANRIGHT_BASE_URL } from "./spanrightUrl";
-import type { CalibrationResult, CalibrationStatus, Monitor } from "./types";
+import type {
+  CalibrationOptions,
+  CalibrationPair,
+  CalibrationResult,
+  CalibrationSession,
+  CalibrationStatus,
+  Constraint,
+  DeskFacts,
+  Monitor,
+  SizeSuggestion,
+  TapeEntry,
+  TapeMeasurement,
+} from "./types";
+
+// Monitors calibrated unless the user picks otherwise: real panels on the desktop
+function defaultSelection(monitors: Monitor[]): number[] {
+  return monitors
+    .filter(
+      (m) =>
+        m.status === "active" &&
+        m.kind === "physical" &&
+        (m.cloneGroup === null || m.cloneGroup === m.id)
+    )
+    .map((m) => m.id);
+}
+
+// Same rule as the backend: the primary if selected, else the first selected
+function defaultReference(monitors: Monitor[], selectedIds: number[]): number | null {
+  return (
+    selectedIds.find((id) => monitors[id]?.isPrimary) ?? selectedIds[0] ?? null
+  );
+}
+
+// A mirror group is laid out through the member it was calibrated on, since
+// the scale and gaps only hold for that panel. If a different member is
+// selected now, it just lends the group its name. Mirrors
+// with_measured_clone_members in export/mod.rs.
+function withMeasuredCloneMembers(
+  monitors: Monitor[],
+  results: CalibrationResult[],
+  selectedIds: number[]
+): { layoutMonitors: Monitor[]; layoutIds: number[] } {
+  const measuredIds = results.flatMap((r) => [r.monitorId, r.boundTo]);
+  const renamed = new Map<number, Monitor>();
+  const layoutIds = selectedIds.map((s) => {
+    const group = monitors[s]?.cloneGroup ?? null;
+    if (group === null) return s;
+    const measured = measuredIds.find((id) => monitors[id]?.cloneGroup === group) ?? s;
+    if (measured !== s) renamed.set(measured, monitors[s]);
+    return measured;
+  });
+  const layoutMonitors = monitors.map((m) => {
+    const shown = renamed.get(m.id);
+    return shown
+      ? { ...m, friendlyName: shown.friendlyName, monitorName: shown.monitorName }
+      : m;
+  });
+  return { layoutMonitors, layoutIds };
+}
 
 export default function App() {
   const [monitors, setMonitors] = useState<Monitor[]>([]);
+  const [selectedIds, setSelectedIds] = useState<number[]>([]);
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState<string | null>(null);
   const [calibrationStatus, setCalibrationStatus] =
@@ -29,6 +94,74 @@ export default function App() {
   const [showAbout, setShowAbout] = useState(false);
   const [showSettings, setShowSettings] = useState(false);
   const [includeVirtualLayout, setIncludeVirtualLayout] = useState(false);
+  const [includeUncertainty, setIncludeUncertainty] = useState(false);
+  // null = automatic choice
+  const [reference, setReference] = useState<number | null>(null);
+  // Bound monitor for each unbound one; null = automatic pairing
+  const [pairing, setPairing] = useState<Record<number, number> | null>(null);
+  const [measureAllAdjacent, setMeasureAllAdjacent] = useState(false);
+  const [repeats, setRepeats] = useState(1);
+  const [gapAngleDeg, setGapAngleDeg] = useState(45);
+  // Monitors compared by size only, e.g. on another wall
+  const [matchedSquare, setMatchedSquare] = useState<number[]>([]);
+  // Tape readings per unbound monitor; null = every pair measured on screen
+  const [tape, setTape] = useState<Record<number, TapeEntry> | null>(null);
+  const [tapeUnit, setTapeUnit] = useState<"mm" | "in">("mm");
+  // Known facts about the desk; null = none declared
+  const [deskFacts, setDeskFacts] = useState<DeskFacts | null>(null);
+  // Pairs the next run will measure, listed for tape entry and desk facts
+  const [calibrationPairs, setCalibrationPairs] = useState<CalibrationPair[]>([]);
+  const [session, setSession] = useState<CalibrationSession | null>(null);
+  // Monitors whose size the calibration contradicts
+  const [sizeSuggestions, setSizeSuggestions] = useState<SizeSuggestion[]>([]);
+  // Eye distance for toed-in monitors; null = flat layout only
+  const [viewingDistanceMm, setViewingDistanceMm] = useState<number | null>(null);
+
+  const effectiveReference =
+    reference !== null && selectedIds.includes(reference)
+      ? reference
+      : defaultReference(monitors, selectedIds);
+
+  // Reference and pairing the next run will use
+  const treeOptions = useMemo(() => {
+    const options: CalibrationOptions = {};
+    if (reference !== null && selectedIds.includes(reference)) {
+      options.reference = reference;
+    }
+    if (pairing !== null && effectiveReference !== null) {
+      options.reference = effectiveReference;
+      options.pairs = selectedIds
+        .filter((id) => id !== effectiveReference)
+        .map((id) => [id, pairing[id] ?? effectiveReference]);
+    }
+    return options;
+  }, [reference, pairing, selectedIds, effectiveReference]);
+
+  const pairsNeeded = tape !== null || deskFacts !== null;
+  useEffect(() => {
+    if (!pairsNeeded || selectedIds.length < 2) {
+      setCalibrationPairs([]);
+      return;
+    }
+    getCalibrationPairs(selectedIds, treeOptions)
+      .then(setCalibrationPairs)
+      .catch(() => setCalibrationPairs([]));
+  }, [pairsNeeded, selectedIds, treeOptions, monitors]);
+
+  useEffect(() => {
+    if (!session) {
+      setSizeSuggestions([]);
+      return;
+    }
+    checkMonitorSizes(session)
+      .then(setSizeSuggestions)
+      .catch(() => setSizeSuggestions([]));
+  }, [session, monitors]);
+
+  const { layoutMonitors, layoutIds } = useMemo(
+    () => withMeasuredCloneMembers(monitors, calibrationResults, selectedIds),
+    [monitors, calibrationResults, selectedIds]
+  );
 
   const refresh = useCallback(async () => {
     setLoading(true);
@@ -36,6 +169,13 @@ export default function App() {
     try {
       const result = await discoverMonitors();
       setMonitors(result);
+      setSelectedIds((prev) => {
+        const active = result
+          .filter((m) => m.status === "active")
+          .map((m) => m.id);
+        const kept = prev.filter((id) => active.includes(id));
+        return kept.length > 0 ? kept : defaultSelection(result);
+      });
     } catch (e) {
       setError(e instanceof Error ? e.message : String(e));
     } finally {
@@ -47,12 +187,69 @@ export default function App() {
     refresh();
   }, [refresh]);
 
+  useEffect(() => {
+    getViewingDistance().then(setViewingDistanceMm).catch(() => {});
+  }, []);
+
+  const handleViewingDistanceChange = async (mm: number | null) => {
+    try {
+      await setViewingDistance(mm);
+      setViewingDistanceMm(mm);
+      setError(null);
+    } catch (e) {
+      setError(e instanceof Error ? e.message : String(e));
+    }
+  };
+
+  const handleApplyDiagonal = async (id: number, diagonal: number) => {
+    try {
+      await setMonitorDiagonal(id, Math.round(diagonal * 10) / 10);
+      await refresh();
+    } catch (e) {
+      setError(e instanceof Error ? e.message : String(e));
+    }
+  };
+
   const handleCalibrate = async () => {
     setCalibrationStatus("in_progress");
     setError(null);
     try {
-      const results = await startCalibration();
-      setCalibrationResults(results);
+      const mmPerUnit = tapeUnit === "mm" ? 1 : 25.4;
+      const tapeMeasurements = calibrationPairs.flatMap((p): TapeMeasurement[] => {
+        const entry = tape?.[p.unbound];
+        const gap = parseFloat(entry?.gap ?? "");
+        const offset = entry?.offset.trim() ? parseFloat(entry.offset) : 0;
+        if (!Number.isFinite(gap) || !Number.isFinite(offset)) return [];
+        return [{ unbound: p.unbound, gapMm: gap * mmPerUnit, offsetMm: offset * mmPerUnit }];
+      });
+      const constraints: Constraint[] = [];
+      if (deskFacts) {
+        const onDesk = deskFacts.onDesk.filter((id) => selectedIds.includes(id));
+        if (onDesk.length >= 2) constraints.push({ kind: "bottomsLevel", monitors: onDesk });
+        for (const p of calibrationPairs) {
+          const pair: [number, number] = [p.unbound, p.bound];
+          if (deskFacts.sameHeight.includes(p.unbound)) {
+            constraints.push({ kind: "sameHeight", monitors: pair });
+          }
+          if (deskFacts.touching.includes(p.unbound)) {
+            constraints.push({ kind: "touching", monitors: pair });
+          }
+        }
+      }
+      const options: CalibrationOptions = {
+        ...treeOptions,
+        measureAllAdjacent,
+        repeats,
+        gapAngleDeg,
+        matchedSquare: matchedSquare.filter(
+          (id) => selectedIds.includes(id) && id !== effectiveReference
+        ),
+        tape: tapeMeasurements,
+        constraints,
+      };
+      const result = await startCalibration(selectedIds, options);
+      setSession(result);
+      setCalibrationResults(result.results);
       setCalibrationStatus("complete");
       const freshMonitors = await discoverMonitors();
       setMonitors(freshMonitors);
@@ -67,11 +264,33 @@ export default function App() {
     }
   };
 
+  const handleRemeasure = async (index: number) => {
+    if (!session) return;
+    setCalibrationStatus("in_progress");
+    setError(null);
+    try {
+      const result = await remeasurePair(session, index);
+      setSession(result);
+      setCalibrationResults(result.results);
+    } catch (e) {
+      const msg = e instanceof Error ? e.message : String(e);
+      if (!msg.includes("cancelled")) {
+        setError(msg);
+      }
+    } finally {
+      setCalibrationStatus("complete");
+    }
+  };
+
   const [copyFeedback, setCopyFeedback] = useState(false);
 
   const handleCopyJson = async () => {
     try {
-      const json = await exportCalibrationJson(calibrationResults, includeVirtualLayout);
+      const json = await exportCalibrationJson(
+        calibrationResults,
+        { includeVirtualLayout, includeUncertainty },
+        selectedIds
+      );
       await navigator.clipboard.writeText(json);
       setError(null);
       setCopyFeedback(true);
@@ -85,7 +304,11 @@ export default function App() {
 
   const handleSaveFile = async () => {
     try {
-      const result = await saveCalibrationFile(calibrationResults, includeVirtualLayout);
+      const result = await saveCalibrationFile(
+        calibrationResults,
+        { includeVirtualLayout, includeUncertainty },
+        selectedIds
+      );
       if (result !== "cancelled") {
         setError(null);
       }
@@ -97,7 +320,12 @@ export default function App() {
   };
 
   const handleOpenSpanright = async () => {
-    const url = buildSpanrightUrl(monitors, calibrationResults, includeVirtualLayout);
+    const url = buildSpanrightUrl(
+      layoutMonitors,
+      calibrationResults,
+      includeVirtualLayout,
+      layoutIds
+    );
     if (!url) {
       setError("Could not build Spanright URL — ensure monitors have diagonal sizes set");
       return;
@@ -168,16 +396,57 @@ export default function App() {
 
       <div>
         <div className="section-title">Detected Monitors</div>
-        <MonitorList monitors={monitors} onRefresh={refresh} />
+        <MonitorList
+          monitors={monitors}
+          onRefresh={refresh}
+          selectedIds={selectedIds}
+          onToggleSelected={(id) =>
+            setSelectedIds((prev) => {
+              if (prev.includes(id)) return prev.filter((s) => s !== id);
+              // Only one member of a mirror group can be selected
+              const group = monitors[id]?.cloneGroup ?? null;
+              const others = prev.filter(
+                (s) => group === null || monitors[s]?.cloneGroup !== group
+              );
+              return [...others, id].sort((a, b) => a - b);
+            })
+          }
+        />
       </div>
 
       <CalibrationPanel
-        monitorCount={monitors.length}
+        monitorCount={selectedIds.length}
+        selectedIds={selectedIds}
+        reference={reference}
+        effectiveReference={effectiveReference}
+        onReferenceChange={setReference}
+        pairing={pairing}
+        onPairingChange={setPairing}
         status={calibrationStatus}
         results={calibrationResults}
         monitors={monitors}
         onCalibrate={handleCalibrate}
+        measureAllAdjacent={measureAllAdjacent}
+        onToggleMeasureAllAdjacent={() => setMeasureAllAdjacent((v) => !v)}
+        repeats={repeats}
+        onRepeatsChange={setRepeats}
+        gapAngleDeg={gapAngleDeg}
+        onGapAngleChange={setGapAngleDeg}
+        matchedSquare={matchedSquare}
+        onMatchedSquareChange={setMatchedSquare}
+        tape={tape}
+        onTapeChange={setTape}
+        tapeUnit={tapeUnit}
+        onTapeUnitChange={setTapeUnit}
+        deskFacts={deskFacts}
+        onDeskFactsChange={setDeskFacts}
+        calibrationPairs={calibrationPairs}
+        session={session}
+        sizeSuggestions={sizeSuggestions}
+        onApplyDiagonal={handleApplyDiagonal}
+        onRemeasure={handleRemeasure}
         onClear={() => {
+          setSession(null);
           setCalibrationResults([]);
           setCalibrationStatus("idle");
         }}
@@ -186,8 +455,10 @@ export default function App() {
       {calibrationStatus === "complete" && calibrationResults.length > 0 && (
         <>
           <PhysicalLayoutMap
-            monitors={monitors}
+            monitors={layoutMonitors}
             results={calibrationResults}
+            selectedIds={layoutIds}
+            onApplyDiagonal={handleApplyDiagonal}
           />
           <ExportPanel
             onCopyJson={handleCopyJson}
@@ -197,6 +468,11 @@ export default function App() {
             copied={copyFeedback}
             includeVirtualLayout={includeVirtualLayout}
             onToggleVirtualLayout={() => setIncludeVirtualLayout((v) => !v)}
+            includeUncertainty={includeUncertainty}
+            onToggleUncertainty={() => setIncludeUncertainty((v) => !v)}
+            showViewingDistance={monitors.some((m) => m.yawDeg != null)}
+            viewingDistanceMm={viewingDistanceMm}
+            onViewingDistanceChange={handleViewingDistanceChange}
           />
         </>
       )}
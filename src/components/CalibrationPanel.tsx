@@ -1,7 +1,77 @@
-import type { CalibrationResult, CalibrationStatus, Monitor } from "../types";
+import { useState } from "react";
+import type {
+  CalibrationPair,
+  CalibrationResult,
+  CalibrationSession,
+  CalibrationStatus,
+  Constraint,
+  DeskFacts,
+  EdgeResidual,
+  Monitor,
+  SizeSuggestion,
+  TapeEntry,
+} from "../types";
+import { MIN_TILT_DEG } from "../panelGeometry";
+
+const SIZE_SOURCE_LABELS: Record<Monitor["sizeSource"], string> = {
+  edid: "EDID",
+  guessed: "Estimated",
+  manual: "Entered",
+  reference: "Measured",
+  none: "Unknown",
+};
+
+// How a contradicted fact is off, from its signed error in mm
+function describeViolation(kind: Constraint["kind"], errorMm: number): string {
+  const mm = Math.abs(errorMm).toFixed(0);
+  switch (kind) {
+    case "bottomsLevel":
+      return `bottom edge ${mm} mm ${errorMm > 0 ? "lower" : "higher"}, not level`;
+    case "sameHeight":
+      return `centre ${mm} mm ${errorMm > 0 ? "lower" : "higher"}, not the same height`;
+    case "touching":
+      return errorMm > 0 ? `${mm} mm apart, not touching` : `housings overlap by ${mm} mm`;
+  }
+}
 
 interface Props {
+  // Number of monitors selected for calibration
   monitorCount: number;
+  selectedIds: number[];
+  // User-chosen reference monitor, or null for automatic
+  reference: number | null;
+  // Reference actually used: the chosen one or the automatic default
+  effectiveReference: number | null;
+  onReferenceChange: (id: number | null) => void;
+  // Bound monitor for each unbound one, or null for automatic pairing
+  pairing: Record<number, number> | null;
+  onPairingChange: (pairing: Record<number, number> | null) => void;
+  measureAllAdjacent: boolean;
+  onToggleMeasureAllAdjacent: () => void;
+  // Trials per scale and gap step
+  repeats: number;
+  onRepeatsChange: (repeats: number) => void;
+  // Angle of the gap-step lines against the shared edge
+  gapAngleDeg: number;
+  onGapAngleChange: (degrees: number) => void;
+  // Monitors compared with their pair by matched squares (scale only)
+  matchedSquare: number[];
+  onMatchedSquareChange: (ids: number[]) => void;
+  // Tape readings per unbound monitor, or null to measure every pair on screen
+  tape: Record<number, TapeEntry> | null;
+  onTapeChange: (tape: Record<number, TapeEntry> | null) => void;
+  tapeUnit: "mm" | "in";
+  onTapeUnitChange: (unit: "mm" | "in") => void;
+  // Known facts about the desk, or null to measure everything
+  deskFacts: DeskFacts | null;
+  onDeskFactsChange: (facts: DeskFacts | null) => void;
+  // Pairs the next run will measure
+  calibrationPairs: CalibrationPair[];
+  session: CalibrationSession | null;
+  // Monitors whose size the measured scales contradict
+  sizeSuggestions: SizeSuggestion[];
+  onApplyDiagonal: (id: number, diagonal: number) => void;
+  onRemeasure: (measurement: number) => void;
   status: CalibrationStatus;
   results: CalibrationResult[];
   monitors: Monitor[];
@@ -11,6 +81,31 @@ interface Props {
 
 export default function CalibrationPanel({
   monitorCount,
+  selectedIds,
+  reference,
+  effectiveReference,
+  onReferenceChange,
+  pairing,
+  onPairingChange,
+  measureAllAdjacent,
+  onToggleMeasureAllAdjacent,
+  repeats,
+  onRepeatsChange,
+  gapAngleDeg,
+  onGapAngleChange,
+  matchedSquare,
+  onMatchedSquareChange,
+  tape,
+  onTapeChange,
+  tapeUnit,
+  onTapeUnitChange,
+  deskFacts,
+  onDeskFactsChange,
+  calibrationPairs,
+  session,
+  sizeSuggestions,
+  onApplyDiagonal,
+  onRemeasure,
   status,
   results,
   monitors,
@@ -18,6 +113,7 @@ export default function CalibrationPanel({
   onClear,
 }: Props) {
   const canCalibrate = monitorCount >= 2 && status !== "in_progress";
+  const [showSizeOnly, setShowSizeOnly] = useState(matchedSquare.length > 0);
 
   const statusText: Record<CalibrationStatus, string> = {
     idle: "Not calibrated",
@@ -32,6 +128,53 @@ export default function CalibrationPanel({
     return m.friendlyName || m.monitorName || `Display ${id + 1}`;
   };
 
+  // Worst misplacement a pair's disagreement causes, in bound-monitor pixels:
+  // scale error counts over the length of the shared edge.
+  const residualPx = (r: EdgeResidual) => {
+    const measurement = session?.measurements[r.measurement];
+    const bound = monitors.find((m) => m.id === r.bound);
+    const edgeLength = !bound
+      ? 0
+      : measurement?.bindHorizontal
+        ? bound.resolutionY
+        : bound.resolutionX;
+    return Math.max(
+      Math.abs(r.offsetErrorPx),
+      Math.abs(r.gapErrorPx),
+      Math.abs(r.scaleError) * edgeLength
+    );
+  };
+
+  const measuredWithTape = (unbound: number) =>
+    session?.measurements.some((m) => m.inTree && m.unbound === unbound && m.method === "tape");
+  const treeMeasurement = (unbound: number) =>
+    session?.measurements.findIndex((m) => m.inTree && m.unbound === unbound) ?? -1;
+  const declaredSteps = (unbound: number) => {
+    const m = session?.measurements[treeMeasurement(unbound)];
+    if (!m || m.method === "tape") return null;
+    if (m.declaredOffset && m.declaredGap) return "Offset and gap from declared facts";
+    if (m.declaredOffset) return "Offset from declared facts and sizes";
+    if (m.declaredGap) return "Gap from declared facts (touching bezels)";
+    return null;
+  };
+  const toggleFact = (field: keyof DeskFacts, id: number) => {
+    if (!deskFacts) return;
+    const ids = deskFacts[field];
+    onDeskFactsChange({
+      ...deskFacts,
+      [field]: ids.includes(id) ? ids.filter((s) => s !== id) : [...ids, id],
+    });
+  };
+  const hasBezels = (id: number) => monitors.find((m) => m.id === id)?.bezelMm != null;
+
+  // Residuals only mean something when the layout is over-determined
+  const residuals =
+    session && session.residuals.some((r) => !r.inTree) ? session.residuals : [];
+  const worstResidual = residuals.reduce<EdgeResidual | null>(
+    (worst, r) => (worst === null || residualPx(r) > residualPx(worst) ? r : worst),
+    null
+  );
+
   return (
     <div className="calibration-panel">
       <div className="actions">
@@ -68,7 +211,252 @@ export default function CalibrationPanel({
 
       {monitorCount < 2 && (
         <div className="error-message">
-          At least 2 monitors are required for calibration.
+          Select at least 2 active monitors to calibrate.
+        </div>
+      )}
+
+      {monitorCount >= 2 && status !== "in_progress" && (
+        <div className="calibration-options">
+          <label className="calibration-option">
+            <span>Reference monitor</span>
+            <select
+              className="calibration-select"
+              value={reference !== null && selectedIds.includes(reference) ? reference : ""}
+              onChange={(e) =>
+                onReferenceChange(e.target.value === "" ? null : Number(e.target.value))
+              }
+            >
+              <option value="">Automatic (primary)</option>
+              {selectedIds.map((id) => (
+                <option key={id} value={id}>
+                  {getMonitorName(id)}
+                </option>
+              ))}
+            </select>
+          </label>
+          <label className="virtual-layout-toggle">
+            <input
+              type="checkbox"
+              checked={pairing !== null}
+              onChange={() => onPairingChange(pairing === null ? {} : null)}
+            />
+            <span>Choose calibration pairs manually</span>
+          </label>
+          <label className="virtual-layout-toggle">
+            <input
+              type="checkbox"
+              checked={measureAllAdjacent}
+              onChange={onToggleMeasureAllAdjacent}
+            />
+            <span>Measure every adjacent pair (more steps, errors don't add up)</span>
+          </label>
+          <label className="calibration-option">
+            <span>Repeat each step</span>
+            <select
+              className="calibration-select"
+              value={repeats}
+              onChange={(e) => onRepeatsChange(Number(e.target.value))}
+            >
+              <option value={1}>Once</option>
+              {/* Outliers can only be spotted with three or more trials */}
+              {[3, 5, 10].map((n) => (
+                <option key={n} value={n}>
+                  {n} times, averaged
+                </option>
+              ))}
+            </select>
+          </label>
+          <label className="calibration-option">
+            <span>Gap line angle</span>
+            <select
+              className="calibration-select"
+              value={gapAngleDeg}
+              onChange={(e) => onGapAngleChange(Number(e.target.value))}
+            >
+              <option value={45}>45° (1 px steps)</option>
+              {/* Each arrow press moves the lines 1px, i.e. tan(angle) px of gap */}
+              {[30, 20, 15].map((deg) => (
+                <option key={deg} value={deg}>
+                  {deg}° ({Math.tan((deg * Math.PI) / 180).toFixed(2)} px steps)
+                </option>
+              ))}
+            </select>
+          </label>
+          <label className="virtual-layout-toggle">
+            <input
+              type="checkbox"
+              checked={showSizeOnly}
+              onChange={() => {
+                if (showSizeOnly) onMatchedSquareChange([]);
+                setShowSizeOnly(!showSizeOnly);
+              }}
+            />
+            <span>Some monitors aren't next to each other (compare by size only)</span>
+          </label>
+          {showSizeOnly &&
+            effectiveReference !== null &&
+            selectedIds
+              .filter((id) => id !== effectiveReference)
+              .map((id) => (
+                <label className="virtual-layout-toggle" key={`square-${id}`}>
+                  <input
+                    type="checkbox"
+                    checked={matchedSquare.includes(id)}
+                    onChange={() =>
+                      onMatchedSquareChange(
+                        matchedSquare.includes(id)
+                          ? matchedSquare.filter((s) => s !== id)
+                          : [...matchedSquare, id]
+                      )
+                    }
+                  />
+                  {/* Matched squares give the scale but not the position */}
+                  <span>{getMonitorName(id)}: matched squares, keeps its Windows position</span>
+                </label>
+              ))}
+          <label className="virtual-layout-toggle">
+            <input
+              type="checkbox"
+              checked={tape !== null}
+              onChange={() => onTapeChange(tape === null ? {} : null)}
+            />
+            <span>Enter some pairs from a tape measure instead of on screen</span>
+          </label>
+          {tape !== null && (
+            <label className="calibration-option">
+              <span>Tape units</span>
+              <select
+                className="calibration-select"
+                value={tapeUnit}
+                onChange={(e) => onTapeUnitChange(e.target.value as "mm" | "in")}
+              >
+                <option value="mm">Millimetres</option>
+                <option value="in">Inches</option>
+              </select>
+            </label>
+          )}
+          {tape !== null &&
+            calibrationPairs.map((p) => {
+              const entry = tape[p.unbound] ?? { gap: "", offset: "" };
+              const update = (field: keyof TapeEntry, value: string) =>
+                onTapeChange({ ...tape, [p.unbound]: { ...entry, [field]: value } });
+              const edge = p.bindHorizontal ? "top edge lower" : "left edge further right";
+              return (
+                <div
+                  className="calibration-option"
+                  key={`tape-${p.unbound}`}
+                  title={
+                    "Measure between the edges of the lit screen areas. A negative gap means " +
+                    `they overlap; a negative offset means ${getMonitorName(p.unbound)}'s ` +
+                    `${p.bindHorizontal ? "top edge is higher" : "left edge is further left"}.`
+                  }
+                >
+                  <span>
+                    {getMonitorName(p.unbound)} ↔ {getMonitorName(p.bound)}: gap
+                  </span>
+                  <input
+                    className="diagonal-input tape-input"
+                    type="number"
+                    step="any"
+                    placeholder="on screen"
+                    value={entry.gap}
+                    onChange={(e) => update("gap", e.target.value)}
+                  />
+                  <span>{edge} by</span>
+                  <input
+                    className="diagonal-input tape-input"
+                    type="number"
+                    step="any"
+                    placeholder="0"
+                    value={entry.offset}
+                    onChange={(e) => update("offset", e.target.value)}
+                  />
+                  <span>{tapeUnit}</span>
+                </div>
+              );
+            })}
+          <label className="virtual-layout-toggle">
+            <input
+              type="checkbox"
+              checked={deskFacts !== null}
+              onChange={() =>
+                onDeskFactsChange(
+                  deskFacts === null ? { onDesk: [], sameHeight: [], touching: [] } : null
+                )
+              }
+            />
+            <span>Declare known facts about the desk (they settle offsets and gaps)</span>
+          </label>
+          {deskFacts !== null &&
+            selectedIds.map((id) => (
+              <label className="virtual-layout-toggle" key={`desk-${id}`}>
+                <input
+                  type="checkbox"
+                  checked={deskFacts.onDesk.includes(id)}
+                  onChange={() => toggleFact("onDesk", id)}
+                />
+                <span>{getMonitorName(id)} stands on the desk (bottom edges level)</span>
+              </label>
+            ))}
+          {deskFacts !== null &&
+            calibrationPairs.map((p) => (
+              <div className="calibration-option" key={`facts-${p.unbound}`}>
+                <span>
+                  {getMonitorName(p.unbound)} ↔ {getMonitorName(p.bound)}
+                </span>
+                {/* Only side-by-side monitors can share an arm at one height */}
+                {p.bindHorizontal && (
+                  <label className="virtual-layout-toggle">
+                    <input
+                      type="checkbox"
+                      checked={deskFacts.sameHeight.includes(p.unbound)}
+                      onChange={() => toggleFact("sameHeight", p.unbound)}
+                    />
+                    <span>same height on one arm</span>
+                  </label>
+                )}
+                <label
+                  className="virtual-layout-toggle"
+                  title={
+                    hasBezels(p.unbound) && hasBezels(p.bound)
+                      ? "No air between the housings: the gap is the two facing bezels"
+                      : "Enter both monitors' bezels to declare them touching"
+                  }
+                >
+                  <input
+                    type="checkbox"
+                    disabled={!hasBezels(p.unbound) || !hasBezels(p.bound)}
+                    checked={deskFacts.touching.includes(p.unbound)}
+                    onChange={() => toggleFact("touching", p.unbound)}
+                  />
+                  <span>bezels touching</span>
+                </label>
+              </div>
+            ))}
+          {pairing !== null &&
+            effectiveReference !== null &&
+            selectedIds
+              .filter((id) => id !== effectiveReference)
+              .map((id) => (
+                <label className="calibration-option" key={id}>
+                  <span>{getMonitorName(id)} measured against</span>
+                  <select
+                    className="calibration-select"
+                    value={pairing[id] ?? effectiveReference}
+                    onChange={(e) =>
+                      onPairingChange({ ...pairing, [id]: Number(e.target.value) })
+                    }
+                  >
+                    {selectedIds
+                      .filter((other) => other !== id)
+                      .map((other) => (
+                        <option key={other} value={other}>
+                          {getMonitorName(other)}
+                        </option>
+                      ))}
+                  </select>
+                </label>
+              ))}
         </div>
       )}
 
@@ -79,7 +467,7 @@ export default function CalibrationPanel({
             {(() => {
               const calibratedIds = new Set(results.map((r) => r.monitorId));
               const referenceMonitors = monitors.filter(
-                (m) => !calibratedIds.has(m.id)
+                (m) => selectedIds.includes(m.id) && !calibratedIds.has(m.id)
               );
               return referenceMonitors.map((m) => (
                 <div className="monitor-card" key={`ref-${m.id}`}>
@@ -106,16 +494,181 @@ export default function CalibrationPanel({
                 <div className="monitor-info">
                   <div className="monitor-name">
                     {getMonitorName(r.monitorId)}
+                    {measuredWithTape(r.monitorId) && (
+                      <span
+                        className="badge badge-edid"
+                        title="Gap and offset typed in from a tape measure"
+                      >
+                        Tape
+                      </span>
+                    )}
+                    {declaredSteps(r.monitorId) && (
+                      <span className="badge badge-edid" title={declaredSteps(r.monitorId)!}>
+                        Declared
+                      </span>
+                    )}
+                    {r.contact === "corner" && (
+                      <span
+                        className="badge badge-no-edid"
+                        title="These monitors only touch at a corner in Windows; check the result"
+                      >
+                        Corner only
+                      </span>
+                    )}
+                    {r.contact === "apart" && (
+                      <span
+                        className="badge badge-no-edid"
+                        title="These monitors don't touch in Windows; check the result"
+                      >
+                        Not adjacent
+                      </span>
+                    )}
                   </div>
                   <div className="monitor-details">
-                    <span>scale {r.scale.toFixed(3)}</span>
-                    <span>gap {r.gap}px</span>
+                    <span>
+                      scale {r.scale.toFixed(3)} ±{(r.scaleSigma * 100).toFixed(1)}%
+                    </span>
+                    <span>
+                      {r.scaleOnly
+                        ? "size only, position from Windows"
+                        : r.gap < 0
+                          ? `overlap ${(-r.gap).toFixed(2)}px`
+                          : `gap ${r.gap.toFixed(2)}px`}
+                    </span>
                     <span>
                       bound to {getMonitorName(r.boundTo)}
                       {r.bindHorizontal ? " (horizontal)" : " (vertical)"}
                     </span>
                   </div>
+                  {Math.abs(r.rotationDeg) >= MIN_TILT_DEG && (
+                    <div className="monitor-details">
+                      <span>
+                        tilted {Math.abs(r.rotationDeg).toFixed(2)}°{" "}
+                        {r.rotationDeg > 0 ? "clockwise" : "counter-clockwise"} against{" "}
+                        {getMonitorName(r.boundTo)}: raise its{" "}
+                        {r.rotationDeg > 0 ? "right" : "left"} side to level it
+                      </span>
+                    </div>
+                  )}
+                  {r.spread && (
+                    <div className="monitor-details">
+                      <span>
+                        {r.spread.trials} trials
+                        {r.spread.rejected > 0 && `, ${r.spread.rejected} dropped`}
+                      </span>
+                      <span>lines ±{r.spread.linePx.toFixed(1)}px</span>
+                      <span>gap ±{r.spread.gapPx.toFixed(1)}px</span>
+                    </div>
+                  )}
+                </div>
+              </div>
+            ))}
+          </div>
+        </div>
+      )}
+
+      {sizeSuggestions.length > 0 && (
+        <div>
+          <div className="section-title">Size Check</div>
+          <div className="monitor-list">
+            {sizeSuggestions.map((s, i) => (
+              <div className="monitor-card" key={s.monitorId}>
+                <div className="monitor-info">
+                  <div className="monitor-name">
+                    {getMonitorName(s.monitorId)}
+                    {i === 0 && <span className="badge badge-no-edid">Most likely wrong</span>}
+                  </div>
+                  <div className="monitor-details">
+                    <span>
+                      {SIZE_SOURCE_LABELS[s.sizeSource]} size {s.diagonalIn.toFixed(1)}" is{" "}
+                      {Math.abs(s.error * 100).toFixed(0)}% too{" "}
+                      {s.error > 0 ? "large" : "small"} for the measured scale
+                    </span>
+                    <span>should be {s.suggestedDiagonalIn.toFixed(1)}"</span>
+                  </div>
+                </div>
+                <button
+                  className="btn btn-secondary"
+                  disabled={status === "in_progress"}
+                  onClick={() => onApplyDiagonal(s.monitorId, s.suggestedDiagonalIn)}
+                >
+                  Use {s.suggestedDiagonalIn.toFixed(1)}"
+                </button>
+              </div>
+            ))}
+          </div>
+        </div>
+      )}
+
+      {session && session.violations.length > 0 && (
+        <div>
+          <div className="section-title">Declared Facts</div>
+          <div className="monitor-list">
+            {session.violations.map((v) => {
+              const kind = session.constraints[v.constraint]?.kind;
+              const redo = treeMeasurement(v.monitors[1]);
+              return (
+                <div className="monitor-card" key={`${v.constraint}-${v.monitors[1]}`}>
+                  <div className="monitor-info">
+                    <div className="monitor-name">
+                      {getMonitorName(v.monitors[0])} ↔ {getMonitorName(v.monitors[1])}
+                      <span className="badge badge-no-edid">Contradicted</span>
+                    </div>
+                    {kind && (
+                      <div className="monitor-details">
+                        <span>
+                          {getMonitorName(v.monitors[1])}: {describeViolation(kind, v.errorMm)}
+                        </span>
+                      </div>
+                    )}
+                  </div>
+                  {redo >= 0 && (
+                    <button
+                      className="btn btn-secondary"
+                      disabled={status === "in_progress"}
+                      onClick={() => onRemeasure(redo)}
+                    >
+                      Redo
+                    </button>
+                  )}
+                </div>
+              );
+            })}
+          </div>
+        </div>
+      )}
+
+      {residuals.length > 0 && (
+        <div>
+          <div className="section-title">Pair Consistency</div>
+          <div className="monitor-list">
+            {residuals.map((r) => (
+              <div className="monitor-card" key={r.measurement}>
+                <div className="monitor-info">
+                  <div className="monitor-name">
+                    {getMonitorName(r.unbound)} ↔ {getMonitorName(r.bound)}
+                    {r === worstResidual && (
+                      <span className="badge badge-no-edid">Least consistent</span>
+                    )}
+                  </div>
+                  <div className="monitor-details">
+                    <span>scale {(r.scaleError * 100).toFixed(2)}%</span>
+                    <span>offset {r.offsetErrorPx.toFixed(1)}px</span>
+                    <span>gap {r.gapErrorPx.toFixed(1)}px</span>
+                  </div>
                 </div>
+                <button
+                  className="btn btn-secondary"
+                  disabled={status === "in_progress"}
+                  onClick={() => onRemeasure(r.measurement)}
+                >
+                  {(() => {
+                    const m = session?.measurements[r.measurement];
+                    return m && (m.method === "tape" || m.declaredOffset || m.declaredGap)
+                      ? "Redo on screen"
+                      : "Redo";
+                  })()}
+                </button>
               </div>
             ))}
           </div>
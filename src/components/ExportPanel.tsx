@@ -1,3 +1,5 @@
+import { useEffect, useState } from "react";
+
 interface Props {
   onCopyJson: () => void;
   onSaveFile: () => void;
@@ -6,6 +8,12 @@ interface Props {
   copied: boolean;
   includeVirtualLayout: boolean;
   onToggleVirtualLayout: () => void;
+  includeUncertainty: boolean;
+  onToggleUncertainty: () => void;
+  // Only relevant once a monitor has a toe-in angle
+  showViewingDistance: boolean;
+  viewingDistanceMm: number | null;
+  onViewingDistanceChange: (mm: number | null) => void;
 }
 
 export default function ExportPanel({
@@ -16,7 +24,22 @@ export default function ExportPanel({
   copied,
   includeVirtualLayout,
   onToggleVirtualLayout,
+  includeUncertainty,
+  onToggleUncertainty,
+  showViewingDistance,
+  viewingDistanceMm,
+  onViewingDistanceChange,
 }: Props) {
+  const [distance, setDistance] = useState(viewingDistanceMm?.toString() ?? "");
+  useEffect(() => {
+    setDistance(viewingDistanceMm?.toString() ?? "");
+  }, [viewingDistanceMm]);
+
+  const commitDistance = () => {
+    const mm = parseFloat(distance);
+    onViewingDistanceChange(distance.trim() === "" || !mm ? null : mm);
+  };
+
   return (
     <div>
       <div className="section-title">Export</div>
@@ -28,6 +51,37 @@ export default function ExportPanel({
         />
         <span>Include virtual layout</span>
       </label>
+      <label className="virtual-layout-toggle">
+        <input
+          type="checkbox"
+          checked={includeUncertainty}
+          onChange={onToggleUncertainty}
+        />
+        <span>Include error bars (JSON only)</span>
+      </label>
+      {showViewingDistance && (
+        <label
+          className="calibration-option"
+          title="Distance from your eyes to the reference monitor. Adds true 3D positions and an eye-corrected flat layout to the JSON."
+        >
+          <span>Viewing distance for angled monitors</span>
+          <input
+            className="diagonal-input"
+            type="number"
+            min="100"
+            max="5000"
+            step="10"
+            placeholder="off"
+            value={distance}
+            onChange={(e) => setDistance(e.target.value)}
+            onBlur={commitDistance}
+            onKeyDown={(e) => {
+              if (e.key === "Enter") commitDistance();
+            }}
+          />
+          <span>mm</span>
+        </label>
+      )}
       <div className="actions">
         <button
           className="btn btn-accent"
@@ -13,7 +13,9 @@ const COLORS = [
   { bg: "rgba(80,200,200,0.18)", border: "#50c8c8", text: "#90dede" },
 ];
 
-export default function MonitorLayoutMap({ monitors }: Props) {
+export default function MonitorLayoutMap({ monitors: allMonitors }: Props) {
+  // Only displays on the desktop have a meaningful virtual position
+  const monitors = allMonitors.filter((m) => m.status === "active");
   if (monitors.length === 0) return null;
 
   const minX = Math.min(...monitors.map((m) => m.positionX));
@@ -1,7 +1,13 @@
 import { useState, useEffect } from "react";
-import type { Monitor } from "../types";
+import type { DisplayKind, Monitor, ReferenceObject } from "../types";
 import {
   setMonitorDiagonal,
+  measureOverscan,
+  setMonitorOverscan,
+  measureReferencePpi,
+  setMonitorYaw,
+  setMonitorCurvature,
+  setMonitorBezels,
   getMonitorInfo,
   type MonitorDetailInfo,
 } from "../hooks/useTauriCommands";
@@ -9,6 +15,8 @@ import {
 interface Props {
   monitors: Monitor[];
   onRefresh: () => void;
+  selectedIds: number[];
+  onToggleSelected: (id: number) => void;
 }
 
 function formatPpi(monitor: Monitor): string {
@@ -91,6 +99,368 @@ function DiagonalField({
   );
 }
 
+function OverscanField({
+  monitor,
+  onRefresh,
+}: {
+  monitor: Monitor;
+  onRefresh: () => void;
+}) {
+  const [editing, setEditing] = useState(false);
+  const [value, setValue] = useState("");
+  const [measuring, setMeasuring] = useState(false);
+
+  // Typed by hand; an empty field clears a wrong measurement back to 0%
+  const save = async () => {
+    const num = parseFloat(value);
+    setEditing(false);
+    if (Number.isNaN(num) && value.trim() !== "") return;
+    try {
+      await setMonitorOverscan(monitor.id, Number.isNaN(num) ? 0 : num);
+      onRefresh();
+    } catch (e) {
+      console.error(e);
+    }
+  };
+
+  const measure = async () => {
+    setEditing(false);
+    setMeasuring(true);
+    try {
+      await measureOverscan(monitor.id);
+      onRefresh();
+    } catch (e) {
+      console.error(e);
+    } finally {
+      setMeasuring(false);
+    }
+  };
+
+  if (editing) {
+    return (
+      <span className="diagonal-edit">
+        <input
+          className="diagonal-input"
+          type="number"
+          step="0.1"
+          min="0"
+          max="10"
+          value={value}
+          onChange={(e) => setValue(e.target.value)}
+          onKeyDown={(e) => {
+            if (e.key === "Enter") save();
+            if (e.key === "Escape") setEditing(false);
+          }}
+          onBlur={save}
+          autoFocus
+        />
+        %
+        <button
+          className="info-btn"
+          // Keep the input focused so its blur doesn't save first
+          onMouseDown={(e) => e.preventDefault()}
+          onClick={measure}
+          title="Measure TV overscan with on-screen edge markers"
+        >
+          measure
+        </button>
+      </span>
+    );
+  }
+
+  return (
+    <span
+      className="diagonal-display"
+      onClick={
+        measuring
+          ? undefined
+          : () => {
+              setValue(monitor.overscanPct?.toString() ?? "");
+              setEditing(true);
+            }
+      }
+      title="TV overscan: type the cropped border per side, or measure it with on-screen edge markers"
+    >
+      {monitor.overscanPct
+        ? `overscan ${monitor.overscanPct.toFixed(1)}%`
+        : "overscan?"}
+    </span>
+  );
+}
+
+const REFERENCE_OBJECTS: { label: string; object: ReferenceObject }[] = [
+  { label: "Credit card", object: { kind: "card" } },
+  { label: "A4 sheet", object: { kind: "a4" } },
+  { label: "Letter sheet", object: { kind: "letter" } },
+  { label: "Ruler, 10 cm", object: { kind: "ruler", lengthMm: 100 } },
+  { label: "Ruler, 20 cm", object: { kind: "ruler", lengthMm: 200 } },
+  { label: "Ruler, 4 in", object: { kind: "ruler", lengthMm: 101.6 } },
+  { label: "Ruler, 8 in", object: { kind: "ruler", lengthMm: 203.2 } },
+];
+
+// Measure the panel's density against a real object held to the screen, for
+// monitors whose size is unknown or wrong
+function ReferenceField({
+  monitor,
+  onRefresh,
+}: {
+  monitor: Monitor;
+  onRefresh: () => void;
+}) {
+  const [editing, setEditing] = useState(false);
+  const [measuring, setMeasuring] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+
+  const measure = async (index: string) => {
+    setEditing(false);
+    if (index === "") return;
+    setMeasuring(true);
+    setError(null);
+    try {
+      await measureReferencePpi(monitor.id, REFERENCE_OBJECTS[Number(index)].object);
+      onRefresh();
+    } catch (e) {
+      setError(String(e));
+    } finally {
+      setMeasuring(false);
+    }
+  };
+
+  if (editing) {
+    return (
+      <span className="diagonal-edit">
+        <select
+          className="diagonal-input curvature-select"
+          value=""
+          onChange={(e) => measure(e.target.value)}
+          onBlur={() => setEditing(false)}
+          autoFocus
+        >
+          <option value="">Measure with…</option>
+          {REFERENCE_OBJECTS.map((r, i) => (
+            <option key={r.label} value={i}>
+              {r.label}
+            </option>
+          ))}
+        </select>
+      </span>
+    );
+  }
+
+  return (
+    <span
+      className="diagonal-display"
+      onClick={measuring ? undefined : () => setEditing(true)}
+      title={
+        error ??
+        "Measure the size by resizing an outline over a card, a sheet of paper or a ruler held against the screen"
+      }
+    >
+      {measuring ? "measuring…" : "measure size"}
+    </span>
+  );
+}
+
+// Toe-in of an angled side monitor, for the viewer model in the export
+function YawField({
+  monitor,
+  onRefresh,
+}: {
+  monitor: Monitor;
+  onRefresh: () => void;
+}) {
+  const [editing, setEditing] = useState(false);
+  const [value, setValue] = useState("");
+
+  const save = async () => {
+    const num = parseFloat(value);
+    setEditing(false);
+    if (Number.isNaN(num) && value.trim() !== "") return;
+    try {
+      await setMonitorYaw(monitor.id, Number.isNaN(num) ? 0 : num);
+      onRefresh();
+    } catch (e) {
+      console.error(e);
+    }
+  };
+
+  if (editing) {
+    return (
+      <span className="diagonal-edit">
+        <input
+          className="diagonal-input"
+          type="number"
+          step="1"
+          min="-60"
+          max="60"
+          value={value}
+          onChange={(e) => setValue(e.target.value)}
+          onKeyDown={(e) => {
+            if (e.key === "Enter") save();
+            if (e.key === "Escape") setEditing(false);
+          }}
+          onBlur={save}
+          autoFocus
+        />
+        °
+      </span>
+    );
+  }
+
+  return (
+    <span
+      className="diagonal-display"
+      onClick={() => {
+        setValue(monitor.yawDeg?.toString() ?? "");
+        setEditing(true);
+      }}
+      title="Toe-in angle: positive if the monitor's right edge is turned away from you (a left-hand monitor angled inwards)"
+    >
+      {monitor.yawDeg ? `angled ${monitor.yawDeg}°` : "angle?"}
+    </span>
+  );
+}
+
+const CURVATURE_RADII = [800, 1000, 1500, 1800, 1900, 2300, 3000, 3800];
+
+// Curved panels take up their chord, not their width, in the physical layout
+function CurvatureField({
+  monitor,
+  onRefresh,
+}: {
+  monitor: Monitor;
+  onRefresh: () => void;
+}) {
+  const [editing, setEditing] = useState(false);
+
+  const save = async (value: string) => {
+    setEditing(false);
+    try {
+      await setMonitorCurvature(monitor.id, value === "" ? null : Number(value));
+      onRefresh();
+    } catch (e) {
+      console.error(e);
+    }
+  };
+
+  if (editing) {
+    const radius = monitor.curvatureRadiusMm;
+    const radii =
+      radius && !CURVATURE_RADII.includes(radius)
+        ? [...CURVATURE_RADII, radius].sort((a, b) => a - b)
+        : CURVATURE_RADII;
+    return (
+      <span className="diagonal-edit">
+        <select
+          className="diagonal-input curvature-select"
+          value={monitor.curvatureSource === "manual" ? radius ?? 0 : ""}
+          onChange={(e) => save(e.target.value)}
+          onBlur={() => setEditing(false)}
+          autoFocus
+        >
+          <option value="">From model</option>
+          <option value={0}>Flat</option>
+          {radii.map((r) => (
+            <option key={r} value={r}>
+              {r}R
+            </option>
+          ))}
+        </select>
+      </span>
+    );
+  }
+
+  return (
+    <span
+      className="diagonal-display"
+      onClick={() => setEditing(true)}
+      title={
+        monitor.curvatureSource === "model"
+          ? "Curvature from the model database; click to change"
+          : "Curvature radius of a curved panel, e.g. 1800R"
+      }
+    >
+      {monitor.curvatureRadiusMm ? `${monitor.curvatureRadiusMm}R` : "curve?"}
+    </span>
+  );
+}
+
+function formatBezels(b: [number, number, number, number]): string {
+  return b.every((v) => v === b[0]) ? `${b[0]}` : b.join("/");
+}
+
+// Housing widths around the lit area, so the measured gap can be split into
+// bezels and air. One number sets all four sides; four set top, bottom,
+// left and right; empty goes back to the model database.
+function BezelField({
+  monitor,
+  onRefresh,
+}: {
+  monitor: Monitor;
+  onRefresh: () => void;
+}) {
+  const [editing, setEditing] = useState(false);
+  const [value, setValue] = useState("");
+
+  const save = async () => {
+    setEditing(false);
+    const nums = value
+      .split(/[\s,\/]+/)
+      .filter((s) => s !== "")
+      .map(Number);
+    if (nums.some((n) => Number.isNaN(n) || n < 0 || n > 100)) return;
+    let bezels: [number, number, number, number] | null;
+    if (nums.length === 0) bezels = null;
+    else if (nums.length === 1) bezels = [nums[0]!, nums[0]!, nums[0]!, nums[0]!];
+    else if (nums.length === 4) bezels = [nums[0]!, nums[1]!, nums[2]!, nums[3]!];
+    else return;
+    try {
+      await setMonitorBezels(monitor.id, bezels);
+      onRefresh();
+    } catch (e) {
+      console.error(e);
+    }
+  };
+
+  if (editing) {
+    return (
+      <span className="diagonal-edit">
+        <input
+          className="diagonal-input bezel-input"
+          type="text"
+          placeholder="t b l r"
+          value={value}
+          onChange={(e) => setValue(e.target.value)}
+          onKeyDown={(e) => {
+            if (e.key === "Enter") save();
+            if (e.key === "Escape") setEditing(false);
+          }}
+          onBlur={save}
+          autoFocus
+        />
+        mm
+      </span>
+    );
+  }
+
+  return (
+    <span
+      className="diagonal-display"
+      onClick={() => {
+        setValue(monitor.bezelMm ? monitor.bezelMm.join(" ") : "");
+        setEditing(true);
+      }}
+      title={
+        monitor.bezelSource === "model"
+          ? "Bezels from the model database (top, bottom, left, right); click to change"
+          : "Bezel widths in mm: one value for all sides, or top, bottom, left, right"
+      }
+    >
+      {monitor.bezelMm ? `bezel ${formatBezels(monitor.bezelMm)} mm` : "bezel?"}
+    </span>
+  );
+}
+
 const MANUFACTURER_NAMES: Record<string, string> = {
   ACI: "ASUS",
   ACR: "Acer",
@@ -284,6 +654,14 @@ function MonitorInfoPopup({
               label="Position"
               value={`(${monitor.positionX}, ${monitor.positionY})`}
             />
+            <InfoRow
+              label="Scaling"
+              value={
+                monitor.scaleFactor != null && monitor.effectiveDpi != null
+                  ? `${Math.round(monitor.scaleFactor * 100)}% (${monitor.effectiveDpi} DPI)`
+                  : null
+              }
+            />
             <InfoRow
               label="PPI"
               value={monitor.ppi ? `${Math.round(monitor.ppi)}` : null}
@@ -299,7 +677,25 @@ function MonitorInfoPopup({
   );
 }
 
-export default function MonitorList({ monitors, onRefresh }: Props) {
+const KIND_LABELS: Record<Exclude<DisplayKind, "physical">, string> = {
+  virtual: "Virtual",
+  wireless: "Wireless",
+  remote: "Remote",
+};
+
+function formatLastSeen(lastSeen: number | null): string {
+  if (lastSeen == null) return "Disconnected";
+  const days = Math.floor((Date.now() - lastSeen) / 86_400_000);
+  if (days <= 0) return "Disconnected today";
+  return `Disconnected ${days}d ago`;
+}
+
+export default function MonitorList({
+  monitors,
+  onRefresh,
+  selectedIds,
+  onToggleSelected,
+}: Props) {
   const [infoMonitor, setInfoMonitor] = useState<Monitor | null>(null);
 
   if (monitors.length === 0) {
@@ -315,7 +711,22 @@ export default function MonitorList({ monitors, onRefresh }: Props) {
           const ppi = formatPpi(m);
 
           return (
-            <div className="monitor-card" key={m.id}>
+            <div
+              className={`monitor-card${m.status === "active" ? "" : " monitor-card-inactive"}`}
+              key={m.id}
+            >
+              <input
+                type="checkbox"
+                className="monitor-select"
+                checked={selectedIds.includes(m.id)}
+                disabled={m.status !== "active"}
+                onChange={() => onToggleSelected(m.id)}
+                title={
+                  m.status === "active"
+                    ? "Include in calibration and export"
+                    : "Only displays on the desktop can be calibrated"
+                }
+              />
               <div className="monitor-icon">🖥</div>
               <div className="monitor-info">
                 <div className="monitor-name">
@@ -323,10 +734,45 @@ export default function MonitorList({ monitors, onRefresh }: Props) {
                   {m.isPrimary && (
                     <span className="badge badge-primary">Primary</span>
                   )}
+                  {m.cloneGroup !== null && (
+                    <span
+                      className="badge badge-edid"
+                      title="Shows the same image as another display; select the one to calibrate and export"
+                    >
+                      Mirrors{" "}
+                      {monitors
+                        .filter((o) => o.cloneGroup === m.cloneGroup && o.id !== m.id)
+                        .map((o) => `Display ${o.id + 1}`)
+                        .join(", ")}
+                    </span>
+                  )}
+                  {m.kind !== "physical" && (
+                    <span
+                      className="badge badge-no-edid"
+                      title="No physical panel; excluded from calibration unless selected"
+                    >
+                      {KIND_LABELS[m.kind]}
+                    </span>
+                  )}
+                  {m.status === "inactive" && (
+                    <span className="badge badge-no-edid">Disabled</span>
+                  )}
+                  {m.status === "disconnected" && (
+                    <span className="badge badge-no-edid">
+                      {formatLastSeen(m.lastSeen)}
+                    </span>
+                  )}
                   {m.sizeSource === "edid" ? (
                     <span className="badge badge-edid">EDID</span>
                   ) : m.sizeSource === "manual" ? (
                     <span className="badge badge-edid">Manual</span>
+                  ) : m.sizeSource === "reference" ? (
+                    <span
+                      className="badge badge-edid"
+                      title="Measured against an object held to the screen"
+                    >
+                      Measured
+                    </span>
                   ) : m.sizeSource === "guessed" ? (
                     <span className="badge badge-no-edid">Estimated</span>
                   ) : (
@@ -346,6 +792,11 @@ export default function MonitorList({ monitors, onRefresh }: Props) {
                     {m.resolutionX}×{m.resolutionY}
                   </span>
                   {ppi && <span>{ppi}</span>}
+                  <ReferenceField monitor={m} onRefresh={onRefresh} />
+                  <OverscanField monitor={m} onRefresh={onRefresh} />
+                  <YawField monitor={m} onRefresh={onRefresh} />
+                  <CurvatureField monitor={m} onRefresh={onRefresh} />
+                  <BezelField monitor={m} onRefresh={onRefresh} />
                   <span>
                     ({m.positionX}, {m.positionY})
                   </span>
@@ -1,8 +1,18 @@
+import { useState } from "react";
 import type { CalibrationResult, Monitor } from "../types";
+import {
+  effectivePpi,
+  flattenOffset,
+  panelCurve,
+  panelSize,
+  tiltAboutSeam,
+} from "../panelGeometry";
 
 interface Props {
   monitors: Monitor[];
   results: CalibrationResult[];
+  selectedIds: number[];
+  onApplyDiagonal: (monitorId: number, diagonal: number) => void;
 }
 
 interface PhysicalRect {
@@ -13,6 +23,14 @@ interface PhysicalRect {
   w: number;
   h: number;
   diagonal: string;
+  // One-sigma uncertainty of x, y, w, h in inches
+  sigma: [number, number, number, number];
+  // In-plane tilt about the centre, degrees clockwise
+  tilt: number;
+  // Housing around the lit area in inches: top, bottom, left, right
+  bezel: [number, number, number, number] | null;
+  // False when only the size was measured and the position is Windows'
+  positioned: boolean;
 }
 
 const COLORS = [
@@ -24,67 +42,100 @@ const COLORS = [
   { bg: "rgba(80,200,200,0.22)", border: "#50c8c8", text: "#90dede" },
 ];
 
+// The uncalibrated monitor everything else is placed against
+function findReference(
+  monitors: Monitor[],
+  results: CalibrationResult[],
+  selectedIds: number[]
+): Monitor | undefined {
+  const calibratedIds = new Set(results.map((r) => r.monitorId));
+  return monitors.find((m) => selectedIds.includes(m.id) && !calibratedIds.has(m.id));
+}
+
+// PPI of every monitor the calibration chain reaches. With no size known
+// anywhere the reference monitor is given 1 "PPI", so the layout comes out
+// in its pixels and `relative` is set.
 function derivePpi(
   monitors: Monitor[],
-  results: CalibrationResult[]
-): Map<number, number> {
+  results: CalibrationResult[],
+  selectedIds: number[]
+): { ppiMap: Map<number, number>; relative: boolean } {
   const ppiMap = new Map<number, number>();
 
   for (const m of monitors) {
-    if (m.ppi != null) ppiMap.set(m.id, m.ppi);
+    if (m.ppi != null) ppiMap.set(m.id, effectivePpi(m.ppi, m.overscanPct));
   }
 
-  // Propagate PPI through calibration chain using scale ratios
+  propagatePpi(ppiMap, results);
+  const ref = findReference(monitors, results, selectedIds);
+  if (!ref || ppiMap.has(ref.id)) return { ppiMap, relative: false };
+  ppiMap.set(ref.id, 1);
+  propagatePpi(ppiMap, results);
+  return { ppiMap, relative: true };
+}
+
+// Propagate PPI through calibration chain using scale ratios
+function propagatePpi(ppiMap: Map<number, number>, results: CalibrationResult[]) {
+  const scaleOf = new Map(results.map((r) => [r.monitorId, r.scale]));
   let changed = true;
   while (changed) {
     changed = false;
     for (const r of results) {
+      // Scales are relative to the reference monitor, not the bound one
+      const ratio = r.scale / (scaleOf.get(r.boundTo) ?? 1);
       if (!ppiMap.has(r.monitorId) && ppiMap.has(r.boundTo)) {
-        ppiMap.set(r.monitorId, ppiMap.get(r.boundTo)! * r.scale);
+        ppiMap.set(r.monitorId, ppiMap.get(r.boundTo)! * ratio);
         changed = true;
       }
       if (!ppiMap.has(r.boundTo) && ppiMap.has(r.monitorId)) {
-        ppiMap.set(r.boundTo, ppiMap.get(r.monitorId)! / r.scale);
+        ppiMap.set(r.boundTo, ppiMap.get(r.monitorId)! / ratio);
         changed = true;
       }
     }
   }
-
-  return ppiMap;
 }
 
 function buildPhysicalLayout(
   monitors: Monitor[],
-  results: CalibrationResult[]
+  results: CalibrationResult[],
+  selectedIds: number[]
 ): PhysicalRect[] | null {
-  const ppiMap = derivePpi(monitors, results);
+  const { ppiMap, relative } = derivePpi(monitors, results, selectedIds);
   if (ppiMap.size === 0) return null;
+  // Curvature and bezels are in real units, which a relative layout lacks
+  const curveOf = (m: Monitor, ppi: number) => (relative ? null : panelCurve(m, ppi));
 
   const rects = new Map<number, PhysicalRect>();
 
   for (const m of monitors) {
     const ppi = ppiMap.get(m.id);
     if (!ppi) continue;
+    const diagonal = Math.hypot(m.resolutionX / ppi, m.resolutionY / ppi);
     rects.set(m.id, {
       id: m.id,
       name: m.friendlyName || m.monitorName || `Display ${m.id + 1}`,
       x: 0,
       y: 0,
-      w: m.resolutionX / ppi,
-      h: m.resolutionY / ppi,
-      diagonal: `${Math.sqrt(
-        (m.resolutionX / ppi) ** 2 + (m.resolutionY / ppi) ** 2
-      ).toFixed(1)}"`,
+      ...(relative ? { w: m.resolutionX / ppi, h: m.resolutionY / ppi } : panelSize(m, ppi)),
+      diagonal: relative ? `${diagonal.toFixed(0)} px` : `${diagonal.toFixed(1)}"`,
+      sigma: [0, 0, 0, 0],
+      tilt: 0,
+      positioned: true,
+      bezel:
+        m.bezelMm && !relative
+          ? [m.bezelMm[0] / 25.4, m.bezelMm[1] / 25.4, m.bezelMm[2] / 25.4, m.bezelMm[3] / 25.4]
+          : null,
     });
   }
 
   // Identify reference monitor (not in results as monitorId)
-  const calibratedIds = new Set(results.map((r) => r.monitorId));
-  const refMon = monitors.find((m) => !calibratedIds.has(m.id) && rects.has(m.id));
-  if (!refMon) return null;
+  const refMon = findReference(monitors, results, selectedIds);
+  if (!refMon || !rects.has(refMon.id)) return null;
 
   // Reference starts at (0, 0) — already set
   const placed = new Set<number>([refMon.id]);
+  // Position uncertainties are in reference-monitor pixels
+  const ppiRef = ppiMap.get(refMon.id)!;
 
   // Place calibrated monitors relative to their bound monitors
   // Process in order, since earlier results bind to already-placed monitors
@@ -98,48 +149,172 @@ function buildPhysicalLayout(
     const mBound = monitors.find((m) => m.id === r.boundTo)!;
     const ppiBound = ppiMap.get(r.boundTo)!;
     const ppiUnbound = ppiMap.get(r.monitorId)!;
+    // Alignment offsets run along each panel's surface, which for a curved
+    // panel is longer than its footprint
+    const alongUnbound = (vertical: boolean) =>
+      flattenOffset(curveOf(mUnbound, ppiUnbound), vertical, r.alignOffsetUnbound / ppiUnbound);
+    const offsetInches = (vertical: boolean) =>
+      flattenOffset(curveOf(mBound, ppiBound), vertical, r.alignOffsetBound / ppiBound) -
+      alongUnbound(vertical);
+    // Edge point of the unbound monitor measured at the seam
+    let pivot: [number, number] | null = null;
 
-    if (r.bindHorizontal) {
-      const gapInches = Math.abs(r.gap) / ppiBound;
+    if (r.scaleOnly) {
+      // Only the size is known; keep the Windows arrangement relative to the
+      // bound monitor, at the bound monitor's density
+      current.x = bound.x + (mUnbound.positionX - mBound.positionX) / ppiBound;
+      current.y = bound.y + (mUnbound.positionY - mBound.positionY) / ppiBound;
+      current.positioned = false;
+    } else if (r.bindHorizontal) {
+      const gapInches = r.gap / ppiBound;
       // Correct physical vertical offset: the alignment midpoints are at
       // the same physical height, so:
       // topUnbound = topBound + alignBound/ppiBound - alignUnbound/ppiUnbound
-      const offsetInches =
-        r.alignOffsetBound / ppiBound - r.alignOffsetUnbound / ppiUnbound;
 
       if (mUnbound.positionX < mBound.positionX) {
         current.x = bound.x - current.w - gapInches;
       } else {
         current.x = bound.x + bound.w + gapInches;
       }
-      current.y = bound.y + offsetInches;
+      current.y = bound.y + offsetInches(true);
+      const unboundLeft = mUnbound.positionX < mBound.positionX;
+      pivot = [unboundLeft ? current.x + current.w : current.x, current.y + alongUnbound(true)];
     } else {
-      const gapInches = Math.abs(r.gap) / ppiBound;
-      const offsetInches =
-        r.alignOffsetBound / ppiBound - r.alignOffsetUnbound / ppiUnbound;
+      const gapInches = r.gap / ppiBound;
 
       if (mUnbound.positionY < mBound.positionY) {
         current.y = bound.y - current.h - gapInches;
       } else {
         current.y = bound.y + bound.h + gapInches;
       }
-      current.x = bound.x + offsetInches;
+      current.x = bound.x + offsetInches(false);
+      const unboundAbove = mUnbound.positionY < mBound.positionY;
+      pivot = [current.x + alongUnbound(false), unboundAbove ? current.y + current.h : current.y];
+    }
+    if (pivot) {
+      const boundCenter: [number, number] = [bound.x + bound.w / 2, bound.y + bound.h / 2];
+      Object.assign(current, tiltAboutSeam(current, pivot, r.rotationDeg, boundCenter, bound.tilt));
     }
 
+    current.sigma = [
+      r.positionSigma[0] / ppiRef,
+      r.positionSigma[1] / ppiRef,
+      current.w * r.scaleSigma,
+      current.h * r.scaleSigma,
+    ];
+    current.tilt = bound.tilt + r.rotationDeg;
     placed.add(r.monitorId);
   }
 
   return Array.from(rects.values()).filter((r) => placed.has(r.id));
 }
 
-export default function PhysicalLayoutMap({ monitors, results }: Props) {
-  const layout = buildPhysicalLayout(monitors, results);
+// Regions lit by two monitors at once (edge-blended projectors, or a monitor
+// partly in front of another), as x, y, w, h in inches
+function findOverlaps(layout: PhysicalRect[]): [number, number, number, number][] {
+  const overlaps: [number, number, number, number][] = [];
+  // An unmeasured position would report overlaps that aren't there
+  const positioned = layout.filter((r) => r.positioned);
+  positioned.forEach((a, i) => {
+    for (const b of positioned.slice(i + 1)) {
+      const x = Math.max(a.x, b.x);
+      const y = Math.max(a.y, b.y);
+      const w = Math.min(a.x + a.w, b.x + b.w) - x;
+      const h = Math.min(a.y + a.h, b.y + b.h) - y;
+      if (w >= 0.01 && h >= 0.01) overlaps.push([x, y, w, h]);
+    }
+  });
+  return overlaps;
+}
+
+// Without any known size the layout is in reference pixels; one real
+// dimension of any placed monitor fixes the scale of all of them.
+function RescaleForm({
+  layout,
+  monitors,
+  onApplyDiagonal,
+}: {
+  layout: PhysicalRect[];
+  monitors: Monitor[];
+  onApplyDiagonal: (monitorId: number, diagonal: number) => void;
+}) {
+  const [monitorId, setMonitorId] = useState(layout[0]!.id);
+  const [dimension, setDimension] = useState<"diagonal" | "width">("diagonal");
+  const [value, setValue] = useState("");
+
+  const apply = () => {
+    const m = monitors.find((mon) => mon.id === monitorId);
+    const inches = parseFloat(value);
+    if (!m || !(inches > 0)) return;
+    // The width runs along the monitor's desktop pixel rows
+    const diagonal =
+      dimension === "diagonal"
+        ? inches
+        : (inches * Math.hypot(m.resolutionX, m.resolutionY)) / m.resolutionX;
+    onApplyDiagonal(monitorId, diagonal);
+  };
+
+  return (
+    <div className="monitor-details">
+      <span>No monitor size is known, so lengths are in reference-monitor pixels. Enter one:</span>
+      <span className="diagonal-edit">
+        <select
+          className="diagonal-input curvature-select"
+          value={monitorId}
+          onChange={(e) => setMonitorId(Number(e.target.value))}
+        >
+          {layout.map((r) => (
+            <option key={r.id} value={r.id}>
+              {r.id + 1}. {r.name}
+            </option>
+          ))}
+        </select>
+        <select
+          className="diagonal-input curvature-select"
+          value={dimension}
+          onChange={(e) => setDimension(e.target.value as "diagonal" | "width")}
+        >
+          <option value="diagonal">Diagonal</option>
+          <option value="width">Width</option>
+        </select>
+        <input
+          className="diagonal-input"
+          type="number"
+          step="0.1"
+          min="1"
+          max="200"
+          placeholder="inches"
+          value={value}
+          onChange={(e) => setValue(e.target.value)}
+          onKeyDown={(e) => {
+            if (e.key === "Enter") apply();
+          }}
+        />
+        <button className="btn btn-secondary" onClick={apply}>
+          Apply
+        </button>
+      </span>
+    </div>
+  );
+}
+
+export default function PhysicalLayoutMap({
+  monitors,
+  results,
+  selectedIds,
+  onApplyDiagonal,
+}: Props) {
+  const layout = buildPhysicalLayout(monitors, results, selectedIds);
   if (!layout || layout.length === 0) return null;
+  const { ppiMap, relative } = derivePpi(monitors, results, selectedIds);
+  // Lengths are inches, or reference pixels when no size is known
+  const formatLength = (v: number) => (relative ? `${v.toFixed(0)} px` : `${v.toFixed(2)}"`);
+  const smallest = relative ? 0.5 : 0.01;
 
-  const minX = Math.min(...layout.map((r) => r.x));
-  const minY = Math.min(...layout.map((r) => r.y));
-  const maxX = Math.max(...layout.map((r) => r.x + r.w));
-  const maxY = Math.max(...layout.map((r) => r.y + r.h));
+  const minX = Math.min(...layout.map((r) => r.x - (r.bezel?.[2] ?? 0)));
+  const minY = Math.min(...layout.map((r) => r.y - (r.bezel?.[0] ?? 0)));
+  const maxX = Math.max(...layout.map((r) => r.x + r.w + (r.bezel?.[3] ?? 0)));
+  const maxY = Math.max(...layout.map((r) => r.y + r.h + (r.bezel?.[1] ?? 0)));
   const totalW = maxX - minX;
   const totalH = maxY - minY;
 
@@ -172,9 +347,41 @@ export default function PhysicalLayoutMap({ monitors, results }: Props) {
             const color = COLORS[r.id % COLORS.length]!;
             const fontSize = Math.min(w * 0.12, h * 0.22, 14);
             const numSize = Math.min(w * 0.25, h * 0.4, 30);
+            const [sx, sy] = r.sigma;
+            const sigmaIn = Math.max(sx, sy);
+            const sigmaLabel = relative
+              ? ` ±${formatLength(sigmaIn)}`
+              : ` ±${sigmaIn.toFixed(2)}" (±${(sigmaIn * 25.4).toFixed(1)} mm)`;
 
             return (
-              <g key={r.id}>
+              <g key={r.id} transform={`rotate(${r.tilt} ${x + w / 2} ${y + h / 2})`}>
+                {r.bezel && (
+                  <rect
+                    x={x - r.bezel[2] * pxPerInch}
+                    y={y - r.bezel[0] * pxPerInch}
+                    width={w + (r.bezel[2] + r.bezel[3]) * pxPerInch}
+                    height={h + (r.bezel[0] + r.bezel[1]) * pxPerInch}
+                    rx={4}
+                    fill="rgba(0,0,0,0.35)"
+                    stroke={color.border}
+                    strokeWidth={1}
+                    opacity={0.6}
+                  />
+                )}
+                {sigmaIn >= smallest && (
+                  <rect
+                    x={x - sx * pxPerInch}
+                    y={y - sy * pxPerInch}
+                    width={w + 2 * sx * pxPerInch}
+                    height={h + 2 * sy * pxPerInch}
+                    rx={3}
+                    fill="none"
+                    stroke={color.border}
+                    strokeWidth={1}
+                    strokeDasharray="2,2"
+                    opacity={0.5}
+                  />
+                )}
                 <rect
                   x={x}
                   y={y}
@@ -184,7 +391,10 @@ export default function PhysicalLayoutMap({ monitors, results }: Props) {
                   fill={color.bg}
                   stroke={color.border}
                   strokeWidth={1.5}
-                />
+                  strokeDasharray={r.positioned ? undefined : "6,3"}
+                >
+                  {!r.positioned && <title>Position not measured (size only)</title>}
+                </rect>
                 <text
                   x={x + w / 2}
                   y={y + h / 2 - fontSize * 0.6}
@@ -218,20 +428,37 @@ export default function PhysicalLayoutMap({ monitors, results }: Props) {
                   opacity={0.6}
                 >
                   {r.diagonal}
+                  {sigmaIn >= smallest && sigmaLabel}
                 </text>
               </g>
             );
           })}
 
+          {findOverlaps(layout).map(([ox, oy, ow, oh], i) => (
+            <rect
+              key={`overlap-${i}`}
+              x={(ox - minX) * pxPerInch + padding}
+              y={(oy - minY) * pxPerInch + padding}
+              width={ow * pxPerInch}
+              height={oh * pxPerInch}
+              fill="rgba(255,255,255,0.12)"
+              stroke="#e8eaed"
+              strokeWidth={1}
+              strokeDasharray="4,3"
+            >
+              <title>{`Overlap ${formatLength(Math.min(ow, oh))} wide`}</title>
+            </rect>
+          ))}
+
           {/* Gap annotations between adjacent monitors */}
           {results.map((r) => {
             const a = layout.find((l) => l.id === r.boundTo);
             const b = layout.find((l) => l.id === r.monitorId);
             if (!a || !b) return null;
-            const ppiBound = monitors.find((m) => m.id === r.boundTo)?.ppi;
+            const ppiBound = ppiMap.get(r.boundTo);
             if (!ppiBound) return null;
-            const gapInches = Math.abs(r.gap) / ppiBound;
-            if (gapInches < 0.05) return null;
+            const gapInches = r.gap / ppiBound;
+            if (Math.abs(gapInches) < (relative ? 1 : 0.05)) return null;
 
             if (r.bindHorizontal) {
               const leftRect = a.x < b.x ? a : b;
@@ -244,6 +471,11 @@ export default function PhysicalLayoutMap({ monitors, results }: Props) {
                 (b.y - minY) * pxPerInch + padding
               );
               const annotY = topEdge - 6;
+              // Air between the housings, when both frames are known
+              const air =
+                leftRect.bezel && rightRect.bezel
+                  ? gapInches - leftRect.bezel[3] - rightRect.bezel[2]
+                  : null;
 
               return (
                 <g key={`gap-${r.monitorId}`} opacity={0.6}>
@@ -279,7 +511,10 @@ export default function PhysicalLayoutMap({ monitors, results }: Props) {
                     fill="#9aa0a6"
                     fontSize={9}
                   >
-                    {gapInches.toFixed(2)}"
+                    {gapInches < 0
+                      ? `overlap ${formatLength(-gapInches)}`
+                      : formatLength(gapInches)}
+                    {air != null && ` (air ${formatLength(air)})`}
                   </text>
                 </g>
               );
@@ -288,6 +523,9 @@ export default function PhysicalLayoutMap({ monitors, results }: Props) {
           })}
         </svg>
       </div>
+      {relative && (
+        <RescaleForm layout={layout} monitors={monitors} onApplyDiagonal={onApplyDiagonal} />
+      )}
     </div>
   );
 }
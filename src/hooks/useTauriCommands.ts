@@ -1,12 +1,44 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { CalibrationResult, Monitor } from "../types";
+import type {
+  CalibrationOptions,
+  CalibrationPair,
+  CalibrationResult,
+  CalibrationSession,
+  ExportOptions,
+  Monitor,
+  ReferenceObject,
+  SizeSuggestion,
+} from "../types";
 
 export async function discoverMonitors(): Promise<Monitor[]> {
   return invoke<Monitor[]>("discover_monitors");
 }
 
-export async function startCalibration(): Promise<CalibrationResult[]> {
-  return invoke<CalibrationResult[]>("start_calibration");
+export async function startCalibration(
+  selected?: number[],
+  options?: CalibrationOptions
+): Promise<CalibrationSession> {
+  return invoke<CalibrationSession>("start_calibration", { selected, options });
+}
+
+export async function getCalibrationPairs(
+  selected?: number[],
+  options?: CalibrationOptions
+): Promise<CalibrationPair[]> {
+  return invoke<CalibrationPair[]>("get_calibration_pairs", { selected, options });
+}
+
+export async function checkMonitorSizes(
+  session: CalibrationSession
+): Promise<SizeSuggestion[]> {
+  return invoke<SizeSuggestion[]>("check_monitor_sizes", { session });
+}
+
+export async function remeasurePair(
+  session: CalibrationSession,
+  index: number
+): Promise<CalibrationSession> {
+  return invoke<CalibrationSession>("remeasure_pair", { session, index });
 }
 
 export async function setMonitorDiagonal(
@@ -16,18 +48,69 @@ export async function setMonitorDiagonal(
   return invoke<void>("set_monitor_diagonal", { id, diagonal });
 }
 
+export async function setMonitorOverscan(
+  id: number,
+  percent: number
+): Promise<void> {
+  return invoke<void>("set_monitor_overscan", { id, percent });
+}
+
+export async function measureOverscan(id: number): Promise<number> {
+  return invoke<number>("measure_overscan", { id });
+}
+
+// Returns the measured pixels per inch, which replaces the EDID size
+export async function measureReferencePpi(
+  id: number,
+  object: ReferenceObject
+): Promise<number> {
+  return invoke<number>("measure_reference_ppi", { id, object });
+}
+
+// 0 clears the angle
+export async function setMonitorYaw(id: number, degrees: number): Promise<void> {
+  return invoke<void>("set_monitor_yaw", { id, degrees });
+}
+
+// 0 marks the panel flat; null goes back to the model database
+export async function setMonitorCurvature(
+  id: number,
+  radiusMm: number | null
+): Promise<void> {
+  return invoke<void>("set_monitor_curvature", { id, radiusMm });
+}
+
+// Top, bottom, left, right in mm; null goes back to the model database
+export async function setMonitorBezels(
+  id: number,
+  bezelsMm: [number, number, number, number] | null
+): Promise<void> {
+  return invoke<void>("set_monitor_bezels", { id, bezelsMm });
+}
+
+// null turns the viewer model off
+export async function setViewingDistance(millimetres: number | null): Promise<void> {
+  return invoke<void>("set_viewing_distance", { millimetres });
+}
+
+export async function getViewingDistance(): Promise<number | null> {
+  return invoke<number | null>("get_viewing_distance");
+}
+
 export async function exportCalibrationJson(
   results: CalibrationResult[],
-  includeVirtualLayout = false
+  options: ExportOptions = {},
+  selected?: number[]
 ): Promise<string> {
-  return invoke<string>("export_calibration_json", { results, includeVirtualLayout });
+  return invoke<string>("export_calibration_json", { results, options, selected });
 }
 
 export async function saveCalibrationFile(
   results: CalibrationResult[],
-  includeVirtualLayout = false
+  options: ExportOptions = {},
+  selected?: number[]
 ): Promise<string> {
-  return invoke<string>("save_calibration_file", { results, includeVirtualLayout });
+  return invoke<string>("save_calibration_file", { results, options, selected });
 }
 
 export async function openUrl(url: string): Promise<void> {
@@ -0,0 +1,81 @@
+import type { Monitor } from "./types";
+
+// A curved panel spreads its pixels evenly along the arc, so on a flat plan
+// it takes up only its chord. Mirrors export/curvature.rs.
+export interface PanelCurve {
+  radius: number;
+  arc: number;
+  chord: number;
+  // True when the curve runs down the desktop (portrait mounting)
+  vertical: boolean;
+}
+
+export function panelCurve(m: Monitor, ppi: number): PanelCurve | null {
+  if (!m.curvatureRadiusMm) return null;
+  const radius = m.curvatureRadiusMm / 25.4;
+  const vertical =
+    m.orientation === "portrait" || m.orientation === "portraitFlipped";
+  const arc = (vertical ? m.resolutionY : m.resolutionX) / ppi;
+  if (arc >= Math.PI * radius) return null;
+  return { radius, arc, chord: 2 * radius * Math.sin(arc / (2 * radius)), vertical };
+}
+
+// Overscan stretches the visible image over the panel, so each desktop pixel
+// is physically larger than the panel PPI implies. Mirrors
+// Monitor::effective_ppi.
+export function effectivePpi(ppi: number, overscanPct: number | null): number {
+  return overscanPct ? ppi * (1 - (2 * overscanPct) / 100) : ppi;
+}
+
+// Footprint in inches, with a curved side replaced by its chord
+export function panelSize(m: Monitor, ppi: number): { w: number; h: number } {
+  const w = m.resolutionX / ppi;
+  const h = m.resolutionY / ppi;
+  const curve = panelCurve(m, ppi);
+  if (!curve) return { w, h };
+  return curve.vertical ? { w, h: curve.chord } : { w: curve.chord, h };
+}
+
+// Flat-plan offset of a point `s` inches along the panel surface from its
+// top (vertical) or left edge
+export function flattenOffset(
+  curve: PanelCurve | null,
+  vertical: boolean,
+  s: number
+): number {
+  if (!curve || curve.vertical !== vertical) return s;
+  return curve.chord / 2 + curve.radius * Math.sin((s - curve.arc / 2) / curve.radius);
+}
+
+// Tilts below this are within measurement noise and shown as level. Mirrors
+// MIN_TILT_DEG in export/mod.rs.
+export const MIN_TILT_DEG = 0.1;
+
+// Rotate `v` clockwise by `deg` degrees, with y pointing down
+function rotate([x, y]: [number, number], deg: number): [number, number] {
+  const a = (deg * Math.PI) / 180;
+  return [x * Math.cos(a) - y * Math.sin(a), x * Math.sin(a) + y * Math.cos(a)];
+}
+
+// Top-left corner of a monitor laid out untilted next to its bound monitor,
+// with its tilt kept as a turn about its centre. The centre moves so that
+// `pivot`, the edge point measured at the seam, stays on the seam; then the
+// monitor is carried into the bound one's frame, itself turned by
+// `boundTilt` about `boundCenter`. Mirrors compute_physical_placements.
+export function tiltAboutSeam(
+  rect: { x: number; y: number; w: number; h: number },
+  pivot: [number, number],
+  tilt: number,
+  boundCenter: [number, number],
+  boundTilt: number
+): { x: number; y: number } {
+  const toCenter = rotate([rect.x + rect.w / 2 - pivot[0], rect.y + rect.h / 2 - pivot[1]], tilt);
+  const fromBound = rotate(
+    [pivot[0] + toCenter[0] - boundCenter[0], pivot[1] + toCenter[1] - boundCenter[1]],
+    boundTilt
+  );
+  return {
+    x: boundCenter[0] + fromBound[0] - rect.w / 2,
+    y: boundCenter[1] + fromBound[1] - rect.h / 2,
+  };
+}
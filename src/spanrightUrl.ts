@@ -1,5 +1,13 @@
 import LZString from "lz-string";
-import type { CalibrationResult, Monitor } from "./types";
+import type { CalibrationResult, Monitor, Orientation } from "./types";
+import {
+  MIN_TILT_DEG,
+  effectivePpi,
+  flattenOffset,
+  panelCurve,
+  panelSize,
+  tiltAboutSeam,
+} from "./panelGeometry";
 
 const LAYOUT_ENCODING_LZ_PREFIX = "~";
 
@@ -11,13 +19,22 @@ interface UrlMonitor {
   ry: number;
   x: number;
   y: number;
-  rot?: 90;
+  rot?: 90 | 180 | 270;
+  // Measured in-plane tilt, degrees clockwise
+  tl?: number;
   dn?: string;
+  os?: number;
+  // Curvature radius, mm
+  cr?: number;
+  // Bezels (top, bottom, left, right), mm
+  bz?: [number, number, number, number];
 }
 
+// Physical (unscaled) pixel coordinates, with the monitor's display scaling
 interface UrlWindowsPosition {
   px: number;
   py: number;
+  sf?: number;
 }
 
 interface UrlLayout {
@@ -48,18 +65,21 @@ function derivePpi(
 ): Map<number, number> {
   const ppiMap = new Map<number, number>();
   for (const m of monitors) {
-    if (m.ppi != null) ppiMap.set(m.id, m.ppi);
+    if (m.ppi != null) ppiMap.set(m.id, effectivePpi(m.ppi, m.overscanPct));
   }
+  const scaleOf = new Map(results.map((r) => [r.monitorId, r.scale]));
   let changed = true;
   while (changed) {
     changed = false;
     for (const r of results) {
+      // Scales are relative to the reference monitor, not the bound one
+      const ratio = r.scale / (scaleOf.get(r.boundTo) ?? 1);
       if (!ppiMap.has(r.monitorId) && ppiMap.has(r.boundTo)) {
-        ppiMap.set(r.monitorId, ppiMap.get(r.boundTo)! * r.scale);
+        ppiMap.set(r.monitorId, ppiMap.get(r.boundTo)! * ratio);
         changed = true;
       }
       if (!ppiMap.has(r.boundTo) && ppiMap.has(r.monitorId)) {
-        ppiMap.set(r.boundTo, ppiMap.get(r.monitorId)! / r.scale);
+        ppiMap.set(r.boundTo, ppiMap.get(r.monitorId)! / ratio);
         changed = true;
       }
     }
@@ -67,6 +87,13 @@ function derivePpi(
   return ppiMap;
 }
 
+const ROTATION_DEGREES: Record<Orientation, 0 | 90 | 180 | 270> = {
+  landscape: 0,
+  portrait: 90,
+  landscapeFlipped: 180,
+  portraitFlipped: 270,
+};
+
 function formatResolution(rx: number, ry: number): string {
   const map: Record<string, string> = {
     "1920x1080": "FHD",
@@ -86,20 +113,22 @@ export const SPANRIGHT_BASE_URL =
 export function buildSpanrightUrl(
   monitors: Monitor[],
   results: CalibrationResult[],
-  includeVirtualLayout = false
+  includeVirtualLayout = false,
+  selectedIds: number[] = monitors.map((m) => m.id)
 ): string | null {
   const ppiMap = derivePpi(monitors, results);
   if (ppiMap.size === 0) return null;
 
   // Build physical placements (same logic as PhysicalLayoutMap)
-  const positions = new Map<number, { x: number; y: number }>();
+  const positions = new Map<number, { x: number; y: number; tilt: number }>();
   const calibratedIds = new Set(results.map((r) => r.monitorId));
   const refMon = monitors.find(
-    (m) => !calibratedIds.has(m.id) && ppiMap.has(m.id)
+    (m) =>
+      selectedIds.includes(m.id) && !calibratedIds.has(m.id) && ppiMap.has(m.id)
   );
   if (!refMon) return null;
 
-  positions.set(refMon.id, { x: 0, y: 0 });
+  positions.set(refMon.id, { x: 0, y: 0, tilt: 0 });
 
   for (const r of results) {
     if (!positions.has(r.boundTo)) continue;
@@ -108,32 +137,42 @@ export function buildSpanrightUrl(
     const bound = positions.get(r.boundTo)!;
     const mUnbound = monitors.find((m) => m.id === r.monitorId)!;
     const mBound = monitors.find((m) => m.id === r.boundTo)!;
-    const w = mUnbound.resolutionX / ppiUnbound;
-    const h = mUnbound.resolutionY / ppiUnbound;
+    const { w, h } = panelSize(mUnbound, ppiUnbound);
+    const boundSize = panelSize(mBound, ppiBound);
+    const alongUnbound = (vertical: boolean) =>
+      flattenOffset(panelCurve(mUnbound, ppiUnbound), vertical, r.alignOffsetUnbound / ppiUnbound);
+    const offsetIn = (vertical: boolean) =>
+      flattenOffset(panelCurve(mBound, ppiBound), vertical, r.alignOffsetBound / ppiBound) -
+      alongUnbound(vertical);
 
     let x: number, y: number;
-    if (r.bindHorizontal) {
-      const gapIn = Math.abs(r.gap) / ppiBound;
-      const offsetIn =
-        r.alignOffsetBound / ppiBound - r.alignOffsetUnbound / ppiUnbound;
-      const boundW = mBound.resolutionX / ppiBound;
+    let pivot: [number, number] | null = null;
+    if (r.scaleOnly) {
+      // Position not measured: keep the Windows arrangement
+      x = bound.x + (mUnbound.positionX - mBound.positionX) / ppiBound;
+      y = bound.y + (mUnbound.positionY - mBound.positionY) / ppiBound;
+    } else if (r.bindHorizontal) {
+      const gapIn = r.gap / ppiBound;
       x =
         mUnbound.positionX < mBound.positionX
           ? bound.x - w - gapIn
-          : bound.x + boundW + gapIn;
-      y = bound.y + offsetIn;
+          : bound.x + boundSize.w + gapIn;
+      y = bound.y + offsetIn(true);
+      pivot = [mUnbound.positionX < mBound.positionX ? x + w : x, y + alongUnbound(true)];
     } else {
-      const gapIn = Math.abs(r.gap) / ppiBound;
-      const offsetIn =
-        r.alignOffsetBound / ppiBound - r.alignOffsetUnbound / ppiUnbound;
-      const boundH = mBound.resolutionY / ppiBound;
+      const gapIn = r.gap / ppiBound;
       y =
         mUnbound.positionY < mBound.positionY
           ? bound.y - h - gapIn
-          : bound.y + boundH + gapIn;
-      x = bound.x + offsetIn;
+          : bound.y + boundSize.h + gapIn;
+      x = bound.x + offsetIn(false);
+      pivot = [x + alongUnbound(false), mUnbound.positionY < mBound.positionY ? y + h : y];
+    }
+    if (pivot) {
+      const boundCenter: [number, number] = [bound.x + boundSize.w / 2, bound.y + boundSize.h / 2];
+      ({ x, y } = tiltAboutSeam({ x, y, w, h }, pivot, r.rotationDeg, boundCenter, bound.tilt));
     }
-    positions.set(r.monitorId, { x, y });
+    positions.set(r.monitorId, { x, y, tilt: bound.tilt + r.rotationDeg });
   }
 
   // Center on Spanright's 144"×96" canvas
@@ -141,7 +180,7 @@ export function buildSpanrightUrl(
     .map(([id, pos]) => {
       const ppi = ppiMap.get(id)!;
       const m = monitors.find((mon) => mon.id === id)!;
-      return { id, ...pos, w: m.resolutionX / ppi, h: m.resolutionY / ppi };
+      return { id, ...pos, ...panelSize(m, ppi) };
     });
 
   if (placed.length === 0) return null;
@@ -170,8 +209,13 @@ export function buildSpanrightUrl(
       x: round4(p.x + offsetX),
       y: round4(p.y + offsetY),
     };
-    if (m.orientation === 1) entry.rot = 90;
+    const rot = ROTATION_DEGREES[m.orientation];
+    if (rot !== 0) entry.rot = rot;
+    if (Math.abs(p.tilt) >= MIN_TILT_DEG) entry.tl = Math.round(p.tilt * 100) / 100;
     if (m.friendlyName) entry.dn = m.friendlyName;
+    if (m.overscanPct) entry.os = Math.round(m.overscanPct * 100) / 100;
+    if (m.curvatureRadiusMm) entry.cr = m.curvatureRadiusMm;
+    if (m.bezelMm) entry.bz = m.bezelMm;
     return entry;
   });
 
@@ -180,7 +224,11 @@ export function buildSpanrightUrl(
   if (includeVirtualLayout) {
     layout.wp = placed.map((p) => {
       const m = monitors.find((mon) => mon.id === p.id)!;
-      return { px: m.positionX, py: m.positionY };
+      const pos: UrlWindowsPosition = { px: m.positionX, py: m.positionY };
+      if (m.scaleFactor != null && m.scaleFactor !== 1) {
+        pos.sf = Math.round(m.scaleFactor * 100) / 100;
+      }
+      return pos;
     });
   }
 
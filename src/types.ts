@@ -1,3 +1,13 @@
+export type Orientation =
+  | "landscape"
+  | "portrait"
+  | "landscapeFlipped"
+  | "portraitFlipped";
+
+export type MonitorStatus = "active" | "inactive" | "disconnected";
+
+export type DisplayKind = "physical" | "virtual" | "wireless" | "remote";
+
 export interface Monitor {
   id: number;
   deviceName: string;
@@ -6,30 +16,208 @@ export interface Monitor {
   adapterName: string;
   monitorDeviceId: string;
   isPrimary: boolean;
+  status: MonitorStatus;
+  kind: DisplayKind;
+  cloneGroup: number | null;
+  lastSeen: number | null;
   resolutionX: number;
   resolutionY: number;
   positionX: number;
   positionY: number;
-  orientation: number;
+  orientation: Orientation;
+  effectiveDpi: number | null;
+  scaleFactor: number | null;
   physicalWidthMm: number | null;
   physicalHeightMm: number | null;
   physicalWidthIn: number | null;
   physicalHeightIn: number | null;
   diagonalIn: number | null;
   ppi: number | null;
-  sizeSource: "edid" | "guessed" | "manual" | "none";
+  // "reference": measured against an object held to the screen
+  sizeSource: "edid" | "guessed" | "manual" | "reference" | "none";
+  overscanPct: number | null;
+  // Toe-in about the vertical axis; positive turns the right edge away
+  yawDeg: number | null;
+  // Radius of a curved panel ("1800R" is 1800); null for flat panels
+  curvatureRadiusMm: number | null;
+  curvatureSource: "model" | "manual" | "none";
+  // Housing around the lit area in mm, as on the desk: top, bottom, left, right
+  bezelMm: [number, number, number, number] | null;
+  bezelSource: "model" | "manual" | "none";
 }
 
+// How a calibrated pair meets in the Windows layout
+export type Contact = "edge" | "corner" | "apart";
+
 export interface CalibrationResult {
   monitorId: number;
   scale: number;
   relativeX: number;
   relativeY: number;
+  // Pixels of the bound monitor; may be fractional, negative where the
+  // monitors overlap
   gap: number;
   boundTo: number;
   bindHorizontal: boolean;
+  contact: Contact;
   alignOffsetUnbound: number;
   alignOffsetBound: number;
+  // One-sigma uncertainty of scale, as a fraction (0.01 = 1%)
+  scaleSigma: number;
+  // One-sigma uncertainty of the top-left corner, in reference-monitor pixels
+  positionSigma: [number, number];
+  // In-plane rotation relative to the bound monitor, degrees clockwise
+  rotationDeg: number;
+  // How much repeated trials disagreed; null when measured once
+  spread: PairSpread | null;
+  // Only the scale was measured (matched squares); position fields are zero
+  scaleOnly: boolean;
+}
+
+export interface PairSpread {
+  trials: number;
+  // Trials dropped as outliers across both steps
+  rejected: number;
+  // Standard deviation of one trial's line placement and gap, in pixels
+  linePx: number;
+  gapPx: number;
+}
+
+// What to include in the export beyond the physical layout
+export interface ExportOptions {
+  includeVirtualLayout?: boolean;
+  // One-sigma error bars (JSON only)
+  includeUncertainty?: boolean;
+}
+
+// Optional choices for a calibration run; omitted fields use the automatic order
+export interface CalibrationOptions {
+  reference?: number;
+  // [unbound, bound] pairs forming a tree rooted at the reference
+  pairs?: [number, number][];
+  // Also measure adjacent pairs outside the tree and solve globally
+  measureAllAdjacent?: boolean;
+  // Assumed placement error of one dragged line, in pixels
+  lineSigmaPx?: number;
+  // Run each step this many times and average, dropping outliers
+  repeats?: number;
+  // Angle of the gap lines against the shared edge; below 45° reads sub-pixel
+  gapAngleDeg?: number;
+  // Monitors compared by matched squares (scale only) instead of lines
+  matchedSquare?: number[];
+  // Tree pairs typed in from a tape measure instead of measured on screen
+  tape?: TapeMeasurement[];
+  // Known facts about the desk; steps they fix are skipped
+  constraints?: Constraint[];
+}
+
+// A fact about the desk declared before calibration
+export type Constraint =
+  // Housing bottoms level, e.g. standing on one desk
+  | { kind: "bottomsLevel"; monitors: number[] }
+  // Centres level, e.g. on one dual arm
+  | { kind: "sameHeight"; monitors: number[] }
+  // Housings touching; needs both monitors' bezels
+  | { kind: "touching"; monitors: [number, number] };
+
+// Desk facts as ticked in the calibration panel
+export interface DeskFacts {
+  // Monitors standing on the desk, so their bottom edges are level
+  onDesk: number[];
+  // Unbound monitors of pairs sharing a dual arm at the same height
+  sameHeight: number[];
+  // Unbound monitors of pairs whose housings touch
+  touching: number[];
+}
+
+// A declared fact the calibration contradicts
+export interface ConstraintViolation {
+  // Index into the session's constraints
+  constraint: number;
+  monitors: [number, number];
+  // Second monitor's bottom or centre below the first's, or the air
+  // between touching housings
+  errorMm: number;
+}
+
+// Between the edges of the two lit areas, in mm
+export interface TapeMeasurement {
+  unbound: number;
+  // Negative where the monitors overlap
+  gapMm: number;
+  // Unbound top edge below the bound one's (side by side), or left edge
+  // right of it (stacked)
+  offsetMm: number;
+}
+
+// Tape readings for one pair as typed, in the chosen unit
+export interface TapeEntry {
+  // Blank measures the pair on screen
+  gap: string;
+  // Blank counts as level
+  offset: string;
+}
+
+// One pair a calibration run measures
+export interface CalibrationPair {
+  unbound: number;
+  bound: number;
+  bindHorizontal: boolean;
+  contact: Contact;
+}
+
+export type PairMethod = "lines" | "matchedSquare" | "tape";
+
+// Raw scale/gap step result for one pair, kept so the pair can be redone
+export interface PairMeasurement {
+  unbound: number;
+  bound: number;
+  bindHorizontal: boolean;
+  contact: Contact;
+  inTree: boolean;
+  lineOffsets: [number, number, number, number];
+  // Gap level with the blue and the red line
+  gaps: [number, number];
+  lineSigmaPx: number | null;
+  spread: PairSpread | null;
+  method: PairMethod;
+  // Lines or gaps that follow from declared facts instead of being measured
+  declaredOffset: boolean;
+  declaredGap: boolean;
+}
+
+// A monitor whose size disagrees with the measured scales
+export interface SizeSuggestion {
+  monitorId: number;
+  diagonalIn: number;
+  // Diagonal that agrees with the calibration and the other monitors' sizes
+  suggestedDiagonalIn: number;
+  // Current over suggested, minus one (0.1 = 10% too large)
+  error: number;
+  sizeSource: Monitor["sizeSource"];
+}
+
+// How far one measured pair is from the solved layout
+export interface EdgeResidual {
+  measurement: number;
+  unbound: number;
+  bound: number;
+  inTree: boolean;
+  scaleError: number;
+  offsetErrorPx: number;
+  gapErrorPx: number;
+}
+
+export interface CalibrationSession {
+  reference: number;
+  lineSigmaPx: number;
+  repeats: number;
+  gapAngleDeg: number;
+  results: CalibrationResult[];
+  measurements: PairMeasurement[];
+  residuals: EdgeResidual[];
+  constraints: Constraint[];
+  violations: ConstraintViolation[];
 }
 
 export type CalibrationStatus = "idle" | "in_progress" | "complete" | "error";
@@ -41,3 +229,10 @@ export interface AppState {
   error: string | null;
   loading: boolean;
 }
+
+// Object of known size held against a screen to measure its pixel density
+export type ReferenceObject =
+  | { kind: "card" }
+  | { kind: "a4" }
+  | { kind: "letter" }
+  | { kind: "ruler"; lengthMm: number };